@@ -0,0 +1,141 @@
+// Move generation speed of Game against CompactGame
+//
+//     cargo bench --bench movegen
+//
+// Plays a handful of random games, then times generating every move in each position they pass
+// through, and making and taking back each of those moves, with both representations.
+
+#![allow(clippy::needless_return)]
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use boudica::game::compact::CompactGame;
+use boudica::game::Game;
+
+const GAMES: u64 = 20;
+const MOVES_PER_GAME: usize = 60;
+const ROUNDS: usize = 5;
+
+// Small LCG so the positions are the same on every run
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, n: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        return ((self.0 >> 33) as usize) % n;
+    }
+}
+
+fn positions() -> Vec<Game> {
+    let mut positions: Vec<Game> = Vec::new();
+    for seed in 0..GAMES {
+        let mut rng = Lcg(seed);
+        let mut game = Game::new();
+        for _ in 0..MOVES_PER_GAME {
+            let moves = game.get_all_moves();
+            if moves.is_empty() {
+                break;
+            }
+            positions.push(game.clone());
+            game.make_move(moves[rng.next(moves.len())].clone())
+                .expect("Generated moves can be made");
+        }
+    }
+    return positions;
+}
+
+fn report(name: &str, game_time: Duration, compact_time: Duration, count: usize) {
+    let per_second = |t: Duration| count as f64 / t.as_secs_f64();
+    println!(
+        "{:<16} Game {:>12.0}/s   CompactGame {:>12.0}/s   {:>6.1}x",
+        name,
+        per_second(game_time),
+        per_second(compact_time),
+        game_time.as_secs_f64() / compact_time.as_secs_f64()
+    );
+}
+
+fn main() {
+    let games = positions();
+    let compacts: Vec<CompactGame> = games.iter().map(CompactGame::from).collect();
+    println!("{} positions", games.len());
+
+    // Generating moves
+    let start = Instant::now();
+    let mut move_count = 0;
+    for _ in 0..ROUNDS {
+        for game in &games {
+            move_count += black_box(game.get_all_moves()).len();
+        }
+    }
+    let game_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut compact_count = 0;
+    let mut moves = Vec::new();
+    for _ in 0..ROUNDS {
+        for compact in &compacts {
+            compact.generate_moves(&mut moves);
+            compact_count += black_box(&moves).len();
+        }
+    }
+    let compact_time = start.elapsed();
+    assert_eq!(move_count, compact_count);
+    report(
+        "get_all_moves",
+        game_time,
+        compact_time,
+        games.len() * ROUNDS,
+    );
+
+    // Making and taking back every move
+    let start = Instant::now();
+    let mut made = 0;
+    for game in &games {
+        let mut game = game.clone();
+        for m in game.get_all_moves() {
+            for _ in 0..ROUNDS {
+                game.make_move(m.clone())
+                    .expect("Generated moves can be made");
+                game.umake_move(m.clone()).expect("Moves can be taken back");
+                made += 1;
+            }
+        }
+    }
+    let game_time = start.elapsed();
+
+    let start = Instant::now();
+    for compact in &compacts {
+        let mut compact = compact.clone();
+        for m in compact.get_all_moves() {
+            for _ in 0..ROUNDS {
+                compact.make_move(m);
+                compact.umake_move(m);
+            }
+        }
+    }
+    let compact_time = start.elapsed();
+    report("make/umake", game_time, compact_time, made);
+
+    // Copying positions
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for game in &games {
+            black_box(game.clone());
+        }
+    }
+    let game_time = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for compact in &compacts {
+            black_box(compact.clone());
+        }
+    }
+    let compact_time = start.elapsed();
+    report("clone", game_time, compact_time, games.len() * ROUNDS);
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::game::compact::{piece_move, CompactGame};
+use crate::game::piece_move::PieceMove;
+use crate::game::Game;
+use crate::rng::Rng;
+
+use self::book::OpeningBook;
+use self::eval::Evaluator;
+use self::search::{search, search_lines, SearchType};
+
+pub mod book;
+pub mod eval;
+pub mod search;
+pub mod selfplay;
+pub mod stats;
+pub mod surround;
+
+// What an engine makes of a position: its best moves in order, each with its score and the line
+// it expects to follow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub depth: usize,
+    // Positions visited
+    pub nodes: u64,
+    pub time: Duration,
+    pub lines: Vec<AnalysisLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisLine {
+    // From the point of view of the player to move
+    pub score: i32,
+    // Starts with the move itself
+    pub pv: Vec<PieceMove>,
+}
+
+impl AnalysisLine {
+    pub fn piece_move(&self) -> &PieceMove {
+        return &self.pv[0];
+    }
+}
+
+// A computer player: how it searches, how it scores positions and how many moves it looks ahead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EngineConfig {
+    pub search: SearchType,
+    pub evaluator: Evaluator,
+    pub depth: usize,
+    // How many moves ahead to look for a forced queen surround at the end of the search (0 for
+    // not at all)
+    pub surround: usize,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        return EngineConfig {
+            search: SearchType::AlphaBeta,
+            evaluator: Evaluator::Mobility,
+            depth: 2,
+            surround: 0,
+        };
+    }
+}
+
+impl EngineConfig {
+    // Written search:evaluator:depth:surround, e.g. `alphabeta:mobility:3:1`. Trailing parts can be
+    // left off to get the defaults, so `random` and `minimax:queen` work too.
+    pub fn parse(s: &str) -> Option<EngineConfig> {
+        let mut config = EngineConfig::default();
+        let mut parts = s.split(':');
+        config.search = SearchType::parse(parts.next()?)?;
+        if let Some(evaluator) = parts.next() {
+            config.evaluator = Evaluator::parse(evaluator)?;
+        }
+        if let Some(depth) = parts.next() {
+            // Searches always look at least one move ahead
+            config.depth = depth.parse().ok().filter(|&d| d > 0)?;
+        }
+        if let Some(surround) = parts.next() {
+            config.surround = surround.parse().ok()?;
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        return Some(config);
+    }
+
+    // The move this engine plays in game, or None if the game is over
+    pub fn choose_move(&self, game: &Game, rng: &mut Rng) -> Option<PieceMove> {
+        let mut compact = CompactGame::from(game);
+        let result = search(
+            &mut compact,
+            self.search,
+            self.evaluator,
+            self.depth,
+            self.surround,
+            rng,
+        )?;
+        return Some(
+            piece_move(game, result.best_move).expect("Searched moves are for pieces in the game"),
+        );
+    }
+
+    // Up to multi_pv of the best moves in game, or None if the game is over
+    pub fn analyze(&self, game: &Game, multi_pv: usize, rng: &mut Rng) -> Option<Analysis> {
+        let mut compact = CompactGame::from(game);
+        let result = search_lines(
+            &mut compact,
+            self.search,
+            self.evaluator,
+            self.depth,
+            self.surround,
+            multi_pv,
+            rng,
+        )?;
+        let lines = result
+            .lines
+            .iter()
+            .map(|line| {
+                let mut replay = game.clone();
+                let mut pv: Vec<PieceMove> = Vec::new();
+                for &m in &line.pv {
+                    let m =
+                        piece_move(&replay, m).expect("Searched moves are for pieces in the game");
+                    replay
+                        .make_move(m.clone())
+                        .expect("Searched moves can be made");
+                    pv.push(m);
+                }
+                return AnalysisLine {
+                    score: line.score,
+                    pv,
+                };
+            })
+            .collect();
+        return Some(Analysis {
+            depth: result.depth,
+            nodes: result.nodes,
+            time: result.time,
+            lines,
+        });
+    }
+
+    // Plays a move from book while game is still in it, and searches as usual after that
+    pub fn choose_move_with_book(
+        &self,
+        game: &Game,
+        book: &OpeningBook,
+        rng: &mut Rng,
+    ) -> Option<PieceMove> {
+        if let Some(book_move) = book.choose_move(game, rng) {
+            return Some(book_move);
+        }
+        return self.choose_move(game, rng);
+    }
+}
+
+impl fmt::Display for EngineConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.search == SearchType::Random {
+            return write!(f, "{}", self.search);
+        }
+        write!(f, "{}:{}:{}", self.search, self.evaluator, self.depth)?;
+        if self.surround > 0 {
+            write!(f, ":{}", self.surround)?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_round_trips_and_rejects_depth_zero() {
+        for s in ["random", "minimax:queen:3", "alphabeta:mobility:2:1"] {
+            assert_eq!(EngineConfig::parse(s).unwrap().to_string(), s);
+        }
+        assert_eq!(EngineConfig::parse("alphabeta:mobility:0"), None);
+        assert_eq!(EngineConfig::parse("alphabeta:mobility:2:1:5"), None);
+    }
+}
@@ -0,0 +1,611 @@
+// Opening books
+//
+// A book holds the moves played from each position in the first few plies of a set of games, and
+// how those moves turned out, so an engine can play known openings instead of searching them.
+//
+// Hive has no fixed board, so the same opening can be played anywhere on the grid and in any of
+// twelve orientations (six rotations, each possibly mirrored). Positions are looked up by a key
+// that ignores all of that: the pieces are rotated / reflected every way, each time translated so
+// the top-left piece sits at q = r = 0, and the smallest of the twelve layouts is the canonical
+// one. Its moves are stored in the same canonical coordinates. The key is an FNV-1a hash of the
+// canonical layout, both hands, the game type, the rules that decide which moves are legal (the
+// tournament opening and the queen deadline) and the player to move, so (unlike
+// Game::position_hash) it comes out the same on every build and can be saved. Pieces are told
+// apart only by colour and type: which of a player's ants is which makes no difference to the
+// position, so swapping wa1 and wa2 gives the same key.
+//
+// Books are saved as a compact little-endian binary file:
+//
+//     "BDCB" version:u8 plies:u16 positions:u32
+//     per position:  key:u64 moves:u16
+//     per move:      kind:u8 piece:u8 from_q:i16 from_r:i16 to_q:i16 to_r:i16
+//                    games:u32 wins:u32 losses:u32
+//
+// where kind is 0 for a pass, 1 for a placement (from is 0, 0) and 2 for a move, and piece is the
+// piece's colour and type (see piece_kind). Coordinates are offsets from the canonical top-left
+// piece, so they're no bigger than the hive is long.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::game::hand::HAND_ORDER;
+use crate::game::hex::{Axis, Hex};
+use crate::game::piece::{PieceColor, PieceId};
+use crate::game::piece_move::PieceMove;
+use crate::game::{Game, GameError, GameState};
+use crate::rng::Rng;
+
+const MAGIC: &[u8; 4] = b"BDCB";
+const VERSION: u8 = 1;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BookError {
+    Io(String),
+    NotABook,
+    UnsupportedVersion(u8),
+    // The file ended part way through
+    Truncated,
+    MalformedMove,
+    // More positions, or moves in a position, than the file format has room for
+    TooBig,
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookError::Io(e) => write!(f, "{}", e),
+            BookError::NotABook => write!(f, "not an opening book"),
+            BookError::UnsupportedVersion(v) => write!(f, "unsupported book version {}", v),
+            BookError::Truncated => write!(f, "book file is truncated"),
+            BookError::MalformedMove => write!(f, "book file has a malformed move"),
+            BookError::TooBig => write!(f, "book is too big to save"),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+impl From<io::Error> for BookError {
+    fn from(e: io::Error) -> Self {
+        return BookError::Io(e.to_string());
+    }
+}
+
+// How a move has done in the games it was played in, from the point of view of the player who
+// played it. Games that never finished count as neither won nor lost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BookEntry {
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+impl BookEntry {
+    pub fn draws(&self) -> u32 {
+        return self.games - self.wins - self.losses;
+    }
+
+    // The average score, counting a draw as half a win
+    pub fn score(&self) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        return (self.wins as f64 + self.draws() as f64 / 2.0) / self.games as f64;
+    }
+
+    // How likely the move is to be picked: the half-points it has scored, so moves played more
+    // often and won more often come up more, and moves that only ever lost never do
+    pub fn weight(&self) -> u32 {
+        return 2 * self.wins + self.draws();
+    }
+}
+
+// A move in a position's canonical coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum BookMove {
+    Pass,
+    Place {
+        piece: u8,
+        to: (i16, i16),
+    },
+    Move {
+        piece: u8,
+        from: (i16, i16),
+        to: (i16, i16),
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpeningBook {
+    plies: usize,
+    positions: HashMap<u64, Vec<(BookMove, BookEntry)>>,
+}
+
+impl OpeningBook {
+    // An empty book that will take moves from the first plies plies of each game added to it
+    pub fn new(plies: usize) -> OpeningBook {
+        return OpeningBook {
+            plies,
+            positions: HashMap::new(),
+        };
+    }
+
+    // How many plies into a game the book goes
+    pub fn plies(&self) -> usize {
+        return self.plies;
+    }
+
+    pub fn position_count(&self) -> usize {
+        return self.positions.len();
+    }
+
+    pub fn move_count(&self) -> usize {
+        return self.positions.values().map(|moves| moves.len()).sum();
+    }
+
+    // Adds the opening of game, crediting each move with the game's result. A game set up from a
+    // position is replayed from that position, so only its moves within the book's plies count.
+    pub fn add_game(&mut self, game: &Game) -> Result<(), GameError> {
+        let winner = match game.get_state() {
+            GameState::WhiteWins => Some(PieceColor::White),
+            GameState::BlackWins => Some(PieceColor::Black),
+            _ => None,
+        };
+        let mut replay = game.clone();
+        for m in game.move_list.iter().rev() {
+            replay.umake_move(m.clone())?;
+        }
+        for m in &game.move_list {
+            if replay.ply() >= self.plies {
+                break;
+            }
+            let position = Position::of(&replay);
+            let book_move = position.book_move(m);
+            let mover = replay.current_player();
+            let moves = self.positions.entry(position.key).or_default();
+            let entry = match moves.iter().position(|(bm, _)| *bm == book_move) {
+                Some(i) => &mut moves[i].1,
+                None => {
+                    moves.push((book_move, BookEntry::default()));
+                    &mut moves.last_mut().unwrap().1
+                }
+            };
+            entry.games += 1;
+            match winner {
+                Some(color) if color == mover => entry.wins += 1,
+                Some(_) => entry.losses += 1,
+                None => {}
+            }
+            replay.make_move(m.clone())?;
+        }
+        return Ok(());
+    }
+
+    // The legal moves in game that the book knows, best weighted first. Empty once the game is
+    // past the book's plies or out of the positions it has seen.
+    pub fn moves(&self, game: &Game) -> Vec<(PieceMove, BookEntry)> {
+        if game.ply() >= self.plies {
+            return Vec::new();
+        }
+        let position = Position::of(game);
+        let book_moves = match self.positions.get(&position.key) {
+            Some(moves) => moves,
+            None => return Vec::new(),
+        };
+        let mut found: Vec<(BookMove, PieceMove, BookEntry)> = Vec::new();
+        for m in game.get_all_moves() {
+            let book_move = position.book_move(&m);
+            // Symmetrical moves in a symmetrical position are the same book move
+            if found.iter().any(|(bm, _, _)| *bm == book_move) {
+                continue;
+            }
+            if let Some((_, entry)) = book_moves.iter().find(|(bm, _)| *bm == book_move) {
+                found.push((book_move, m, *entry));
+            }
+        }
+        found.sort_by_key(|(bm, _, entry)| (std::cmp::Reverse(entry.weight()), *bm));
+        return found.into_iter().map(|(_, m, entry)| (m, entry)).collect();
+    }
+
+    // A book move for game picked at random by weight, or None if the book has nothing worth
+    // playing
+    pub fn choose_move(&self, game: &Game, rng: &mut Rng) -> Option<PieceMove> {
+        let moves = self.moves(game);
+        let total: u32 = moves.iter().map(|(_, entry)| entry.weight()).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.below(total as usize) as u32;
+        for (m, entry) in moves {
+            if pick < entry.weight() {
+                return Some(m);
+            }
+            pick -= entry.weight();
+        }
+        unreachable!("pick is below the total weight");
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<OpeningBook, BookError> {
+        return OpeningBook::read(&mut File::open(path)?);
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BookError> {
+        let mut file = File::create(path)?;
+        return self.write(&mut file);
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<OpeningBook, BookError> {
+        let mut bytes: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut input = Input { bytes: &bytes };
+        if input.take(4)? != MAGIC {
+            return Err(BookError::NotABook);
+        }
+        let version = input.u8()?;
+        if version != VERSION {
+            return Err(BookError::UnsupportedVersion(version));
+        }
+        let mut book = OpeningBook::new(input.u16()? as usize);
+        for _ in 0..input.u32()? {
+            let key = input.u64()?;
+            let count = input.u16()? as usize;
+            let mut moves: Vec<(BookMove, BookEntry)> = Vec::with_capacity(count);
+            for _ in 0..count {
+                let kind = input.u8()?;
+                let piece = input.u8()?;
+                let from = (input.i16()?, input.i16()?);
+                let to = (input.i16()?, input.i16()?);
+                let book_move = match kind {
+                    0 => BookMove::Pass,
+                    1 => BookMove::Place { piece, to },
+                    2 => BookMove::Move { piece, from, to },
+                    _ => return Err(BookError::MalformedMove),
+                };
+                let entry = BookEntry {
+                    games: input.u32()?,
+                    wins: input.u32()?,
+                    losses: input.u32()?,
+                };
+                if entry.wins + entry.losses > entry.games {
+                    return Err(BookError::MalformedMove);
+                }
+                moves.push((book_move, entry));
+            }
+            book.positions.insert(key, moves);
+        }
+        return Ok(book);
+    }
+
+    // Positions are written in key order so the same book always makes the same file
+    pub fn write(&self, writer: &mut impl Write) -> Result<(), BookError> {
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.plies.min(u16::MAX as usize) as u16).to_le_bytes());
+        let positions = u32::try_from(self.positions.len()).map_err(|_| BookError::TooBig)?;
+        bytes.extend_from_slice(&positions.to_le_bytes());
+        let mut keys: Vec<&u64> = self.positions.keys().collect();
+        keys.sort();
+        for key in keys {
+            let moves = &self.positions[key];
+            bytes.extend_from_slice(&key.to_le_bytes());
+            let count = u16::try_from(moves.len()).map_err(|_| BookError::TooBig)?;
+            bytes.extend_from_slice(&count.to_le_bytes());
+            for (book_move, entry) in moves {
+                let (kind, piece, from, to) = match *book_move {
+                    BookMove::Pass => (0, 0, (0, 0), (0, 0)),
+                    BookMove::Place { piece, to } => (1, piece, (0, 0), to),
+                    BookMove::Move { piece, from, to } => (2, piece, from, to),
+                };
+                bytes.extend_from_slice(&[kind, piece]);
+                for n in [from.0, from.1, to.0, to.1] {
+                    bytes.extend_from_slice(&n.to_le_bytes());
+                }
+                for n in [entry.games, entry.wins, entry.losses] {
+                    bytes.extend_from_slice(&n.to_le_bytes());
+                }
+            }
+        }
+        writer.write_all(&bytes)?;
+        return Ok(());
+    }
+}
+
+// A position's book key, and the orientations that give its canonical layout (more than one when
+// the position is symmetrical)
+struct Position {
+    key: u64,
+    frames: Vec<Frame>,
+}
+
+// One of the twelve symmetries, and where the top-left piece ends up after it
+#[derive(Clone, Copy)]
+struct Frame {
+    symmetry: usize,
+    origin: (isize, isize),
+}
+
+impl Frame {
+    fn cell(&self, q: isize, r: isize) -> (i16, i16) {
+        let (q, r) = transform(self.symmetry, q, r);
+        return (offset(q - self.origin.0), offset(r - self.origin.1));
+    }
+}
+
+impl Position {
+    fn of(game: &Game) -> Position {
+        let mut best: Vec<(i16, i16, i16, u8)> = Vec::new();
+        let mut frames: Vec<Frame> = Vec::new();
+        for symmetry in 0..12 {
+            let moved: Vec<(isize, isize, isize, u8)> = game
+                .grid
+                .node_weights()
+                .map(|p| {
+                    let (q, r) = transform(symmetry, p.hex.q(), p.hex.r());
+                    (q, r, p.hex.z, piece_kind(p.id))
+                })
+                .collect();
+            let origin = moved
+                .iter()
+                .map(|&(q, r, _, _)| (q, r))
+                .min()
+                .unwrap_or((0, 0));
+            let mut layout: Vec<(i16, i16, i16, u8)> = moved
+                .into_iter()
+                .map(|(q, r, z, piece)| {
+                    (offset(q - origin.0), offset(r - origin.1), offset(z), piece)
+                })
+                .collect();
+            layout.sort();
+            let frame = Frame { symmetry, origin };
+            if frames.is_empty() || layout < best {
+                best = layout;
+                frames = vec![frame];
+            } else if layout == best {
+                frames.push(frame);
+            }
+        }
+
+        let mut hash = Fnv::new();
+        let game_type = game.game_type;
+        hash.write(&[
+            game_type.mosquito as u8,
+            game_type.ladybug as u8,
+            game_type.pillbug as u8,
+            game.rules.tournament_opening as u8,
+            (game.current_player() == PieceColor::Black) as u8,
+        ]);
+        hash.write(&(game.rules.queen_deadline as u64).to_le_bytes());
+        for color in [PieceColor::White, PieceColor::Black] {
+            for piece_type in HAND_ORDER {
+                hash.write(&[game.hand(color).count(piece_type)]);
+            }
+        }
+        for (q, r, z, piece) in best {
+            for n in [q, r, z] {
+                hash.write(&n.to_le_bytes());
+            }
+            hash.write(&[piece]);
+        }
+        return Position {
+            key: hash.finish(),
+            frames,
+        };
+    }
+
+    // piece_move in canonical coordinates. With several canonical orientations the smallest
+    // version is used, so that a move and its mirror image in a symmetrical position match.
+    fn book_move(&self, piece_move: &PieceMove) -> BookMove {
+        return self
+            .frames
+            .iter()
+            .map(|frame| match piece_move {
+                PieceMove::Pass => BookMove::Pass,
+                PieceMove::Place { piece_id, hex } => BookMove::Place {
+                    piece: piece_kind(*piece_id),
+                    to: frame.cell(hex.q(), hex.r()),
+                },
+                PieceMove::Move {
+                    piece_id,
+                    from,
+                    hex,
+                } => BookMove::Move {
+                    piece: piece_kind(*piece_id),
+                    from: frame.cell(from.q(), from.r()),
+                    to: frame.cell(hex.q(), hex.r()),
+                },
+            })
+            .min()
+            .expect("There is always at least one canonical orientation");
+    }
+}
+
+// A piece's colour and type: white's are 0..8 in HAND_ORDER, black's 8..16
+fn piece_kind(piece_id: PieceId) -> u8 {
+    let i = HAND_ORDER
+        .iter()
+        .position(|&t| t == piece_id.piece_type)
+        .expect("Every piece type is in HAND_ORDER");
+    return (piece_id.color as usize * HAND_ORDER.len() + i) as u8;
+}
+
+// A distance within the hive, which has far fewer pieces than fit in an i16
+fn offset(n: isize) -> i16 {
+    return i16::try_from(n).expect("Offsets within a hive fit in an i16");
+}
+
+// Symmetries 0..6 rotate by a sixth of a turn that many times, and 6..12 do the same after
+// mirroring the grid (swapping r and s)
+fn transform(symmetry: usize, q: isize, r: isize) -> (isize, isize) {
+    let origin = Hex::from_axial(0, 0);
+    let mut hex = Hex::from_axial(q, r);
+    if symmetry >= 6 {
+        hex = hex.reflect_about(origin, Axis::Q);
+    }
+    return hex.rotate_about(origin, (symmetry % 6) as isize).to_axial();
+}
+
+// 64-bit FNV-1a
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        return Fnv(0xcbf2_9ce4_8422_2325);
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        return self.0;
+    }
+}
+
+// Reads a book file from the front
+struct Input<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BookError> {
+        if self.bytes.len() < n {
+            return Err(BookError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        return Ok(taken);
+    }
+
+    fn u8(&mut self) -> Result<u8, BookError> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u16(&mut self) -> Result<u16, BookError> {
+        return Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()));
+    }
+
+    fn i16(&mut self) -> Result<i16, BookError> {
+        return Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()));
+    }
+
+    fn u32(&mut self) -> Result<u32, BookError> {
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    fn u64(&mut self) -> Result<u64, BookError> {
+        return Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_type::GameType;
+    use crate::game::hex::Hex;
+    use crate::game::rules::Rules;
+    use crate::game::uhp::import_from_game_string;
+
+    fn opening() -> Game {
+        return import_from_game_string(
+            r"Base;InProgress;White[4];wQ;bQ wQ-;wA1 -wQ;bA1 bQ-;wA2 \wQ;bA2 bQ/",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn key_ignores_piece_numbers() {
+        let game = opening();
+        let mut swapped = game.clone();
+        let [wa1, wa2] = ["wa1", "wa2"].map(|s| PieceId::parse(s).unwrap());
+        for piece in swapped.grid.node_weights_mut() {
+            if piece.id == wa1 {
+                piece.id = wa2;
+            } else if piece.id == wa2 {
+                piece.id = wa1;
+            }
+        }
+        assert_eq!(Position::of(&game).key, Position::of(&swapped).key);
+    }
+
+    #[test]
+    fn key_ignores_where_the_hive_is() {
+        let game = opening();
+        let mut far = game.clone();
+        for piece in far.grid.node_weights_mut() {
+            piece.hex =
+                Hex::from_axial(piece.hex.q() + 5000, piece.hex.r() - 3000).with_z(piece.hex.z);
+        }
+        assert_eq!(Position::of(&game).key, Position::of(&far).key);
+
+        let mut book = OpeningBook::new(8);
+        let mut played = game.clone();
+        let m = played.get_all_moves()[0].clone();
+        played.make_move(m).unwrap();
+        book.add_game(&played).unwrap();
+        assert_eq!(book.moves(&far).len(), 1);
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let mut book = OpeningBook::new(6);
+        book.add_game(&opening()).unwrap();
+        let mut bytes: Vec<u8> = Vec::new();
+        book.write(&mut bytes).unwrap();
+        assert_eq!(OpeningBook::read(&mut bytes.as_slice()).unwrap(), book);
+        assert_eq!(
+            OpeningBook::read(&mut &bytes[..bytes.len() - 1]).unwrap_err(),
+            BookError::Truncated
+        );
+    }
+
+    #[test]
+    fn key_depends_on_the_opening_rules() {
+        let casual = Game::with_type(GameType::BASE);
+        let mut tournament = casual.clone();
+        tournament.rules = Rules::tournament();
+        assert_ne!(Position::of(&casual).key, Position::of(&tournament).key);
+
+        let mut deadline = casual.clone();
+        deadline.rules.queen_deadline = 3;
+        assert_ne!(Position::of(&casual).key, Position::of(&deadline).key);
+
+        // A casual book's queen openings aren't offered in a tournament game
+        let mut book = OpeningBook::new(4);
+        book.add_game(&opening()).unwrap();
+        assert_eq!(book.moves(&casual).len(), 1);
+        assert!(book.moves(&tournament).is_empty());
+    }
+
+    #[test]
+    fn replays_games_set_up_from_a_position() {
+        let mut game = opening().position();
+        let m = game.get_all_moves()[0].clone();
+        game.make_move(m.clone()).unwrap();
+
+        let mut book = OpeningBook::new(8);
+        book.add_game(&game).unwrap();
+        assert_eq!(book.move_count(), 1);
+        let moves = book.moves(&opening());
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0, m);
+
+        // Its moves come after the book's plies
+        let mut short = OpeningBook::new(6);
+        short.add_game(&game).unwrap();
+        assert_eq!(short.move_count(), 0);
+    }
+
+    #[test]
+    fn write_refuses_more_moves_than_fit() {
+        let mut book = OpeningBook::new(1);
+        let moves = vec![(BookMove::Pass, BookEntry::default()); u16::MAX as usize + 1];
+        book.positions.insert(0, moves);
+        assert_eq!(book.write(&mut Vec::new()), Err(BookError::TooBig));
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+use crate::game::compact::{piece_color, CompactGame, IN_HAND, PIECE_COUNT};
+use crate::game::piece::PieceColor;
+
+// The score of a won position, well clear of anything an evaluator returns. Search takes off one
+// per move so that quicker wins score higher.
+pub const WIN_SCORE: i32 = 1_000_000;
+
+// How an engine scores a position that isn't over, from the point of view of the player to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Evaluator {
+    // Pieces around the opponent's queen minus pieces around our own
+    Queen,
+    // Queen, plus how many more of our pieces than theirs are free to move
+    Mobility,
+}
+
+impl Evaluator {
+    pub fn parse(s: &str) -> Option<Evaluator> {
+        match s {
+            "queen" => return Some(Evaluator::Queen),
+            "mobility" => return Some(Evaluator::Mobility),
+            _ => return None,
+        }
+    }
+
+    pub fn evaluate(&self, game: &CompactGame) -> i32 {
+        let us = game.current_player();
+        let them = us.opponent();
+        let queen = game.queen_neighbors(them) as i32 - game.queen_neighbors(us) as i32;
+        match self {
+            Evaluator::Queen => return 100 * queen,
+            Evaluator::Mobility => {
+                let pinned = game.pinned_pieces();
+                let mobility = free_pieces(game, us, pinned) - free_pieces(game, them, pinned);
+                return 100 * queen + 10 * mobility;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Evaluator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Evaluator::Queen => write!(f, "queen"),
+            Evaluator::Mobility => write!(f, "mobility"),
+        }
+    }
+}
+
+// color's pieces on top of their stacks that can leave without splitting the hive
+fn free_pieces(game: &CompactGame, color: PieceColor, pinned: u32) -> i32 {
+    return (0..PIECE_COUNT as u8)
+        .filter(|&piece| {
+            let cell = game.piece_cell(piece);
+            return piece_color(piece) == color
+                && cell < IN_HAND
+                && game.top_piece_at(cell) == Some(piece)
+                && pinned & (1 << piece) == 0;
+        })
+        .count() as i32;
+}
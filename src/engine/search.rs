@@ -0,0 +1,298 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::engine::eval::{Evaluator, WIN_SCORE};
+use crate::engine::surround::SurroundSolver;
+use crate::game::compact::{CompactGame, CompactMove};
+use crate::game::piece::PieceColor;
+use crate::game::GameState;
+use crate::rng::Rng;
+
+// Beyond any score a position can get
+const INFINITY: i32 = 2 * WIN_SCORE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchType {
+    // Any legal move, ignoring the evaluator and depth
+    Random,
+    // Full-width negamax
+    Minimax,
+    // Negamax with alpha-beta pruning: the same scores as Minimax from fewer positions
+    AlphaBeta,
+}
+
+impl SearchType {
+    pub fn parse(s: &str) -> Option<SearchType> {
+        match s {
+            "random" => return Some(SearchType::Random),
+            "minimax" => return Some(SearchType::Minimax),
+            "alphabeta" => return Some(SearchType::AlphaBeta),
+            _ => return None,
+        }
+    }
+}
+
+impl fmt::Display for SearchType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchType::Random => write!(f, "random"),
+            SearchType::Minimax => write!(f, "minimax"),
+            SearchType::AlphaBeta => write!(f, "alphabeta"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub best_move: CompactMove,
+    // From the point of view of the player to move
+    pub score: i32,
+    pub depth: usize,
+    // The line the search expects after best_move, starting with best_move. It stops short of
+    // depth where the game ends or a forced surround was found.
+    pub pv: Vec<CompactMove>,
+    // Positions visited
+    pub nodes: u64,
+    pub time: Duration,
+    // The best moves in order, best_move first, as many as were asked for
+    pub lines: Vec<SearchLine>,
+}
+
+// One of the best moves at the root and how it scored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchLine {
+    pub score: i32,
+    // Starts with the move itself
+    pub pv: Vec<CompactMove>,
+}
+
+// Picks a move for the player to move by looking depth moves ahead, or None if the game is over.
+// Every move is tried, so depth 0 searches as deep as depth 1 and is reported as 1.
+// With surround above 0, positions at the end of the search are checked for a forced queen
+// surround in up to that many moves before being evaluated. Moves that score the same are chosen
+// between with rng. game is left as it was.
+pub fn search(
+    game: &mut CompactGame,
+    search_type: SearchType,
+    evaluator: Evaluator,
+    depth: usize,
+    surround: usize,
+    rng: &mut Rng,
+) -> Option<SearchResult> {
+    return search_lines(game, search_type, evaluator, depth, surround, 1, rng);
+}
+
+// search, also scoring the next best moves exactly so that lines has up to multi_pv of them. A
+// random search only has the one line.
+pub fn search_lines(
+    game: &mut CompactGame,
+    search_type: SearchType,
+    evaluator: Evaluator,
+    depth: usize,
+    surround: usize,
+    multi_pv: usize,
+    rng: &mut Rng,
+) -> Option<SearchResult> {
+    let start = Instant::now();
+    let moves = game.get_all_moves();
+    if moves.is_empty() {
+        return None;
+    }
+    if search_type == SearchType::Random {
+        let best_move = *rng.choose(&moves);
+        let score = evaluator.evaluate(game);
+        return Some(SearchResult {
+            best_move,
+            score,
+            depth: 0,
+            pv: vec![best_move],
+            nodes: 1,
+            time: start.elapsed(),
+            lines: vec![SearchLine {
+                score,
+                pv: vec![best_move],
+            }],
+        });
+    }
+
+    let multi_pv = multi_pv.max(1);
+    let depth = depth.max(1);
+    let mut searcher = Searcher {
+        prune: search_type == SearchType::AlphaBeta,
+        evaluator,
+        surround,
+        nodes: 1,
+    };
+    // Every move searched so far, best first
+    let mut lines: Vec<SearchLine> = Vec::new();
+    for m in moves {
+        // Keep the window open just below the multi_pv-th best score, so that every move that
+        // could make the cut (ties included) gets an exact score
+        let alpha = match lines.get(multi_pv - 1) {
+            Some(line) if searcher.prune => line.score - 1,
+            _ => -INFINITY,
+        };
+        let mut pv = vec![m];
+        game.make_move(m);
+        let score = -searcher.negamax(game, depth - 1, 1, -INFINITY, -alpha, &mut pv);
+        game.umake_move(m);
+        let at = lines.partition_point(|line| line.score >= score);
+        lines.insert(at, SearchLine { score, pv });
+    }
+
+    // Moves that score the same as the best are chosen between with rng
+    let score = lines[0].score;
+    let tied = lines.partition_point(|line| line.score == score);
+    let best = lines.remove(rng.below(tied));
+    lines.insert(0, best);
+    lines.truncate(multi_pv);
+    return Some(SearchResult {
+        best_move: lines[0].pv[0],
+        score,
+        depth,
+        pv: lines[0].pv.clone(),
+        nodes: searcher.nodes,
+        time: start.elapsed(),
+        lines,
+    });
+}
+
+struct Searcher {
+    prune: bool,
+    evaluator: Evaluator,
+    // Attacker moves to look for a forced surround in at the leaves
+    surround: usize,
+    nodes: u64,
+}
+
+impl Searcher {
+    // The score of game for the player to move, ply moves below the root. The best line found
+    // from game is added to pv, which is only the principal variation when the score is inside
+    // the window.
+    fn negamax(
+        &mut self,
+        game: &mut CompactGame,
+        depth: usize,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<CompactMove>,
+    ) -> i32 {
+        self.nodes += 1;
+        // Either player can lose on their own move (by surrounding their own queen)
+        let winner = match game.get_state() {
+            GameState::Draw => return 0,
+            GameState::WhiteWins => Some(PieceColor::White),
+            GameState::BlackWins => Some(PieceColor::Black),
+            GameState::NotStarted | GameState::InProgress => None,
+        };
+        if let Some(winner) = winner {
+            if winner == game.current_player() {
+                return WIN_SCORE - ply;
+            }
+            return -(WIN_SCORE - ply);
+        }
+        if depth == 0 {
+            if self.surround > 0 {
+                let mut solver = SurroundSolver::new(game.current_player());
+                let fastest = solver.fastest(game, self.surround);
+                self.nodes += solver.nodes;
+                if let Some(moves) = fastest {
+                    // The winning move is made moves * 2 - 1 plies below this one
+                    return WIN_SCORE - (ply + 2 * moves as i32 - 1);
+                }
+            }
+            return self.evaluator.evaluate(game);
+        }
+
+        let mut moves: Vec<CompactMove> = Vec::with_capacity(64);
+        game.generate_moves(&mut moves);
+        let mut best = -INFINITY;
+        let mut best_line: Vec<CompactMove> = Vec::new();
+        let mut line: Vec<CompactMove> = Vec::new();
+        for m in moves {
+            line.clear();
+            game.make_move(m);
+            let score = -self.negamax(game, depth - 1, ply + 1, -beta, -alpha, &mut line);
+            game.umake_move(m);
+            if score > best {
+                best = score;
+                best_line.clear();
+                best_line.push(m);
+                best_line.append(&mut line);
+            }
+            if self.prune {
+                alpha = alpha.max(best);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        pv.extend(best_line);
+        return best;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::uhp::import_from_game_string;
+
+    fn opening() -> CompactGame {
+        let game =
+            import_from_game_string("Base;InProgress;White[3];wQ;bQ wQ-;wA1 -wQ;bA1 bQ-").unwrap();
+        return CompactGame::from(&game);
+    }
+
+    #[test]
+    fn depth_zero_searches_and_reports_one_ply() {
+        let mut game = opening();
+        let run = |game: &mut CompactGame, depth: usize| {
+            return search(
+                game,
+                SearchType::AlphaBeta,
+                Evaluator::Mobility,
+                depth,
+                0,
+                &mut Rng::new(0),
+            )
+            .unwrap();
+        };
+        let zero = run(&mut game, 0);
+        let one = run(&mut game, 1);
+        assert_eq!(zero.depth, 1);
+        assert_eq!((zero.best_move, zero.score), (one.best_move, one.score));
+    }
+
+    #[test]
+    fn multi_pv_lines_are_best_first() {
+        let mut game = opening();
+        let result = search_lines(
+            &mut game,
+            SearchType::AlphaBeta,
+            Evaluator::Mobility,
+            2,
+            0,
+            3,
+            &mut Rng::new(0),
+        )
+        .unwrap();
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.lines[0].pv[0], result.best_move);
+        assert!(result.lines.windows(2).all(|w| w[0].score >= w[1].score));
+
+        // Alpha-beta has to agree with a full-width search on every line it reports
+        let minimax = search_lines(
+            &mut game,
+            SearchType::Minimax,
+            Evaluator::Mobility,
+            2,
+            0,
+            3,
+            &mut Rng::new(0),
+        )
+        .unwrap();
+        let scores = |r: &SearchResult| r.lines.iter().map(|l| l.score).collect::<Vec<i32>>();
+        assert_eq!(scores(&result), scores(&minimax));
+    }
+}
@@ -0,0 +1,440 @@
+// Engine-vs-engine matches, for measuring whether a change to an engine makes it stronger.
+//
+// Games are played in pairs from the same random opening, once with each engine as white, so
+// neither engine gets the better openings or the first move more often. Everything random (the
+// openings and the engines' tie-breaks) comes from the match seed, so the same seed always plays
+// the same games.
+//
+// A match either plays a fixed number of games or, in SPRT mode, stops as soon as it's clear
+// whether A is better than B by the margin being tested (see Sprt).
+
+use std::fmt;
+use std::rc::Rc;
+
+use crate::engine::book::OpeningBook;
+use crate::engine::stats::{EloEstimate, Sprt, SprtDecision};
+use crate::engine::EngineConfig;
+use crate::game::game_type::GameType;
+use crate::game::piece::PieceColor;
+use crate::game::piece_move::PieceMove;
+use crate::game::random::{play_random_moves, Weighting};
+use crate::game::rules::Rules;
+use crate::game::{DrawReason, Game, GameState};
+use crate::rng::Rng;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchConfig {
+    pub games: usize,
+    pub seed: u64,
+    // Random moves played at the start of each pair of games
+    pub opening_moves: usize,
+    pub game_type: GameType,
+    // Games only end by a queen being surrounded unless these have a draw rule, so the default
+    // adds repetition draws and a turn limit
+    pub rules: Rules,
+    // Played from by both engines after the random opening, for as long as it has moves
+    pub book: Option<Rc<OpeningBook>>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        return MatchConfig {
+            games: 100,
+            seed: 0,
+            opening_moves: 4,
+            game_type: GameType::default(),
+            rules: Rules {
+                draw_by_repetition: true,
+                turn_limit: Some(100),
+                ..Rules::default()
+            },
+            book: None,
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    // Counting from 0
+    pub number: usize,
+    // Whether engine A played white
+    pub a_is_white: bool,
+    pub result: GameState,
+    pub draw_reason: Option<DrawReason>,
+    pub move_list: Vec<PieceMove>,
+    pub game_string: String,
+}
+
+impl GameRecord {
+    // 1 for a win for engine A, 0.5 for a draw and 0 for a loss
+    pub fn score_for_a(&self) -> f64 {
+        let a_color = if self.a_is_white {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        match self.result {
+            GameState::WhiteWins if a_color == PieceColor::White => return 1.0,
+            GameState::BlackWins if a_color == PieceColor::Black => return 1.0,
+            GameState::WhiteWins | GameState::BlackWins => return 0.0,
+            _ => return 0.5,
+        }
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let score = self.score_for_a();
+        let outcome = if score == 1.0 {
+            "won"
+        } else if score == 0.0 {
+            "lost"
+        } else {
+            "drew"
+        };
+        write!(
+            f,
+            "game {}: A {} as {}, {:?}",
+            self.number + 1,
+            outcome,
+            if self.a_is_white { "white" } else { "black" },
+            self.result
+        )?;
+        if let Some(reason) = self.draw_reason {
+            write!(f, " ({:?})", reason)?;
+        }
+        write!(
+            f,
+            " in {} moves: {}",
+            self.move_list.len(),
+            self.game_string
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchResult {
+    pub a: EngineConfig,
+    pub b: EngineConfig,
+    pub games: Vec<GameRecord>,
+}
+
+impl MatchResult {
+    // Wins, losses and draws are all from engine A's point of view
+    pub fn wins(&self) -> usize {
+        return tally(&self.games).0;
+    }
+
+    pub fn losses(&self) -> usize {
+        return tally(&self.games).1;
+    }
+
+    pub fn draws(&self) -> usize {
+        return tally(&self.games).2;
+    }
+
+    // How much stronger A is than B
+    pub fn elo(&self) -> Option<EloEstimate> {
+        return EloEstimate::new(self.wins(), self.losses(), self.draws());
+    }
+
+    // Moves per game, counting both players
+    pub fn average_length(&self) -> f64 {
+        if self.games.is_empty() {
+            return 0.0;
+        }
+        let moves: usize = self.games.iter().map(|g| g.move_list.len()).sum();
+        return moves as f64 / self.games.len() as f64;
+    }
+}
+
+impl fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "A: {}", self.a)?;
+        writeln!(f, "B: {}", self.b)?;
+        writeln!(
+            f,
+            "{} games: A +{} -{} ={}",
+            self.games.len(),
+            self.wins(),
+            self.losses(),
+            self.draws()
+        )?;
+        if let Some(elo) = self.elo() {
+            writeln!(f, "Elo (A - B): {}", elo)?;
+        }
+        write!(f, "Average length: {:.1} moves", self.average_length())
+    }
+}
+
+pub fn play_match(a: EngineConfig, b: EngineConfig, config: &MatchConfig) -> MatchResult {
+    return play_match_with(a, b, config, |_| {});
+}
+
+// Same as play_match, handing each game to on_game as soon as it's finished
+pub fn play_match_with(
+    a: EngineConfig,
+    b: EngineConfig,
+    config: &MatchConfig,
+    mut on_game: impl FnMut(&GameRecord),
+) -> MatchResult {
+    return run_match(a, b, config, |games| {
+        on_game(&games[games.len() - 1]);
+        return true;
+    });
+}
+
+#[derive(Debug, Clone)]
+pub struct SprtResult {
+    pub sprt: Sprt,
+    pub result: MatchResult,
+    pub llr: f64,
+    // None if the match ran out of games first
+    pub decision: Option<SprtDecision>,
+}
+
+impl fmt::Display for SprtResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.result)?;
+        let (lower, upper) = self.sprt.bounds();
+        let decision = match self.decision {
+            Some(SprtDecision::Accept) => "H1 accepted",
+            Some(SprtDecision::Reject) => "H0 accepted (H1 rejected)",
+            None => "inconclusive",
+        };
+        write!(
+            f,
+            "SPRT elo0 {} elo1 {}: LLR {:.2} ({:.2}, {:.2}), {}",
+            self.sprt.elo0, self.sprt.elo1, self.llr, lower, upper, decision
+        )
+    }
+}
+
+// Plays pairs of games until sprt reaches a decision, or until config.games have been played.
+// on_pair gets the games so far and the LLR after each pair.
+pub fn play_sprt(
+    a: EngineConfig,
+    b: EngineConfig,
+    config: &MatchConfig,
+    sprt: Sprt,
+    mut on_pair: impl FnMut(&[GameRecord], f64),
+) -> SprtResult {
+    let mut llr = 0.0;
+    let result = run_match(a, b, config, |games| {
+        if games.len() % 2 == 1 {
+            return true;
+        }
+        let (wins, losses, draws) = tally(games);
+        llr = sprt.llr(wins, losses, draws);
+        on_pair(games, llr);
+        return sprt.decision(llr).is_none();
+    });
+    return SprtResult {
+        sprt,
+        decision: sprt.decision(llr),
+        result,
+        llr,
+    };
+}
+
+// Plays up to config.games games, stopping early once keep_going (given every game so far) says so
+fn run_match(
+    a: EngineConfig,
+    b: EngineConfig,
+    config: &MatchConfig,
+    mut keep_going: impl FnMut(&[GameRecord]) -> bool,
+) -> MatchResult {
+    let mut rng = Rng::new(config.seed);
+    let mut games: Vec<GameRecord> = Vec::new();
+    let mut opening: Vec<PieceMove> = Vec::new();
+    for number in 0..config.games {
+        let a_is_white = number % 2 == 0;
+        if a_is_white {
+            opening = random_opening(config, &mut rng);
+        }
+        let mut game_rng = Rng::new(rng.next_u64());
+        games.push(play_game(
+            number,
+            a,
+            b,
+            a_is_white,
+            &opening,
+            config,
+            &mut game_rng,
+        ));
+        if !keep_going(&games) {
+            break;
+        }
+    }
+    return MatchResult { a, b, games };
+}
+
+// A's wins, losses and draws
+fn tally(games: &[GameRecord]) -> (usize, usize, usize) {
+    let wins = games.iter().filter(|g| g.score_for_a() == 1.0).count();
+    let losses = games.iter().filter(|g| g.score_for_a() == 0.0).count();
+    return (wins, losses, games.len() - wins - losses);
+}
+
+fn new_game(config: &MatchConfig) -> Game {
+    let mut game = Game::with_type(config.game_type);
+    game.rules = config.rules;
+    return game;
+}
+
+fn random_opening(config: &MatchConfig, rng: &mut Rng) -> Vec<PieceMove> {
+    let mut game = new_game(config);
+    play_random_moves(&mut game, rng, config.opening_moves, Weighting::Uniform);
+    return game.move_list;
+}
+
+fn play_game(
+    number: usize,
+    a: EngineConfig,
+    b: EngineConfig,
+    a_is_white: bool,
+    opening: &[PieceMove],
+    config: &MatchConfig,
+    rng: &mut Rng,
+) -> GameRecord {
+    let mut game = new_game(config);
+    for m in opening {
+        game.make_move(m.clone())
+            .expect("Openings are replayed from the start");
+    }
+    loop {
+        let engine = if (game.current_player() == PieceColor::White) == a_is_white {
+            a
+        } else {
+            b
+        };
+        let chosen = match &config.book {
+            Some(book) => engine.choose_move_with_book(&game, book, rng),
+            None => engine.choose_move(&game, rng),
+        };
+        match chosen {
+            Some(m) => game.make_move(m).expect("Engines play legal moves"),
+            None => break,
+        }
+    }
+    return GameRecord {
+        number,
+        a_is_white,
+        result: game.get_state(),
+        draw_reason: game.draw_reason(),
+        game_string: game
+            .export_game_string()
+            .expect("Self-play games start from the beginning"),
+        move_list: game.move_list,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_engine() -> EngineConfig {
+        return EngineConfig::parse("random").unwrap();
+    }
+
+    fn small_match(seed: u64) -> MatchConfig {
+        return MatchConfig {
+            games: 6,
+            seed,
+            rules: Rules {
+                draw_by_repetition: true,
+                turn_limit: Some(20),
+                ..Rules::default()
+            },
+            ..MatchConfig::default()
+        };
+    }
+
+    #[test]
+    fn same_seed_plays_the_same_games() {
+        let config = small_match(7);
+        let first = play_match(random_engine(), random_engine(), &config);
+        let second = play_match(random_engine(), random_engine(), &config);
+        assert_eq!(first.games.len(), 6);
+        for (a, b) in first.games.iter().zip(second.games.iter()) {
+            assert_eq!(a.move_list, b.move_list);
+            assert_eq!(a.result, b.result);
+        }
+
+        let other = play_match(random_engine(), random_engine(), &small_match(8));
+        assert!(first
+            .games
+            .iter()
+            .zip(other.games.iter())
+            .any(|(a, b)| a.move_list != b.move_list));
+    }
+
+    #[test]
+    fn games_come_in_pairs_from_the_same_opening() {
+        let config = small_match(3);
+        let result = play_match(random_engine(), random_engine(), &config);
+        for pair in result.games.chunks(2) {
+            assert!(pair[0].a_is_white);
+            assert!(!pair[1].a_is_white);
+            assert_eq!(
+                pair[0].move_list[..config.opening_moves],
+                pair[1].move_list[..config.opening_moves]
+            );
+        }
+        assert_eq!(
+            result.wins() + result.losses() + result.draws(),
+            result.games.len()
+        );
+    }
+
+    #[test]
+    fn score_is_from_a_point_of_view() {
+        let record = |a_is_white, result| GameRecord {
+            number: 0,
+            a_is_white,
+            result,
+            draw_reason: None,
+            move_list: Vec::new(),
+            game_string: String::new(),
+        };
+        assert_eq!(record(true, GameState::WhiteWins).score_for_a(), 1.0);
+        assert_eq!(record(false, GameState::WhiteWins).score_for_a(), 0.0);
+        assert_eq!(record(false, GameState::BlackWins).score_for_a(), 1.0);
+        assert_eq!(record(true, GameState::BlackWins).score_for_a(), 0.0);
+        assert_eq!(record(true, GameState::Draw).score_for_a(), 0.5);
+    }
+
+    #[test]
+    fn sprt_stops_after_a_pair_once_decided() {
+        let config = MatchConfig {
+            games: 40,
+            ..small_match(5)
+        };
+        // So wide that a handful of games decides it
+        let sprt = Sprt {
+            elo0: -400.0,
+            elo1: 400.0,
+            alpha: 0.2,
+            beta: 0.2,
+        };
+        let mut pairs = 0;
+        let result = play_sprt(
+            random_engine(),
+            random_engine(),
+            &config,
+            sprt,
+            |games, _| {
+                assert!(games.len().is_multiple_of(2));
+                pairs += 1;
+            },
+        );
+        assert!(result.result.games.len().is_multiple_of(2));
+        assert_eq!(pairs, result.result.games.len() / 2);
+        assert_eq!(result.decision, sprt.decision(result.llr));
+        if result.decision.is_none() {
+            assert_eq!(result.result.games.len(), config.games);
+        } else {
+            assert!(result.result.games.len() < config.games);
+        }
+    }
+}
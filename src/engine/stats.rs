@@ -0,0 +1,193 @@
+use std::fmt;
+
+// Elo difference implied by an average score between 0 and 1 (e.g. 0.64 -> +100). Scores of 0 and
+// 1 come out as -inf and +inf.
+pub fn elo_from_score(score: f64) -> f64 {
+    return -400.0 * (1.0 / score - 1.0).log10();
+}
+
+// An Elo difference with its 95% confidence interval
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EloEstimate {
+    pub elo: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl EloEstimate {
+    // From a player's wins, losses and draws, or None if no games were played. The interval uses
+    // the normal approximation to the spread of the per-game scores.
+    pub fn new(wins: usize, losses: usize, draws: usize) -> Option<EloEstimate> {
+        let (games, score, variance) = score_and_variance(wins, losses, draws)?;
+        let margin = 1.96 * (variance / games).sqrt();
+        return Some(EloEstimate {
+            elo: elo_from_score(score),
+            low: elo_from_score((score - margin).max(0.0)),
+            high: elo_from_score((score + margin).min(1.0)),
+        });
+    }
+}
+
+impl fmt::Display for EloEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:+.1} (95% CI {:+.1} to {:+.1})",
+            self.elo, self.low, self.high
+        )
+    }
+}
+
+// The inverse of elo_from_score: the expected score against a player `elo` points weaker
+pub fn score_from_elo(elo: f64) -> f64 {
+    return 1.0 / (1.0 + 10f64.powf(-elo / 400.0));
+}
+
+// A sequential probability ratio test between H0 "the Elo difference is elo0" and H1 "it is
+// elo1", which can be stopped as soon as either is likely enough. alpha is the chance of accepting
+// H1 when H0 is true and beta the chance of the reverse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        return Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtDecision {
+    // H1: the change is (at least) elo1 better
+    Accept,
+    // H0: the change is no better than elo0
+    Reject,
+}
+
+impl Sprt {
+    // The log-likelihood ratio for H0 and H1 at which the test stops
+    pub fn bounds(&self) -> (f64, f64) {
+        let lower = (self.beta / (1.0 - self.alpha)).ln();
+        let upper = ((1.0 - self.beta) / self.alpha).ln();
+        return (lower, upper);
+    }
+
+    // Log-likelihood ratio of H1 against H0 given the results so far, using the normal
+    // approximation to the per-game scores. 0 with no games.
+    pub fn llr(&self, wins: usize, losses: usize, draws: usize) -> f64 {
+        let (games, score, mut variance) = match score_and_variance(wins, losses, draws) {
+            Some(stats) => stats,
+            None => return 0.0,
+        };
+        if variance == 0.0 {
+            // Every game had the same result, which would make the ratio infinite. Pretend A also
+            // won one more and lost one more to get a spread to work with.
+            variance = score_and_variance(wins + 1, losses + 1, draws).unwrap().2;
+        }
+        let s0 = score_from_elo(self.elo0);
+        let s1 = score_from_elo(self.elo1);
+        return games * (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * variance);
+    }
+
+    // None while the test should carry on
+    pub fn decision(&self, llr: f64) -> Option<SprtDecision> {
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            return Some(SprtDecision::Accept);
+        } else if llr <= lower {
+            return Some(SprtDecision::Reject);
+        }
+        return None;
+    }
+}
+
+// The number of games, average score and per-game variance of the score, or None with no games
+fn score_and_variance(wins: usize, losses: usize, draws: usize) -> Option<(f64, f64, f64)> {
+    let games = (wins + losses + draws) as f64;
+    if games == 0.0 {
+        return None;
+    }
+    let score = (wins as f64 + draws as f64 / 2.0) / games;
+    let variance = (wins as f64 * (1.0 - score).powi(2)
+        + losses as f64 * score.powi(2)
+        + draws as f64 * (0.5 - score).powi(2))
+        / games;
+    return Some((games, score, variance));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_and_score_are_inverses() {
+        assert_eq!(elo_from_score(0.5), 0.0);
+        assert!((elo_from_score(0.64) - 100.0).abs() < 1.0);
+        assert!((elo_from_score(0.36) + 100.0).abs() < 1.0);
+        assert_eq!(elo_from_score(1.0), f64::INFINITY);
+        assert_eq!(elo_from_score(0.0), f64::NEG_INFINITY);
+        for elo in [-300.0, -50.0, 0.0, 25.0, 400.0] {
+            assert!((elo_from_score(score_from_elo(elo)) - elo).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn estimate_brackets_the_elo() {
+        assert_eq!(EloEstimate::new(0, 0, 0), None);
+
+        let even = EloEstimate::new(10, 10, 5).unwrap();
+        assert_eq!(even.elo, 0.0);
+        assert!(even.low < 0.0 && even.high > 0.0);
+        assert!((even.low + even.high).abs() < 1e-9);
+
+        let ahead = EloEstimate::new(30, 10, 10).unwrap();
+        assert!(ahead.low < ahead.elo && ahead.elo < ahead.high);
+        assert!(ahead.low > 0.0);
+
+        // More games at the same score give a narrower interval
+        let more = EloEstimate::new(300, 100, 100).unwrap();
+        assert!((more.elo - ahead.elo).abs() < 1e-9);
+        assert!(more.high - more.low < ahead.high - ahead.low);
+    }
+
+    #[test]
+    fn sprt_bounds_and_decisions() {
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 1e-3);
+        assert!((upper - 2.944).abs() < 1e-3);
+        assert_eq!(sprt.decision(0.0), None);
+        assert_eq!(sprt.decision(upper), Some(SprtDecision::Accept));
+        assert_eq!(sprt.decision(lower), Some(SprtDecision::Reject));
+    }
+
+    #[test]
+    fn llr_leans_towards_the_likelier_hypothesis() {
+        let sprt = Sprt::default();
+        assert_eq!(sprt.llr(0, 0, 0), 0.0);
+        assert!(sprt.llr(60, 40, 20) > 0.0);
+        assert!(sprt.llr(40, 60, 20) < 0.0);
+        // More games at the same score are stronger evidence
+        assert!(sprt.llr(600, 400, 200) > sprt.llr(60, 40, 20));
+        assert_eq!(
+            sprt.decision(sprt.llr(600, 400, 200)),
+            Some(SprtDecision::Accept)
+        );
+        assert_eq!(
+            sprt.decision(sprt.llr(400, 600, 200)),
+            Some(SprtDecision::Reject)
+        );
+        // Winning every game still gives a finite ratio
+        assert!(sprt.llr(10, 0, 0).is_finite() && sprt.llr(10, 0, 0) > 0.0);
+        assert!(sprt.llr(0, 0, 10).is_finite());
+    }
+}
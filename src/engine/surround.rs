@@ -0,0 +1,319 @@
+// Queen-surround solver
+//
+// Proves or refutes "the attacker can surround the other queen within n of their own moves,
+// however the defender plays" with a depth-limited AND/OR search: on the attacker's turns one
+// move has to work, on the defender's turns every move has to. A line fails for the attacker if
+// the game ends any other way first, including the attacker surrounding both queens at once.
+//
+// Results are cached by position, the piece moved last turn (which can't be moved or thrown by a
+// pillbug this turn) and moves left, so a position reached by different orders of the same moves
+// is only solved once. That means the cache doesn't know how else a position was reached, so
+// draws by repetition part way through a line aren't noticed.
+//
+// prove_surround solves a Game position on its own. Search can also call SurroundSolver at its
+// leaves (EngineConfig::surround) to spot forced wins just over its horizon.
+
+use std::collections::HashMap;
+
+use crate::game::compact::{piece_move, CompactGame, CompactMove};
+use crate::game::piece::PieceColor;
+use crate::game::piece_move::PieceMove;
+use crate::game::{Game, GameState};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SurroundProof {
+    // The attacker surrounds the queen on their moves-th move at the latest, whatever the defender
+    // does. line is one way it can go, with the defender holding out as long as possible.
+    Win { moves: usize, line: Vec<PieceMove> },
+    // The defender can hold out for longer than the moves searched (or the game is already over)
+    NoWin,
+}
+
+// Whether attacker can force a win by surrounding the other queen within max_moves of their own
+// moves, from game's position (with either player to move)
+pub fn prove_surround(game: &Game, attacker: PieceColor, max_moves: usize) -> SurroundProof {
+    let mut compact = CompactGame::from(game);
+    if compact.is_over() {
+        return SurroundProof::NoWin;
+    }
+    let mut solver = SurroundSolver::new(attacker);
+    let moves = match solver.fastest(&mut compact, max_moves) {
+        Some(moves) => moves,
+        None => return SurroundProof::NoWin,
+    };
+
+    let mut replay = game.clone();
+    let mut line: Vec<PieceMove> = Vec::new();
+    for m in solver.line(&mut compact, moves) {
+        let m = piece_move(&replay, m).expect("Solved moves are for pieces in the game");
+        replay
+            .make_move(m.clone())
+            .expect("Solved moves can be made");
+        line.push(m);
+    }
+    return SurroundProof::Win { moves, line };
+}
+
+pub struct SurroundSolver {
+    attacker: PieceColor,
+    // Positions visited
+    pub nodes: u64,
+    // key() -> whether the attacker wins
+    table: HashMap<(u64, Option<u8>, usize), bool>,
+}
+
+impl SurroundSolver {
+    pub fn new(attacker: PieceColor) -> SurroundSolver {
+        return SurroundSolver {
+            attacker,
+            nodes: 0,
+            table: HashMap::new(),
+        };
+    }
+
+    pub fn attacker(&self) -> PieceColor {
+        return self.attacker;
+    }
+
+    // The fewest attacker moves, up to max_moves, that the attacker can surround the queen in
+    // from game (which has to be still in progress), or None if they can't. game is left as it was.
+    pub fn fastest(&mut self, game: &mut CompactGame, max_moves: usize) -> Option<usize> {
+        return (1..=max_moves).find(|&moves| self.can_surround(game, moves));
+    }
+
+    // Whether the attacker can surround the queen within moves of their own moves from game
+    pub fn can_surround(&mut self, game: &mut CompactGame, moves: usize) -> bool {
+        if game.current_player() == self.attacker {
+            return self.attack(game, moves);
+        }
+        return self.defend(game, moves);
+    }
+
+    fn attack(&mut self, game: &mut CompactGame, moves: usize) -> bool {
+        if moves == 0 {
+            return false;
+        }
+        let key = key(game, moves);
+        if let Some(&won) = self.table.get(&key) {
+            return won;
+        }
+        self.nodes += 1;
+
+        let mut candidates = game.get_all_moves();
+        if moves > 1 {
+            // Moves that close in on the queen first, since they're the likeliest to work
+            self.order(game, &mut candidates, true);
+        }
+        let mut won = false;
+        for m in candidates {
+            game.make_move(m);
+            won = match self.outcome(game) {
+                Some(attacker_won) => attacker_won,
+                None => moves > 1 && self.defend(game, moves - 1),
+            };
+            game.umake_move(m);
+            if won {
+                break;
+            }
+        }
+        self.table.insert(key, won);
+        return won;
+    }
+
+    // moves is what the attacker has left after the defender's move
+    fn defend(&mut self, game: &mut CompactGame, moves: usize) -> bool {
+        if moves == 0 {
+            return false;
+        }
+        let key = key(game, moves);
+        if let Some(&won) = self.table.get(&key) {
+            return won;
+        }
+        self.nodes += 1;
+
+        let mut candidates = game.get_all_moves();
+        // Moves that free up the queen first, since they're the likeliest to hold out
+        self.order(game, &mut candidates, false);
+        let mut won = true;
+        for m in candidates {
+            game.make_move(m);
+            won = match self.outcome(game) {
+                Some(attacker_won) => attacker_won,
+                None => self.attack(game, moves),
+            };
+            game.umake_move(m);
+            if !won {
+                break;
+            }
+        }
+        self.table.insert(key, won);
+        return won;
+    }
+
+    // Some(true) once the attacker has won, Some(false) if the game has ended any other way
+    fn outcome(&self, game: &CompactGame) -> Option<bool> {
+        match game.get_state() {
+            GameState::WhiteWins => return Some(self.attacker == PieceColor::White),
+            GameState::BlackWins => return Some(self.attacker == PieceColor::Black),
+            GameState::Draw => return Some(false),
+            GameState::NotStarted | GameState::InProgress => return None,
+        }
+    }
+
+    // Sorts moves by how many of the cells around the defender's queen they leave occupied, most
+    // first when closing_in
+    fn order(&self, game: &mut CompactGame, moves: &mut [CompactMove], closing_in: bool) {
+        let defender = self.attacker.opponent();
+        moves.sort_by_cached_key(|&m| {
+            game.make_move(m);
+            let neighbors = game.queen_neighbors(defender) as isize;
+            game.umake_move(m);
+            if closing_in {
+                return -neighbors;
+            }
+            return neighbors;
+        });
+    }
+
+    // A line of play from game where the attacker wins on their moves-th move (which has to be the
+    // fewest they can do it in) and the defender takes as long as they can to lose
+    fn line(&mut self, game: &mut CompactGame, moves: usize) -> Vec<CompactMove> {
+        let mut line: Vec<CompactMove> = Vec::new();
+        let mut left = moves;
+        while self.outcome(game).is_none() && left > 0 {
+            let candidates = game.get_all_moves();
+            let chosen = if game.current_player() == self.attacker {
+                // Any move that still wins in time
+                *candidates
+                    .iter()
+                    .find(|&&m| {
+                        game.make_move(m);
+                        let won = match self.outcome(game) {
+                            Some(attacker_won) => attacker_won,
+                            None => self.defend(game, left - 1),
+                        };
+                        game.umake_move(m);
+                        return won;
+                    })
+                    .expect("The attacker has a winning move")
+            } else {
+                // The move that leaves the attacker needing the most moves
+                *candidates
+                    .iter()
+                    .max_by_key(|&&m| {
+                        game.make_move(m);
+                        let needed = match self.outcome(game) {
+                            Some(_) => 0,
+                            None => self.fastest(game, left).unwrap_or(left),
+                        };
+                        game.umake_move(m);
+                        return needed;
+                    })
+                    .expect("The defender always has a move or a pass")
+            };
+            if game.current_player() == self.attacker {
+                left -= 1;
+            }
+            game.make_move(chosen);
+            line.push(chosen);
+        }
+        // Leave game as it was
+        for &m in line.iter().rev() {
+            game.umake_move(m);
+        }
+        return line;
+    }
+}
+
+// What the result of searching game with moves left depends on. The piece moved last turn
+// changes which moves there are, so the same position after a different last move is kept apart.
+fn key(game: &CompactGame, moves: usize) -> (u64, Option<u8>, usize) {
+    return (game.position_hash(), game.last_moved_piece(), moves);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_type::GameType;
+    use crate::game::random::{random_game, RandomGameConfig, Weighting};
+    use crate::game::uhp::import_from_game_string;
+
+    fn compact(moves: &str) -> CompactGame {
+        let game_string = format!(
+            r"Base;InProgress;White[6];wQ;bQ wQ-;wA1 -wQ;bA1 bQ-;wA2 \wQ;bA2 bQ/;{}",
+            moves
+        );
+        return CompactGame::from(&import_from_game_string(&game_string).unwrap());
+    }
+
+    #[test]
+    fn key_tells_apart_the_last_piece_moved() {
+        // The same ant moves in either order, so only which black ant moved last differs
+        let first = compact(r"wA1 bQ\;bA1 /wQ;wA2 wQ/;bA2 wQ\");
+        let second = compact(r"wA2 wQ/;bA2 wQ\;wA1 bQ\;bA1 /wQ");
+        assert_eq!(first.position_hash(), second.position_hash());
+        assert_ne!(first.last_moved_piece(), second.last_moved_piece());
+        assert_ne!(key(&first, 2), key(&second, 2));
+    }
+
+    // Positions one move from the end of random games the last mover won by surrounding the
+    // other queen, with the winner to move
+    fn one_move_from_winning() -> Vec<Game> {
+        let mut positions: Vec<Game> = Vec::new();
+        for seed in 0..12 {
+            let config = RandomGameConfig {
+                seed,
+                game_type: GameType::BASE,
+                weighting: Weighting::Aggressive,
+                ..RandomGameConfig::default()
+            };
+            let mut game = random_game(&config);
+            let winner = match game.get_state() {
+                GameState::WhiteWins => PieceColor::White,
+                GameState::BlackWins => PieceColor::Black,
+                _ => continue,
+            };
+            let last = game.move_list.last().cloned().unwrap();
+            game.umake_move(last).unwrap();
+            // Players can surround their own queen too
+            if game.current_player() == winner {
+                positions.push(game);
+            }
+        }
+        return positions;
+    }
+
+    #[test]
+    fn proves_a_surround_in_one() {
+        let positions = one_move_from_winning();
+        assert!(!positions.is_empty());
+        for game in positions {
+            let attacker = game.current_player();
+            let line = match prove_surround(&game, attacker, 2) {
+                SurroundProof::Win { moves: 1, line } => line,
+                other => panic!("expected a win in one, got {:?}", other),
+            };
+            assert_eq!(line.len(), 1);
+            let mut played = game.clone();
+            played.make_move(line[0].clone()).unwrap();
+            let won = if attacker == PieceColor::White {
+                GameState::WhiteWins
+            } else {
+                GameState::BlackWins
+            };
+            assert_eq!(played.get_state(), won);
+        }
+    }
+
+    #[test]
+    fn no_win_once_the_game_is_over() {
+        let mut game = one_move_from_winning().remove(0);
+        let attacker = game.current_player();
+        let m = match prove_surround(&game, attacker, 1) {
+            SurroundProof::Win { line, .. } => line[0].clone(),
+            SurroundProof::NoWin => panic!("expected a win in one"),
+        };
+        game.make_move(m).unwrap();
+        assert_eq!(prove_surround(&game, attacker, 3), SurroundProof::NoWin);
+    }
+}
@@ -1,91 +1,574 @@
+use crate::game::game_type::GameType;
+use crate::game::hand::Hand;
 use crate::game::hex::{Hex, HexEdge};
-use crate::game::piece::{Piece, PieceType};
-use crate::game::piece_move::PieceMove;
+use crate::game::piece::{Piece, PieceId, PieceType};
+use crate::game::piece_move::{gate_blocked, MoveError, PieceMove};
+use crate::game::rules::Rules;
 use petgraph::graph::NodeIndex;
+use petgraph::visit::{Dfs, EdgeRef, NodeFiltered};
 use petgraph::Graph;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
-use self::hex::get_edge_types;
+use self::hand::HAND_ORDER;
+use self::hex::{get_all_edge_types, get_edge_types};
 use self::piece::PieceColor;
 
+pub mod compact;
+pub mod danger;
+pub mod error;
+pub mod game_type;
+pub mod hand;
 pub mod hex;
+pub mod ign;
+pub mod notation;
 pub mod piece;
 pub mod piece_move;
+pub mod position_stats;
+pub mod random;
+pub mod rules;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod sgf;
+pub mod uhp;
+pub mod validate;
 
-pub fn get_starting_hand(color: PieceColor) -> Vec<Piece> {
-    return vec![
-        Piece::new(color, PieceType::QueenBee, "q"),
-        Piece::new(color, PieceType::Spider, "s1"),
-        Piece::new(color, PieceType::Spider, "s2"),
-        Piece::new(color, PieceType::Beetle, "b1"),
-        Piece::new(color, PieceType::Beetle, "b2"),
-        Piece::new(color, PieceType::Grasshopper, "g1"),
-        Piece::new(color, PieceType::Grasshopper, "g2"),
-        Piece::new(color, PieceType::Grasshopper, "g3"),
-        Piece::new(color, PieceType::Ant, "a1"),
-        Piece::new(color, PieceType::Ant, "a2"),
-        Piece::new(color, PieceType::Ant, "a3"),
-        Piece::new(color, PieceType::Mosquito, "m"),
-        Piece::new(color, PieceType::Ladybug, "l"),
-        Piece::new(color, PieceType::Pillbug, "p"),
-    ];
+pub use self::error::GameError;
+pub use self::ign::import_from_ign;
+pub use self::sgf::import_from_sgf;
+pub use self::uhp::import_from_game_string;
+
+pub fn get_starting_hand(color: PieceColor, game_type: GameType) -> Vec<Piece> {
+    return Hand::starting(color, game_type).pieces();
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameState {
+    NotStarted,
+    InProgress,
+    Draw,
+    WhiteWins,
+    BlackWins,
+}
+
+// Why a game ended in a draw
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DrawReason {
+    BothQueensSurrounded,
+    // The same position came up three times (with Rules::draw_by_repetition)
+    Repetition,
+    // Both players reached Rules::turn_limit
+    TurnLimit,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "serialize::GameRepr", try_from = "serialize::GameRepr")
+)]
 pub struct Game {
     pub grid: Graph<Piece, HexEdge, petgraph::Undirected>,
-    pub p1_hand: Vec<Piece>,
-    pub p2_hand: Vec<Piece>,
+    pub p1_hand: Hand,
+    pub p2_hand: Hand,
     pub move_list: Vec<PieceMove>,
+    // Moves made before move_list starts, for a game set up from a position (see position())
+    pub start_ply: usize,
+    pub game_type: GameType,
+    pub rules: Rules,
+    // position_hash() after each move in move_list
+    history: Vec<u64>,
+}
+
+// An empty grid with nothing in either hand
+impl Default for Game {
+    fn default() -> Self {
+        return Game {
+            grid: Graph::<Piece, HexEdge, petgraph::Undirected>::new_undirected(),
+            p1_hand: Hand::new(PieceColor::Black),
+            p2_hand: Hand::new(PieceColor::White),
+            move_list: Vec::new(),
+            start_ply: 0,
+            game_type: GameType::default(),
+            rules: Rules::default(),
+            history: Vec::new(),
+        };
+    }
 }
 
 impl Game {
+    // A game with every expansion bug (see with_type)
     pub fn new() -> Game {
+        return Game::with_type(GameType::default());
+    }
+
+    pub fn with_type(game_type: GameType) -> Game {
         return Game {
             grid: Graph::<Piece, HexEdge, petgraph::Undirected>::new_undirected(),
-            p1_hand: get_starting_hand(PieceColor::Black),
-            p2_hand: get_starting_hand(PieceColor::White),
+            p1_hand: Hand::starting(PieceColor::Black, game_type),
+            p2_hand: Hand::starting(PieceColor::White, game_type),
             move_list: Vec::new(),
+            start_ply: 0,
+            game_type,
+            rules: Rules::default(),
+            history: Vec::new(),
         };
     }
 
-    pub fn export_ign(&self) -> &str {
-        todo!();
+    // Moves made so far, by both players
+    pub fn ply(&self) -> usize {
+        return self.start_ply + self.move_list.len();
     }
 
+    // White always moves first
+    pub fn current_player(&self) -> PieceColor {
+        if self.ply().is_multiple_of(2) {
+            return PieceColor::White;
+        }
+        return PieceColor::Black;
+    }
+
+    // The current player's turn number, counting from 1
+    pub fn turn_number(&self) -> usize {
+        return self.ply() / 2 + 1;
+    }
+
+    // The current position on its own: the same grid, hands, player to move and turn, with the
+    // moves that led to it forgotten
+    pub fn position(&self) -> Game {
+        let mut position = self.clone();
+        position.start_ply = self.ply();
+        position.move_list.clear();
+        position.history.clear();
+        return position;
+    }
+
+    pub fn hand(&self, color: PieceColor) -> &Hand {
+        match color {
+            PieceColor::Black => return &self.p1_hand,
+            PieceColor::White => return &self.p2_hand,
+        }
+    }
+
+    fn hand_mut(&mut self, color: PieceColor) -> &mut Hand {
+        match color {
+            PieceColor::Black => return &mut self.p1_hand,
+            PieceColor::White => return &mut self.p2_hand,
+        }
+    }
+
+    pub fn get_state(&self) -> GameState {
+        let white_surrounded = self.queen_surrounded(PieceColor::White);
+        let black_surrounded = self.queen_surrounded(PieceColor::Black);
+        if white_surrounded && black_surrounded {
+            return GameState::Draw;
+        } else if white_surrounded {
+            return GameState::BlackWins;
+        } else if black_surrounded {
+            return GameState::WhiteWins;
+        } else if self.draw_reason().is_some() {
+            return GameState::Draw;
+        } else if self.grid.node_count() == 0 {
+            return GameState::NotStarted;
+        }
+        return GameState::InProgress;
+    }
+
+    // None unless get_state() is Draw
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        let white_surrounded = self.queen_surrounded(PieceColor::White);
+        let black_surrounded = self.queen_surrounded(PieceColor::Black);
+        if white_surrounded && black_surrounded {
+            return Some(DrawReason::BothQueensSurrounded);
+        } else if white_surrounded || black_surrounded {
+            return None;
+        } else if self.rules.draw_by_repetition && self.repetitions() >= 3 {
+            return Some(DrawReason::Repetition);
+        } else if let Some(limit) = self.rules.turn_limit {
+            if self.ply() >= 2 * limit {
+                return Some(DrawReason::TurnLimit);
+            }
+        }
+        return None;
+    }
+
+    // How many times the current position has come up since the first move in move_list
+    pub fn repetitions(&self) -> usize {
+        return match self.history.last() {
+            Some(current) => self.history.iter().filter(|&h| h == current).count(),
+            None => 1,
+        };
+    }
+
+    // Identifies a position (the grid up to translation, both hands and who is to move) without
+    // depending on how it was reached
+    pub fn position_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        normalized_grid(self).hash(&mut hasher);
+        self.p1_hand.hash(&mut hasher);
+        self.p2_hand.hash(&mut hasher);
+        self.current_player().hash(&mut hasher);
+        return hasher.finish();
+    }
+
+    // position_hash() after each move in move_list
+    pub fn position_history(&self) -> &[u64] {
+        return &self.history;
+    }
+
+    // Works out the history for a game whose move_list was filled in without make_move. Errors if
+    // the moves can't be taken back from the grid, leaving the history as it was.
+    fn rebuild_history(&mut self) -> Result<(), GameError> {
+        let mut rewind = self.clone();
+        let mut history: Vec<u64> = Vec::new();
+        for m in self.move_list.iter().rev() {
+            history.push(rewind.position_hash());
+            rewind.umake_move(m.clone())?;
+        }
+        history.reverse();
+        self.history = history;
+        return Ok(());
+    }
+
+    pub fn is_over(&self) -> bool {
+        return matches!(
+            self.get_state(),
+            GameState::Draw | GameState::WhiteWins | GameState::BlackWins
+        );
+    }
+
+    pub fn get_piece(&self, piece_id: PieceId) -> Option<&Piece> {
+        return self.grid.node_weights().find(|p| p.id == piece_id);
+    }
+
+    // The piece on top of the stack at hex's spot on the grid, whatever hex.z is
+    pub fn top_piece_at(&self, hex: Hex) -> Option<&Piece> {
+        return self
+            .grid
+            .node_weights()
+            .filter(|p| p.hex.same_cell(hex))
+            .max_by_key(|p| p.hex.z);
+    }
+
+    pub fn stack_height(&self, hex: Hex) -> isize {
+        return self
+            .grid
+            .node_weights()
+            .filter(|p| p.hex.same_cell(hex))
+            .count() as isize;
+    }
+
+    pub fn queen_placed(&self, color: PieceColor) -> bool {
+        return self
+            .grid
+            .node_weights()
+            .any(|p| p.id.color == color && p.id.piece_type == PieceType::QueenBee);
+    }
+
+    pub fn queen_surrounded(&self, color: PieceColor) -> bool {
+        if let Some(queen) = self.queen(color) {
+            return queen
+                .hex
+                .get_neighbors()
+                .iter()
+                .all(|&n| self.stack_height(n) > 0);
+        }
+        return false;
+    }
+
+    // The piece moved on the previous turn. It can't be moved again (by itself or by a pillbug)
+    // until the current player has had their turn.
+    pub fn last_moved_piece(&self) -> Option<PieceId> {
+        if let Some(PieceMove::Move { piece_id, .. }) = self.move_list.last() {
+            return Some(*piece_id);
+        }
+        return None;
+    }
+
+    // Every legal move for the current player, or a single Pass if they have none
     pub fn get_all_moves(&self) -> Vec<PieceMove> {
         let mut valid_moves: Vec<PieceMove> = Vec::new();
-        for piece in self.grid.node_weights() {
-            if piece.can_move(self) {
-                for pm in piece.get_moves(&self) {
-                    valid_moves.push(pm);
+        if self.is_over() {
+            return valid_moves;
+        }
+
+        valid_moves.extend(self.get_placements());
+
+        // Pieces on the grid can only move once their queen has been placed
+        let color = self.current_player();
+        if self.queen_placed(color) {
+            for piece in self.grid.node_weights() {
+                if piece.id.color != color
+                    || self.top_piece_at(piece.hex).map(|p| p.id) != Some(piece.id)
+                {
+                    continue;
+                }
+                for pm in piece.get_moves(self) {
+                    if pm.piece_id() != self.last_moved_piece() && !valid_moves.contains(&pm) {
+                        valid_moves.push(pm);
+                    }
                 }
             }
         }
+
+        if valid_moves.is_empty() {
+            valid_moves.push(PieceMove::Pass);
+        }
         return valid_moves;
     }
 
-    /// Assumes piece_move is valid move
-    pub fn make_move(&mut self, piece_move: PieceMove) {
-        // get piece from node:
-        let piece = self
-            .grid
-            .node_weight(piece_move.piece_node)
-            .expect("Unable to find Piece")
-            .clone();
-        self.grid.remove_node(piece_move.piece_node);
-        self.add_to_grid(piece, piece_move.hex);
+    pub fn get_placements(&self) -> Vec<PieceMove> {
+        let mut placements: Vec<PieceMove> = Vec::new();
+        let color = self.current_player();
+
+        // Only the lowest numbered piece of each type can be placed, and the queen has to be down
+        // by the rules' deadline (but can't go first in a tournament opening)
+        let mut candidates: Vec<PieceId> = Vec::new();
+        for piece_type in HAND_ORDER {
+            if let Some(id) = self.hand(color).next(piece_type) {
+                candidates.push(id);
+            }
+        }
+        let (turn, queen_placed) = (self.turn_number(), self.queen_placed(color));
+        candidates.retain(|c| {
+            return self
+                .rules
+                .check_placement(turn, queen_placed, c.piece_type)
+                .is_ok();
+        });
+
+        let mut hexes: Vec<Hex> = Vec::new();
+        if self.grid.node_count() == 0 {
+            hexes.push(Hex::new());
+        } else {
+            let own_pieces_placed = self.grid.node_weights().any(|p| {
+                p.id.color == color && self.top_piece_at(p.hex).map(|t| t.id) == Some(p.id)
+            });
+            for piece in self.grid.node_weights() {
+                for n in piece.hex.get_neighbors() {
+                    let n = n.with_z(0);
+                    if hexes.contains(&n) || self.stack_height(n) > 0 {
+                        continue;
+                    }
+                    // New pieces have to touch their own colour and nothing of the opponent's,
+                    // except for each player's very first placement
+                    let neighbor_colors: Vec<PieceColor> = n
+                        .get_neighbors()
+                        .iter()
+                        .filter_map(|&h| self.top_piece_at(h))
+                        .map(|p| p.id.color)
+                        .collect();
+                    if !own_pieces_placed || neighbor_colors.iter().all(|&c| c == color) {
+                        hexes.push(n);
+                    }
+                }
+            }
+        }
+
+        for piece_id in candidates {
+            for &hex in &hexes {
+                placements.push(PieceMove::Place { piece_id, hex });
+            }
+        }
+        return placements;
+    }
+
+    /// Assumes piece_move is valid move (use try_make_move for moves from outside). Only errors
+    /// when the move can't be made at all, e.g. its piece is somewhere else.
+    pub fn make_move(&mut self, piece_move: PieceMove) -> Result<(), GameError> {
+        match &piece_move {
+            PieceMove::Place { piece_id, hex } => {
+                if !self.hand(piece_id.color).contains(*piece_id) {
+                    return Err(GameError::PieceNotInHand(*piece_id));
+                }
+                self.add_to_grid(Piece::from(*piece_id), *hex)?;
+            }
+            PieceMove::Move {
+                piece_id,
+                from,
+                hex,
+            } => {
+                match self.get_piece(*piece_id) {
+                    None => return Err(GameError::PieceNotOnGrid(*piece_id)),
+                    Some(piece) if piece.hex != *from => {
+                        return Err(GameError::WrongOrigin {
+                            piece_id: *piece_id,
+                            from: *from,
+                        })
+                    }
+                    Some(_) => {}
+                }
+                // Checked before the piece is lifted, so a failed move leaves it where it was
+                if self.occupied_by_other(*hex, *piece_id) {
+                    return Err(GameError::HexOccupied(*hex));
+                }
+                let piece = self.remove_from_grid(*piece_id)?;
+                self.add_to_grid(piece, *hex)?;
+            }
+            PieceMove::Pass => {}
+        }
         self.move_list.push(piece_move);
+        self.history.push(self.position_hash());
+        #[cfg(debug_assertions)]
+        if let Err(e) = self.validate() {
+            panic!("invalid game after {:?}: {}", self.move_list.last(), e);
+        }
+        return Ok(());
     }
 
-    pub fn umake_move(&mut self, piece_move: PieceMove) {
-        // piece_move should contain the previous location of the piece
-        todo!();
+    // Makes piece_move if it's legal, otherwise leaves the game as it is and says why not
+    pub fn try_make_move(&mut self, piece_move: PieceMove) -> Result<(), MoveError> {
+        self.check_move(&piece_move)?;
+        self.make_move(piece_move)
+            .expect("Legal moves can always be made");
+        return Ok(());
     }
 
-    pub fn add_to_grid(&mut self, mut piece: Piece, hex: Hex) -> NodeIndex {
-        // Remove piece from both hands:
-        self.p1_hand.retain(|p| *p != piece);
-        self.p2_hand.retain(|p| *p != piece);
+    // Ok if piece_move is one of get_all_moves(), otherwise the reason it isn't
+    pub fn check_move(&self, piece_move: &PieceMove) -> Result<(), MoveError> {
+        if self.is_over() {
+            return Err(MoveError::GameOver);
+        }
+        if self.get_all_moves().contains(piece_move) {
+            return Ok(());
+        }
+
+        let color = self.current_player();
+        match piece_move {
+            PieceMove::Place { piece_id, .. } => {
+                let piece_id = *piece_id;
+                if piece_id.color != color {
+                    return Err(MoveError::NotYourTurn(piece_id));
+                }
+                if !self.game_type.includes(piece_id.piece_type) {
+                    return Err(MoveError::PieceNotInGame(piece_id));
+                }
+                if !self.hand(color).contains(piece_id) {
+                    return Err(MoveError::PieceNotInHand(piece_id));
+                }
+                self.rules.check_placement(
+                    self.turn_number(),
+                    self.queen_placed(color),
+                    piece_id.piece_type,
+                )?;
+                if let Some(expected) = self.hand(color).next(piece_id.piece_type) {
+                    if expected != piece_id {
+                        return Err(MoveError::NotLowestNumbered { piece_id, expected });
+                    }
+                }
+                return Err(MoveError::IllegalPlacement(piece_id));
+            }
+            PieceMove::Move {
+                piece_id,
+                from,
+                hex,
+            } => {
+                let piece_id = *piece_id;
+                let piece = match self.get_piece(piece_id) {
+                    Some(p) => p,
+                    None => return Err(MoveError::PieceNotOnGrid(piece_id)),
+                };
+                if piece.hex != *from {
+                    return Err(MoveError::WrongOrigin {
+                        piece_id,
+                        from: *from,
+                    });
+                }
+                if !self.queen_placed(color) {
+                    return Err(MoveError::QueenNotPlaced);
+                }
+                if self.top_piece_at(piece.hex).map(|p| p.id) != Some(piece_id) {
+                    return Err(MoveError::PieceCovered(piece_id));
+                }
+                if self.last_moved_piece() == Some(piece_id) {
+                    return Err(MoveError::PieceJustMoved(piece_id));
+                }
+                // The other player's pieces can only be moved by a pillbug (or a mosquito next to
+                // one) that is next to them
+                let next_to_pillbug = piece.hex.get_neighbors().iter().any(|&n| {
+                    self.top_piece_at(n).is_some_and(|p| {
+                        p.id.color == color
+                            && matches!(p.id.piece_type, PieceType::Pillbug | PieceType::Mosquito)
+                    })
+                });
+                if piece.id.color != color && !next_to_pillbug {
+                    return Err(MoveError::NotYourTurn(piece_id));
+                }
+                if !piece.can_move(self) {
+                    return Err(MoveError::PiecePinned(piece_id));
+                }
+                if piece.id.color != color {
+                    return Err(MoveError::IllegalDestination(piece_id));
+                }
+                if let Some(dir) = get_edge_types()
+                    .into_iter()
+                    .find(|&e| piece.hex.get_neighbor(e).same_cell(*hex))
+                {
+                    if gate_blocked(piece.hex, dir, self, piece_id) {
+                        return Err(MoveError::GateBlocked(piece_id));
+                    }
+                }
+                return Err(MoveError::IllegalDestination(piece_id));
+            }
+            PieceMove::Pass => return Err(MoveError::CannotPass),
+        }
+    }
+
+    /// Takes back piece_move, which must be the last move made
+    pub fn umake_move(&mut self, piece_move: PieceMove) -> Result<(), GameError> {
+        if self.move_list.last() != Some(&piece_move) {
+            return Err(GameError::NotLastMove);
+        }
+        match &piece_move {
+            PieceMove::Place { piece_id, .. } => {
+                let mut hand = *self.hand(piece_id.color);
+                if !hand.put_back(*piece_id) {
+                    return Err(GameError::PieceOutOfOrder(*piece_id));
+                }
+                self.remove_from_grid(*piece_id)?;
+                *self.hand_mut(piece_id.color) = hand;
+            }
+            PieceMove::Move { piece_id, from, .. } => {
+                if self.occupied_by_other(*from, *piece_id) {
+                    return Err(GameError::HexOccupied(*from));
+                }
+                let piece = self.remove_from_grid(*piece_id)?;
+                self.add_to_grid(piece, *from)?;
+            }
+            PieceMove::Pass => {}
+        }
+        self.move_list.pop();
+        self.history.pop();
+        return Ok(());
+    }
+
+    // True if some piece other than piece_id is at exactly hex
+    fn occupied_by_other(&self, hex: Hex, piece_id: PieceId) -> bool {
+        return self
+            .grid
+            .node_weights()
+            .any(|p| p.hex == hex && p.id != piece_id);
+    }
+
+    fn remove_from_grid(&mut self, piece_id: PieceId) -> Result<Piece, GameError> {
+        let piece_node = self
+            .node_of(piece_id)
+            .ok_or(GameError::PieceNotOnGrid(piece_id))?;
+        // remove_node only returns None for an index that isn't in the graph
+        return Ok(self.grid.remove_node(piece_node).unwrap());
+    }
+
+    pub fn add_to_grid(&mut self, mut piece: Piece, hex: Hex) -> Result<NodeIndex, GameError> {
+        if self.grid.node_weights().any(|p| p.hex == hex) {
+            return Err(GameError::HexOccupied(hex));
+        }
+        // Take piece out of its hand if it's there (it won't be if it's moving)
+        let hand = self.hand_mut(piece.id.color);
+        if hand.contains(piece.id) && !hand.take(piece.id) {
+            return Err(GameError::PieceOutOfOrder(piece.id));
+        }
 
         // Update new_piece
         piece.in_hand = false;
@@ -95,32 +578,92 @@ impl Game {
 
         self.update_piece_edges(new_piece);
 
-        return new_piece;
+        return Ok(new_piece);
     }
 
+    // Links piece to every piece touching it: beside it at the same height, or directly above or
+    // below it. Each pair of pieces gets exactly one edge, pointing from piece to the other one and
+    // labelled with the direction the other one lies in (see neighbors_of). Any edges piece
+    // already had are replaced.
     pub fn update_piece_edges(&mut self, piece: NodeIndex) {
-        for hex_edge in get_edge_types() {
-            let piece_node = self
-                .grid
-                .node_indices()
-                .find(|&p| self.grid[p].hex == self.grid[piece].hex)
-                .expect("Error finding piece_node in game_grid");
-
+        while let Some(edge) = self.grid.edges(piece).next().map(|e| e.id()) {
+            self.grid.remove_edge(edge);
+        }
+        for hex_edge in get_all_edge_types() {
             let neighbor_hex = self.grid[piece].hex.get_neighbor(hex_edge);
-
-            if let Some(found_neighbor_p) = self
-                .grid
-                .node_indices()
-                .find(|&p| self.grid[p].hex == neighbor_hex)
-            {
-                // Add connection both ways
-                self.grid.add_edge(piece_node, found_neighbor_p, hex_edge);
-                self.grid
-                    .add_edge(found_neighbor_p, piece_node, hex_edge.get_opposite());
+            if let Some(neighbor) = self.node_at(neighbor_hex) {
+                self.grid.add_edge(piece, neighbor, hex_edge);
             }
         }
     }
 
+    // The grid node of the piece at exactly hex, height included
+    pub fn node_at(&self, hex: Hex) -> Option<NodeIndex> {
+        return self.grid.node_indices().find(|&n| self.grid[n].hex == hex);
+    }
+
+    pub fn node_of(&self, piece_id: PieceId) -> Option<NodeIndex> {
+        return self
+            .grid
+            .node_indices()
+            .find(|&n| self.grid[n].id == piece_id);
+    }
+
+    // Every piece touching piece_id, with the direction it lies in from piece_id: N to NW for the
+    // pieces beside it and T and B for the ones on top of it and underneath it
+    pub fn neighbors_of(&self, piece_id: PieceId) -> Vec<(HexEdge, &Piece)> {
+        let node = match self.node_of(piece_id) {
+            Some(node) => node,
+            None => return Vec::new(),
+        };
+        return self
+            .grid
+            .edges(node)
+            .map(|e| {
+                // An edge's label is the direction from the piece it was added from, so it's turned
+                // round when that's the other piece
+                let (source, target) = self.grid.edge_endpoints(e.id()).unwrap();
+                if source == node {
+                    return (*e.weight(), &self.grid[target]);
+                }
+                return (e.weight().get_opposite(), &self.grid[source]);
+            })
+            .collect();
+    }
+
+    // The piece in direction edge from piece_id, e.g. which piece is NE of it or on top of it
+    pub fn neighbor_of(&self, piece_id: PieceId, edge: HexEdge) -> Option<&Piece> {
+        return self
+            .neighbors_of(piece_id)
+            .into_iter()
+            .find(|(e, _)| *e == edge)
+            .map(|(_, piece)| piece);
+    }
+
+    // True if the pieces on the grid all join up into one hive (or there aren't any)
+    pub fn is_one_hive(&self) -> bool {
+        return self.is_one_hive_without(None);
+    }
+
+    // Same as is_one_hive, as if without (which has to be on top of its stack) were lifted off
+    // the grid
+    pub fn is_one_hive_without(&self, without: Option<PieceId>) -> bool {
+        // A search through the grid's edges. Stacked pieces are linked to the piece underneath,
+        // so they join up exactly when the ground pieces do.
+        let skip = without.and_then(|id| self.node_of(id));
+        let remaining = NodeFiltered::from_fn(&self.grid, |n| Some(n) != skip);
+        let start = match self.grid.node_indices().find(|&n| Some(n) != skip) {
+            Some(n) => n,
+            None => return true,
+        };
+        let mut dfs = Dfs::new(&remaining, start);
+        let mut reached = 0;
+        while dfs.next(&remaining).is_some() {
+            reached += 1;
+        }
+        return reached == self.grid.node_count() - skip.is_some() as usize;
+    }
+
     pub fn hex_connects(&self, hex: Hex) -> bool {
         if let Some(_neighbor) = self
             .grid
@@ -132,24 +675,29 @@ impl Game {
         return false;
     }
 
-    pub fn slide_distance(&self, start: Hex, end: Hex) -> usize {
-        println!("Looking for dist between {:?} and {:?}", start, end);
+    // Number of steps from start to end through empty hexes touching the hive
+    pub fn slide_distance(&self, start: Hex, end: Hex) -> Result<usize, GameError> {
         // Let's do some BFS, baby!
-        let mut total_distance: usize = 1;
-        let mut hexes_to_check: Vec<Hex> = start.get_neighbors();
-
-        // Only keep hexes that dont contain pieces but are still connected to the grid
-        hexes_to_check.retain(|h| {
-            self.hex_connects(*h) && self.grid.node_weights().find(|&p| p.hex == *h).is_none()
-        });
+        let mut total_distance: usize = 0;
+        let mut seen: Vec<Hex> = vec![start];
+        let mut hexes_to_check: Vec<Hex> = vec![start];
 
         while !hexes_to_check.is_empty() {
             let mut next_hexes: Vec<Hex> = Vec::new();
-            while let Some(curr_hex) = hexes_to_check.pop() {
+            for curr_hex in hexes_to_check {
                 if curr_hex == end {
-                    return total_distance;
+                    return Ok(total_distance);
+                }
+                // Only keep hexes that dont contain pieces but are still connected to the grid
+                for neighbor in curr_hex.get_neighbors() {
+                    if !seen.contains(&neighbor)
+                        && self.hex_connects(neighbor)
+                        && !self.grid.node_weights().any(|p| p.hex == neighbor)
+                    {
+                        seen.push(neighbor);
+                        next_hexes.push(neighbor);
+                    }
                 }
-                next_hexes.extend(curr_hex.get_neighbors())
             }
 
             // No more hexes in hexes_to_check -> now go through next_hexes and add 1 to
@@ -157,35 +705,411 @@ impl Game {
             hexes_to_check = next_hexes;
             total_distance += 1;
         }
-        // This means that there is no path between start and end, which should be impossible.
-        unreachable!()
+        return Err(GameError::NoPath { start, end });
     }
+}
 
-    pub fn grid_distance(&self, start: Hex, end: Hex) -> usize {
-        // Returns distance along grid
-        return (start.q.abs_diff(end.q) + start.r.abs_diff(end.r) + start.s.abs_diff(end.s)) / 2;
+// Two games are equal when they hold the same position (the same stacks in the same arrangement,
+// wherever the hive happens to sit on the grid, and the same pieces left in each hand) with the
+// same player to move on the same turn, were reached by the same moves and are played with the
+// same pieces and rules. Compare position()s to ignore how the games got there.
+impl PartialEq for Game {
+    fn eq(&self, other: &Self) -> bool {
+        return normalized_grid(self) == normalized_grid(other)
+            && self.p1_hand == other.p1_hand
+            && self.p2_hand == other.p2_hand
+            && self.start_ply == other.start_ply
+            && self.move_list == other.move_list
+            && self.game_type == other.game_type
+            && self.rules == other.rules;
     }
 }
 
-// Returns true if hex is attached to some piece in game.grid other than itself
-fn piece_is_connected(hex: Hex, game: &Game, piece_id: &str) -> bool {
-    if let Some(_neighbor) = game
+// Every piece on the grid as (q, r, z, id), translated so the top-left piece sits at q = r = 0
+fn normalized_grid(game: &Game) -> Vec<(isize, isize, isize, PieceId)> {
+    let origin = game
         .grid
         .node_weights()
-        .find(|&piece| piece.hex.get_neighbors().contains(&hex) && piece.id != piece_id)
-    {
-        return true;
-    }
-    return false;
+        .map(|p| (p.hex.q(), p.hex.r()))
+        .min()
+        .unwrap_or((0, 0));
+    let mut pieces: Vec<(isize, isize, isize, PieceId)> = game
+        .grid
+        .node_weights()
+        .map(|p| (p.hex.q() - origin.0, p.hex.r() - origin.1, p.hex.z, p.id))
+        .collect();
+    pieces.sort();
+    return pieces;
 }
 
-// IGN: Inline Grid Notation
-// Example IGN: wa1 ba1 1_bb1 |1| wb1 wq ba2 bq ...
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> PieceId {
+        return PieceId::parse(s).unwrap();
+    }
+
+    fn hex(q: isize, r: isize) -> Hex {
+        return Hex::from_axial(q, r);
+    }
+
+    fn place(id: &str, hex: Hex) -> PieceMove {
+        return PieceMove::Place {
+            piece_id: self::id(id),
+            hex,
+        };
+    }
+
+    // wq at the origin with bq to its north
+    fn two_queens() -> Game {
+        let mut game = Game::new();
+        game.make_move(place("wq", hex(0, 0))).unwrap();
+        game.make_move(place("bq", hex(0, -1))).unwrap();
+        return game;
+    }
+
+    #[test]
+    fn move_onto_occupied_hex_leaves_game_intact() {
+        let mut game = two_queens();
+        let bad = PieceMove::Move {
+            piece_id: id("wq"),
+            from: hex(0, 0),
+            hex: hex(0, -1),
+        };
+        assert_eq!(game.make_move(bad), Err(GameError::HexOccupied(hex(0, -1))));
+        assert_eq!(game.validate(), Ok(()));
+        assert_eq!(game.get_piece(id("wq")).map(|p| p.hex), Some(hex(0, 0)));
+        assert_eq!(game.move_list.len(), 2);
+    }
+
+    #[test]
+    fn undo_onto_occupied_hex_leaves_game_intact() {
+        let mut game = two_queens();
+        let slide = PieceMove::Move {
+            piece_id: id("wq"),
+            from: hex(0, 0),
+            hex: hex(1, -1),
+        };
+        game.make_move(slide.clone()).unwrap();
+        // Something else has since been put where wq came from
+        game.add_to_grid(Piece::from(id("ws1")), hex(0, 0)).unwrap();
+
+        assert_eq!(
+            game.umake_move(slide),
+            Err(GameError::HexOccupied(hex(0, 0)))
+        );
+        assert_eq!(game.validate(), Ok(()));
+        assert_eq!(game.get_piece(id("wq")).map(|p| p.hex), Some(hex(1, -1)));
+    }
+
+    #[test]
+    fn make_and_umake_round_trip() {
+        let mut game = two_queens();
+        let before = game.clone();
+        let moves = game.get_all_moves();
+        for m in moves {
+            game.make_move(m.clone()).unwrap();
+            game.umake_move(m).unwrap();
+            assert!(game == before);
+            assert_eq!(game.move_list, before.move_list);
+        }
+    }
+
+    #[test]
+    fn umake_takes_a_game_back_to_the_start() {
+        let config = random::RandomGameConfig {
+            seed: 7,
+            max_plies: 60,
+            ..random::RandomGameConfig::default()
+        };
+        let mut game = random::random_game(&config);
+        while let Some(m) = game.move_list.last().cloned() {
+            game.umake_move(m).unwrap();
+            game.validate().unwrap();
+        }
+        assert!(game == Game::with_type(config.game_type));
+    }
+
+    #[test]
+    fn moves_outlast_changes_to_the_grid() {
+        let mut game = two_queens();
+        let moves = game.get_all_moves();
+        assert!(!moves.is_empty());
+        // Moving a piece takes it off the grid and puts it back, so the grid's node indices change
+        for m in &moves {
+            game.make_move(m.clone()).unwrap();
+            game.umake_move(m.clone()).unwrap();
+        }
+        for m in moves {
+            game.try_make_move(m.clone()).unwrap();
+            game.umake_move(m).unwrap();
+        }
+    }
+
+    #[test]
+    fn try_make_move_says_why_a_move_is_illegal() {
+        let mut game = two_queens();
+        let before = game.clone();
+        let place = |piece: &str, q: isize, r: isize| PieceMove::Place {
+            piece_id: id(piece),
+            hex: hex(q, r),
+        };
+        let cases = [
+            (place("ba1", 0, 1), MoveError::NotYourTurn(id("ba1"))),
+            (
+                place("wa2", 0, 1),
+                MoveError::NotLowestNumbered {
+                    piece_id: id("wa2"),
+                    expected: id("wa1"),
+                },
+            ),
+            (place("wq", 0, 1), MoveError::PieceNotInHand(id("wq"))),
+            // Only touching black's queen
+            (place("wa1", 0, -2), MoveError::IllegalPlacement(id("wa1"))),
+            (
+                PieceMove::Move {
+                    piece_id: id("wa1"),
+                    from: hex(0, 1),
+                    hex: hex(0, 2),
+                },
+                MoveError::PieceNotOnGrid(id("wa1")),
+            ),
+            (
+                PieceMove::Move {
+                    piece_id: id("wq"),
+                    from: hex(0, 1),
+                    hex: hex(1, -1),
+                },
+                MoveError::WrongOrigin {
+                    piece_id: id("wq"),
+                    from: hex(0, 1),
+                },
+            ),
+            (
+                PieceMove::Move {
+                    piece_id: id("wq"),
+                    from: hex(0, 0),
+                    hex: hex(0, 1),
+                },
+                MoveError::IllegalDestination(id("wq")),
+            ),
+            (PieceMove::Pass, MoveError::CannotPass),
+        ];
+        for (m, error) in cases {
+            assert_eq!(game.try_make_move(m), Err(error));
+            assert!(game == before);
+        }
+    }
+
+    #[test]
+    fn try_make_move_checks_the_queen_and_the_hive() {
+        let mut game = Game::with_type(GameType::BASE);
+        assert_eq!(
+            game.try_make_move(PieceMove::Place {
+                piece_id: id("wm"),
+                hex: hex(0, 0),
+            }),
+            Err(MoveError::PieceNotInGame(id("wm")))
+        );
+
+        let mut game = import_from_game_string("Base;InProgress;White[2];wA1;bA1 wA1-").unwrap();
+        let wa1 = game.get_piece(id("wa1")).unwrap().hex;
+        assert_eq!(
+            game.try_make_move(PieceMove::Move {
+                piece_id: id("wa1"),
+                from: wa1,
+                hex: wa1.get_neighbor(HexEdge::N),
+            }),
+            Err(MoveError::QueenNotPlaced)
+        );
+
+        // wq holds wa1 to the rest of the hive
+        let mut game =
+            import_from_game_string("Base;InProgress;White[3];wQ;bQ wQ-;wA1 -wQ;bA1 bQ-").unwrap();
+        let wq = game.get_piece(id("wq")).unwrap().hex;
+        assert_eq!(
+            game.try_make_move(PieceMove::Move {
+                piece_id: id("wq"),
+                from: wq,
+                hex: wq.get_neighbor(HexEdge::N),
+            }),
+            Err(MoveError::PiecePinned(id("wq")))
+        );
+    }
+
+    // The MoveStrings played from the start under rules
+    fn play(rules: Rules, moves: &[&str]) -> Game {
+        let mut game = Game::with_type(GameType::BASE);
+        game.rules = rules;
+        for move_string in moves {
+            let m = game.parse_move_string(move_string).unwrap();
+            game.try_make_move(m).unwrap();
+        }
+        return game;
+    }
+
+    #[test]
+    fn threefold_repetition_is_a_draw_when_the_rules_say_so() {
+        let mut moves = vec!["wQ", "bQ wQ-", "wA1 -wQ", "bA1 bQ-"];
+        // Each ant steps away and back twice, so the position after black's ant first arrives is
+        // seen for the third time
+        for _ in 0..2 {
+            moves.extend(["wA1 \\wQ", "bA1 bQ/", "wA1 -wQ", "bA1 bQ-"]);
+        }
+        let rules = Rules {
+            draw_by_repetition: true,
+            ..Rules::default()
+        };
+        let game = play(rules, &moves);
+        assert_eq!(game.repetitions(), 3);
+        assert_eq!(game.get_state(), GameState::Draw);
+        assert_eq!(game.draw_reason(), Some(DrawReason::Repetition));
+        assert_eq!(
+            compact::CompactGame::from(&game).get_state(),
+            GameState::Draw
+        );
+
+        let game = play(Rules::default(), &moves);
+        assert_eq!(game.get_state(), GameState::InProgress);
+        // Stopping one move short only makes it twice
+        let game = play(rules, &moves[..moves.len() - 4]);
+        assert_eq!(game.get_state(), GameState::InProgress);
+    }
+
+    #[test]
+    fn turn_limit_draws_once_both_players_reach_it() {
+        let rules = Rules {
+            turn_limit: Some(2),
+            ..Rules::default()
+        };
+        let game = play(rules, &["wQ", "bQ wQ-", "wA1 -wQ"]);
+        assert_eq!(game.get_state(), GameState::InProgress);
+        let game = play(rules, &["wQ", "bQ wQ-", "wA1 -wQ", "bA1 bQ-"]);
+        assert_eq!(game.draw_reason(), Some(DrawReason::TurnLimit));
+        assert!(game.get_all_moves().is_empty());
+        assert_eq!(
+            compact::CompactGame::from(&game).get_state(),
+            GameState::Draw
+        );
+    }
+
+    #[test]
+    fn only_the_last_move_can_be_taken_back() {
+        let mut game = two_queens();
+        let first = game.move_list[0].clone();
+        assert_eq!(game.umake_move(first), Err(GameError::NotLastMove));
+    }
+
+    #[test]
+    fn pieces_are_placed_in_number_order() {
+        let mut game = two_queens();
+        assert_eq!(
+            game.make_move(place("wa2", hex(0, 1))),
+            Err(GameError::PieceOutOfOrder(id("wa2")))
+        );
+        assert_eq!(
+            game.hand(PieceColor::White).next(PieceType::Ant),
+            Some(id("wa1"))
+        );
+        game.make_move(place("wa1", hex(0, 1))).unwrap();
+        let wa1 = game.get_piece(id("wa1")).unwrap();
+        assert_eq!(
+            (wa1.id.color, wa1.id.piece_type),
+            (PieceColor::White, PieceType::Ant)
+        );
+        assert_eq!(
+            game.hand(PieceColor::White).next(PieceType::Ant),
+            Some(id("wa2"))
+        );
+    }
+
+    #[test]
+    fn equality_covers_side_to_move_moves_and_rules() {
+        let game = two_queens();
+        assert!(game.position() == game.position());
+        assert!(game.position() != game);
+
+        let mut black_to_move = game.position();
+        black_to_move.start_ply += 1;
+        assert!(black_to_move != game.position());
+
+        let mut other_rules = game.clone();
+        other_rules.rules.tournament_opening = !other_rules.rules.tournament_opening;
+        assert!(other_rules != game);
+    }
+
+    // Pairs of pieces side by side at the same height or one on top of the other
+    fn touching_pairs(game: &Game) -> usize {
+        let pieces: Vec<&Piece> = game.grid.node_weights().collect();
+        let mut pairs = 0;
+        for (i, a) in pieces.iter().enumerate() {
+            for b in &pieces[i + 1..] {
+                if get_all_edge_types()
+                    .iter()
+                    .any(|&e| a.hex.get_neighbor(e) == b.hex)
+                {
+                    pairs += 1;
+                }
+            }
+        }
+        return pairs;
+    }
+
+    #[test]
+    fn one_edge_per_touching_pair() {
+        let config = random::RandomGameConfig {
+            seed: 3,
+            max_plies: 60,
+            ..random::RandomGameConfig::default()
+        };
+        let mut game = random::random_game(&config);
+        loop {
+            assert_eq!(game.grid.edge_count(), touching_pairs(&game));
+            for piece in game.grid.node_weights() {
+                for (edge, neighbor) in game.neighbors_of(piece.id) {
+                    assert_eq!(piece.hex.get_neighbor(edge), neighbor.hex);
+                    assert_eq!(
+                        game.neighbor_of(neighbor.id, edge.get_opposite())
+                            .map(|p| p.id),
+                        Some(piece.id)
+                    );
+                }
+            }
+            match game.move_list.last().cloned() {
+                Some(m) => game.umake_move(m).unwrap(),
+                None => break,
+            }
+        }
+        assert_eq!(game.grid.edge_count(), 0);
+    }
 
-pub fn import_from_ign(ign: &str) -> Game {
-    // Pick an orientation of the board such that the top of each piece is flat.
-    // find the column that is the left-most and the piece at the top of that column
-    // you will be reading up --> down and then left --> right
+    #[test]
+    fn neighbours_beside_above_and_below() {
+        let mut game = two_queens();
+        game.make_move(PieceMove::Place {
+            piece_id: id("wb1"),
+            hex: hex(0, 1),
+        })
+        .unwrap();
+        assert!(!game.is_one_hive_without(Some(id("wq"))));
+        assert!(game.is_one_hive_without(Some(id("wb1"))));
 
-    todo!();
+        game.make_move(PieceMove::Pass).unwrap();
+        game.make_move(PieceMove::Move {
+            piece_id: id("wb1"),
+            from: hex(0, 1),
+            hex: hex(0, 0).with_z(1),
+        })
+        .unwrap();
+        let neighbor = |piece_id, edge| game.neighbor_of(id(piece_id), edge).map(|p| p.id);
+        assert_eq!(neighbor("wq", HexEdge::N), Some(id("bq")));
+        assert_eq!(neighbor("bq", HexEdge::S), Some(id("wq")));
+        assert_eq!(neighbor("wq", HexEdge::T), Some(id("wb1")));
+        assert_eq!(neighbor("wb1", HexEdge::B), Some(id("wq")));
+        // bq is beside wb1's stack, not beside wb1
+        assert_eq!(neighbor("wb1", HexEdge::N), None);
+        assert_eq!(game.neighbors_of(id("wb1")).len(), 1);
+        assert_eq!(game.neighbors_of(id("wa1")), Vec::new());
+    }
 }
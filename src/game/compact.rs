@@ -0,0 +1,1149 @@
+// Compact position for search
+//
+// Game keeps its pieces in a petgraph Graph with String ids. That is easy to work with, but every
+// clone and every move allocates. CompactGame holds the same position in fixed-size arrays:
+//
+// - The 28 pieces are numbered 0..28: white's first, in starting hand order (wq = 0, ws1 = 1,
+//   ... wp = 13), then black's (bq = 14, ... bp = 27).
+// - The grid is 32 x 32 cells that wrap around at the edges, with each cell packed into a u16 as
+//   q + 32 * r. A hive of 28 pieces can't stretch far enough to meet itself around the edge.
+// - Each cell keeps its height and top piece, and each piece keeps the piece underneath it, so
+//   stacks are linked lists.
+//
+// CompactGame generates exactly the same moves as Game, and make_move / umake_move don't allocate
+// (apart from growing the move list). Convert with CompactGame::from(&game) and
+// Game::from(&compact), and single moves with compact_move and piece_move.
+
+use crate::game::game_type::GameType;
+use crate::game::hex::{get_edge_types, Hex};
+use crate::game::piece::{Piece, PieceColor, PieceId, PieceType};
+use crate::game::piece_move::PieceMove;
+use crate::game::rules::Rules;
+use crate::game::{DrawReason, Game, GameError, GameState};
+
+pub const PIECE_COUNT: usize = 28;
+const CELL_COUNT: usize = 1024;
+const GRID_MASK: i32 = 31;
+
+// Where a piece is when it isn't on the grid
+pub const IN_HAND: u16 = u16::MAX - 1;
+pub const NOT_IN_GAME: u16 = u16::MAX;
+const NO_PIECE: u8 = u8::MAX;
+
+// One colour's pieces, in starting hand order (see get_starting_hand)
+const PIECES: [(PieceType, u8); 14] = [
+    (PieceType::QueenBee, 1),
+    (PieceType::Spider, 1),
+    (PieceType::Spider, 2),
+    (PieceType::Beetle, 1),
+    (PieceType::Beetle, 2),
+    (PieceType::Grasshopper, 1),
+    (PieceType::Grasshopper, 2),
+    (PieceType::Grasshopper, 3),
+    (PieceType::Ant, 1),
+    (PieceType::Ant, 2),
+    (PieceType::Ant, 3),
+    (PieceType::Mosquito, 1),
+    (PieceType::Ladybug, 1),
+    (PieceType::Pillbug, 1),
+];
+
+// Neighbour offsets, in get_edge_types() order: N, NE, SE, S, SW, NW
+const DQ: [i32; 6] = [0, 1, 1, 0, -1, -1];
+const DR: [i32; 6] = [-1, -1, 0, 1, 1, 0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CompactMove {
+    Place { piece: u8, to: u16 },
+    Move { piece: u8, from: u16, to: u16 },
+    Pass,
+}
+
+impl CompactMove {
+    pub fn piece(&self) -> Option<u8> {
+        match self {
+            CompactMove::Place { piece, .. } | CompactMove::Move { piece, .. } => {
+                return Some(*piece)
+            }
+            CompactMove::Pass => return None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompactGame {
+    // Cell of each piece, or IN_HAND / NOT_IN_GAME
+    cells: [u16; PIECE_COUNT],
+    // Height of each piece within its stack (0 on the ground)
+    z: [u8; PIECE_COUNT],
+    // The piece directly underneath each piece, or NO_PIECE
+    under: [u8; PIECE_COUNT],
+    height: [u8; CELL_COUNT],
+    top: [u8; CELL_COUNT],
+    move_list: Vec<CompactMove>,
+    // Moves made before move_list starts (see Game::start_ply)
+    start_ply: usize,
+    // position_hash() after each move in move_list
+    history: Vec<u64>,
+    pub rules: Rules,
+}
+
+impl Default for CompactGame {
+    fn default() -> Self {
+        return CompactGame::new();
+    }
+}
+
+impl CompactGame {
+    pub fn new() -> CompactGame {
+        return CompactGame {
+            cells: [IN_HAND; PIECE_COUNT],
+            z: [0; PIECE_COUNT],
+            under: [NO_PIECE; PIECE_COUNT],
+            height: [0; CELL_COUNT],
+            top: [NO_PIECE; CELL_COUNT],
+            move_list: Vec::new(),
+            start_ply: 0,
+            history: Vec::new(),
+            rules: Rules::default(),
+        };
+    }
+
+    // A new game with only game_type's pieces in it
+    pub fn with_type(game_type: GameType) -> CompactGame {
+        let mut compact = CompactGame::new();
+        for piece in 0..PIECE_COUNT as u8 {
+            if !game_type.includes(piece_type(piece)) {
+                compact.cells[piece as usize] = NOT_IN_GAME;
+            }
+        }
+        return compact;
+    }
+
+    // Worked out from which pieces are in the game
+    pub fn game_type(&self) -> GameType {
+        return GameType::containing(
+            (0..PIECE_COUNT as u8)
+                .filter(|&p| self.cells[p as usize] != NOT_IN_GAME)
+                .map(piece_type),
+        );
+    }
+
+    pub fn move_list(&self) -> &[CompactMove] {
+        return &self.move_list;
+    }
+
+    // The cell a piece is on, or IN_HAND / NOT_IN_GAME
+    pub fn piece_cell(&self, piece: u8) -> u16 {
+        return self.cells[piece as usize];
+    }
+
+    pub fn top_piece_at(&self, cell: u16) -> Option<u8> {
+        let top = self.top[cell as usize];
+        if top == NO_PIECE {
+            return None;
+        }
+        return Some(top);
+    }
+
+    pub fn stack_height(&self, cell: u16) -> usize {
+        return self.height[cell as usize] as usize;
+    }
+
+    // Moves made so far, by both players
+    pub fn ply(&self) -> usize {
+        return self.start_ply + self.move_list.len();
+    }
+
+    // White always moves first
+    pub fn current_player(&self) -> PieceColor {
+        if self.ply().is_multiple_of(2) {
+            return PieceColor::White;
+        }
+        return PieceColor::Black;
+    }
+
+    // The current player's turn number, counting from 1
+    pub fn turn_number(&self) -> usize {
+        return self.ply() / 2 + 1;
+    }
+
+    pub fn get_state(&self) -> GameState {
+        let white_surrounded = self.queen_surrounded(PieceColor::White);
+        let black_surrounded = self.queen_surrounded(PieceColor::Black);
+        if white_surrounded && black_surrounded {
+            return GameState::Draw;
+        } else if white_surrounded {
+            return GameState::BlackWins;
+        } else if black_surrounded {
+            return GameState::WhiteWins;
+        } else if self.draw_reason().is_some() {
+            return GameState::Draw;
+        } else if !self.cells.iter().any(|&c| on_grid(c)) {
+            return GameState::NotStarted;
+        }
+        return GameState::InProgress;
+    }
+
+    // None unless get_state() is Draw
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        let white_surrounded = self.queen_surrounded(PieceColor::White);
+        let black_surrounded = self.queen_surrounded(PieceColor::Black);
+        if white_surrounded && black_surrounded {
+            return Some(DrawReason::BothQueensSurrounded);
+        } else if white_surrounded || black_surrounded {
+            return None;
+        } else if self.rules.draw_by_repetition && self.repetitions() >= 3 {
+            return Some(DrawReason::Repetition);
+        } else if let Some(limit) = self.rules.turn_limit {
+            if self.ply() >= 2 * limit {
+                return Some(DrawReason::TurnLimit);
+            }
+        }
+        return None;
+    }
+
+    // How many times the current position has come up since the first move in move_list
+    pub fn repetitions(&self) -> usize {
+        return match self.history.last() {
+            Some(current) => self.history.iter().filter(|&h| h == current).count(),
+            None => 1,
+        };
+    }
+
+    // Identifies a position up to translation, like Game::position_hash (though the two don't give
+    // the same numbers). Cells are taken relative to the lowest numbered piece on the grid and
+    // folded together FNV-style, which is cheap enough to do on every move.
+    pub fn position_hash(&self) -> u64 {
+        let origin = self
+            .cells
+            .iter()
+            .copied()
+            .find(|&c| on_grid(c))
+            .unwrap_or(0) as i32;
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for p in 0..PIECE_COUNT {
+            let cell = self.cells[p];
+            let relative = if on_grid(cell) {
+                let q = (cell as i32 - origin) & GRID_MASK;
+                let r = ((cell as i32 >> 5) - (origin >> 5)) & GRID_MASK;
+                (r << 5 | q) as u64
+            } else {
+                cell as u64
+            };
+            hash = (hash ^ (relative << 8 | self.z[p] as u64)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        hash = (hash ^ (self.ply() % 2) as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        return hash;
+    }
+
+    // position_hash() after each move in move_list
+    pub fn position_history(&self) -> &[u64] {
+        return &self.history;
+    }
+
+    pub fn is_over(&self) -> bool {
+        return matches!(
+            self.get_state(),
+            GameState::Draw | GameState::WhiteWins | GameState::BlackWins
+        );
+    }
+
+    pub fn queen_placed(&self, color: PieceColor) -> bool {
+        return on_grid(self.cells[queen(color) as usize]);
+    }
+
+    pub fn queen_surrounded(&self, color: PieceColor) -> bool {
+        let cell = self.cells[queen(color) as usize];
+        if !on_grid(cell) {
+            return false;
+        }
+        return (0..6).all(|d| self.height[neighbor(cell, d) as usize] > 0);
+    }
+
+    // How many of the cells around color's queen are occupied (0 if it hasn't been placed)
+    pub fn queen_neighbors(&self, color: PieceColor) -> usize {
+        let cell = self.cells[queen(color) as usize];
+        if !on_grid(cell) {
+            return 0;
+        }
+        return (0..6)
+            .filter(|&d| self.height[neighbor(cell, d) as usize] > 0)
+            .count();
+    }
+
+    // The piece moved on the previous turn. It can't be moved again (by itself or by a pillbug)
+    // until the current player has had their turn.
+    pub fn last_moved_piece(&self) -> Option<u8> {
+        if let Some(CompactMove::Move { piece, .. }) = self.move_list.last() {
+            return Some(*piece);
+        }
+        return None;
+    }
+
+    /// Assumes piece_move is valid move
+    pub fn make_move(&mut self, piece_move: CompactMove) {
+        match piece_move {
+            CompactMove::Place { piece, to } => self.push(piece, to),
+            CompactMove::Move { piece, to, .. } => {
+                self.pop(piece);
+                self.push(piece, to);
+            }
+            CompactMove::Pass => {}
+        }
+        self.move_list.push(piece_move);
+        self.history.push(self.position_hash());
+    }
+
+    /// Takes back piece_move, which must be the last move made
+    pub fn umake_move(&mut self, piece_move: CompactMove) {
+        debug_assert_eq!(self.move_list.last(), Some(&piece_move));
+        match piece_move {
+            CompactMove::Place { piece, .. } => {
+                self.pop(piece);
+                self.cells[piece as usize] = IN_HAND;
+            }
+            CompactMove::Move { piece, from, .. } => {
+                self.pop(piece);
+                self.push(piece, from);
+            }
+            CompactMove::Pass => {}
+        }
+        self.move_list.pop();
+        self.history.pop();
+    }
+
+    fn push(&mut self, piece: u8, cell: u16) {
+        let p = piece as usize;
+        let c = cell as usize;
+        self.cells[p] = cell;
+        self.z[p] = self.height[c];
+        self.under[p] = self.top[c];
+        self.top[c] = piece;
+        self.height[c] += 1;
+    }
+
+    // piece has to be on top of its stack
+    fn pop(&mut self, piece: u8) {
+        let p = piece as usize;
+        let c = self.cells[p] as usize;
+        debug_assert_eq!(self.top[c], piece);
+        self.top[c] = self.under[p];
+        self.height[c] -= 1;
+        self.under[p] = NO_PIECE;
+    }
+
+    // Every legal move for the current player, or a single Pass if they have none
+    pub fn get_all_moves(&self) -> Vec<CompactMove> {
+        let mut moves: Vec<CompactMove> = Vec::with_capacity(64);
+        self.generate_moves(&mut moves);
+        return moves;
+    }
+
+    // Same as get_all_moves, reusing the caller's Vec (which is cleared first)
+    pub fn generate_moves(&self, moves: &mut Vec<CompactMove>) {
+        moves.clear();
+        if self.is_over() {
+            return;
+        }
+
+        self.generate_placements(moves);
+
+        // Pieces on the grid can only move once their queen has been placed
+        let color = self.current_player();
+        if self.queen_placed(color) {
+            let pinned = self.pinned_pieces();
+            for piece in color_pieces(color) {
+                let cell = self.cells[piece as usize];
+                if on_grid(cell) && self.top[cell as usize] == piece {
+                    let piece_type = piece_type(piece);
+                    self.generate_piece_moves(piece, piece_type, pinned, moves);
+                }
+            }
+            let last_moved = self.last_moved_piece();
+            if last_moved.is_some() {
+                moves.retain(|m| m.piece() != last_moved);
+            }
+            moves.sort_unstable();
+            moves.dedup();
+        }
+
+        if moves.is_empty() {
+            moves.push(CompactMove::Pass);
+        }
+    }
+
+    pub fn get_placements(&self) -> Vec<CompactMove> {
+        let mut placements: Vec<CompactMove> = Vec::new();
+        self.generate_placements(&mut placements);
+        return placements;
+    }
+
+    fn generate_placements(&self, moves: &mut Vec<CompactMove>) {
+        let color = self.current_player();
+
+        // Only the lowest numbered piece of each type can be placed, and the queen has to be down
+        // by the rules' deadline (but can't go first in a tournament opening)
+        let (turn, queen_placed) = (self.turn_number(), self.queen_placed(color));
+        let mut candidates: [u8; 8] = [NO_PIECE; 8];
+        let mut candidate_count = 0;
+        let mut types_seen: u32 = 0;
+        for piece in color_pieces(color) {
+            let bit = 1 << piece_type(piece) as u32;
+            if self.cells[piece as usize] == IN_HAND
+                && types_seen & bit == 0
+                && self
+                    .rules
+                    .check_placement(turn, queen_placed, piece_type(piece))
+                    .is_ok()
+            {
+                types_seen |= bit;
+                candidates[candidate_count] = piece;
+                candidate_count += 1;
+            }
+        }
+        let candidates = &candidates[..candidate_count];
+
+        if !self.cells.iter().any(|&c| on_grid(c)) {
+            for &piece in candidates {
+                moves.push(CompactMove::Place { piece, to: 0 });
+            }
+            return;
+        }
+
+        let own_pieces_placed = color_pieces(color).any(|piece| {
+            let cell = self.cells[piece as usize];
+            return on_grid(cell) && self.top[cell as usize] == piece;
+        });
+        let mut checked = CellSet::new();
+        for piece in 0..PIECE_COUNT {
+            let cell = self.cells[piece];
+            if !on_grid(cell) || self.z[piece] != 0 {
+                continue;
+            }
+            for d in 0..6 {
+                let n = neighbor(cell, d);
+                if self.height[n as usize] > 0 || !checked.insert(n) {
+                    continue;
+                }
+                // New pieces have to touch their own colour and nothing of the opponent's,
+                // except for each player's very first placement
+                let touches_opponent = (0..6).any(|e| {
+                    let top = self.top[neighbor(n, e) as usize];
+                    return top != NO_PIECE && piece_color(top) != color;
+                });
+                if !own_pieces_placed || !touches_opponent {
+                    for &piece in candidates {
+                        moves.push(CompactMove::Place { piece, to: n });
+                    }
+                }
+            }
+        }
+    }
+
+    fn generate_piece_moves(
+        &self,
+        piece: u8,
+        as_type: PieceType,
+        pinned: u32,
+        moves: &mut Vec<CompactMove>,
+    ) {
+        // Climbing off a stack leaves the piece underneath behind to hold the hive together
+        let can_move = self.z[piece as usize] > 0 || pinned & (1 << piece) == 0;
+        match as_type {
+            PieceType::QueenBee if can_move => self.queen_moves(piece, moves),
+            PieceType::Ant if can_move => self.ant_moves(piece, moves),
+            PieceType::Beetle if can_move => self.beetle_moves(piece, moves),
+            PieceType::Grasshopper if can_move => self.grasshopper_moves(piece, moves),
+            PieceType::Spider if can_move => self.spider_moves(piece, moves),
+            PieceType::Ladybug if can_move => self.ladybug_moves(piece, moves),
+            PieceType::Pillbug => {
+                if can_move {
+                    self.queen_moves(piece, moves);
+                }
+                self.pillbug_ability(piece, pinned, moves);
+            }
+            PieceType::Mosquito => {
+                // A mosquito on top of the hive can only move like a beetle
+                if self.z[piece as usize] > 0 {
+                    self.beetle_moves(piece, moves);
+                    return;
+                }
+                let cell = self.cells[piece as usize];
+                let mut types_copied: u32 = 1 << PieceType::Mosquito as u32;
+                for d in 0..6 {
+                    let top = self.top[neighbor(cell, d) as usize];
+                    if top == NO_PIECE {
+                        continue;
+                    }
+                    let copied = piece_type(top);
+                    if types_copied & (1 << copied as u32) == 0 {
+                        types_copied |= 1 << copied as u32;
+                        self.generate_piece_moves(piece, copied, pinned, moves);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn queen_moves(&self, piece: u8, moves: &mut Vec<CompactMove>) {
+        let from = self.cells[piece as usize];
+        for d in 0..6 {
+            let to = neighbor(from, d);
+            if self.height_without(to, from) == 0 && self.can_step(from, d, from) {
+                moves.push(CompactMove::Move { piece, from, to });
+            }
+        }
+    }
+
+    fn ant_moves(&self, piece: u8, moves: &mut Vec<CompactMove>) {
+        // BFS over every empty cell the ant can slide to
+        let from = self.cells[piece as usize];
+        let mut checked = CellSet::new();
+        checked.insert(from);
+        let mut cells_to_check: [u16; CELL_COUNT] = [0; CELL_COUNT];
+        cells_to_check[0] = from;
+        let mut count = 1;
+        while count > 0 {
+            count -= 1;
+            let cell = cells_to_check[count];
+            for d in 0..6 {
+                let n = neighbor(cell, d);
+                if self.height_without(n, from) == 0
+                    && self.can_step(cell, d, from)
+                    && checked.insert(n)
+                {
+                    cells_to_check[count] = n;
+                    count += 1;
+                    moves.push(CompactMove::Move { piece, from, to: n });
+                }
+            }
+        }
+    }
+
+    fn beetle_moves(&self, piece: u8, moves: &mut Vec<CompactMove>) {
+        // Either slide along the ground or climb on top of whatever is there
+        let from = self.cells[piece as usize];
+        for d in 0..6 {
+            if self.can_step(from, d, from) {
+                moves.push(CompactMove::Move {
+                    piece,
+                    from,
+                    to: neighbor(from, d),
+                });
+            }
+        }
+    }
+
+    fn grasshopper_moves(&self, piece: u8, moves: &mut Vec<CompactMove>) {
+        let from = self.cells[piece as usize];
+        for d in 0..6 {
+            let mut to = neighbor(from, d);
+            if self.height_without(to, from) == 0 {
+                continue;
+            }
+            while self.height_without(to, from) > 0 {
+                to = neighbor(to, d);
+            }
+            moves.push(CompactMove::Move { piece, from, to });
+        }
+    }
+
+    fn spider_moves(&self, piece: u8, moves: &mut Vec<CompactMove>) {
+        // DFS over every path of exactly three slides that doesn't revisit a cell
+        let from = self.cells[piece as usize];
+        let mut path: [u16; 4] = [from; 4];
+        self.spider_step(piece, &mut path, 1, moves);
+    }
+
+    fn spider_step(
+        &self,
+        piece: u8,
+        path: &mut [u16; 4],
+        len: usize,
+        moves: &mut Vec<CompactMove>,
+    ) {
+        let from = path[0];
+        if len == path.len() {
+            moves.push(CompactMove::Move {
+                piece,
+                from,
+                to: path[len - 1],
+            });
+            return;
+        }
+        let cell = path[len - 1];
+        for d in 0..6 {
+            let n = neighbor(cell, d);
+            if !path[..len].contains(&n)
+                && self.height_without(n, from) == 0
+                && self.can_step(cell, d, from)
+            {
+                path[len] = n;
+                self.spider_step(piece, path, len + 1, moves);
+            }
+        }
+    }
+
+    fn ladybug_moves(&self, piece: u8, moves: &mut Vec<CompactMove>) {
+        // Two steps across the top of the hive, then one step down
+        let from = self.cells[piece as usize];
+        for up in 0..6 {
+            let first = neighbor(from, up);
+            if self.height_without(first, from) == 0 || !self.can_step(from, up, from) {
+                continue;
+            }
+            for across in 0..6 {
+                let second = neighbor(first, across);
+                if self.height_without(second, from) == 0 || !self.can_step(first, across, from) {
+                    continue;
+                }
+                for down in 0..6 {
+                    let to = neighbor(second, down);
+                    if to != from
+                        && self.height_without(to, from) == 0
+                        && self.can_step(second, down, from)
+                    {
+                        moves.push(CompactMove::Move { piece, from, to });
+                    }
+                }
+            }
+        }
+    }
+
+    // Lift an adjacent piece over the pillbug and drop it on an empty neighbouring cell
+    fn pillbug_ability(&self, pillbug: u8, pinned: u32, moves: &mut Vec<CompactMove>) {
+        // A pillbug that was just moved can't use its ability this turn
+        let last_moved = self.last_moved_piece();
+        if last_moved == Some(pillbug) {
+            return;
+        }
+        let cell = self.cells[pillbug as usize];
+        for from_dir in 0..6 {
+            let from = neighbor(cell, from_dir);
+            let target = self.top[from as usize];
+            if target == NO_PIECE
+                || self.height[from as usize] != 1
+                || last_moved == Some(target)
+                || pinned & (1 << target) != 0
+                || !self.can_step(from, opposite(from_dir), from)
+            {
+                continue;
+            }
+            for to_dir in 0..6 {
+                let to = neighbor(cell, to_dir);
+                if to_dir != from_dir
+                    && self.height_without(to, from) == 0
+                    && self.can_step(cell, to_dir, from)
+                {
+                    moves.push(CompactMove::Move {
+                        piece: target,
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+    }
+
+    // Same rules as piece_move::can_step: whether a piece can take one step from cell in
+    // direction d, ignoring the moving piece (which is on top of `origin`)
+    fn can_step(&self, cell: u16, d: usize, origin: u16) -> bool {
+        let from_height = self.height_without(cell, origin);
+        let to_height = self.height_without(neighbor(cell, d), origin);
+        let left = self.height_without(neighbor(cell, (d + 5) % 6), origin);
+        let right = self.height_without(neighbor(cell, (d + 1) % 6), origin);
+
+        if left.min(right) > from_height.max(to_height) {
+            return false;
+        }
+        if from_height == 0 && to_height == 0 {
+            return left.max(right) > 0;
+        }
+        return true;
+    }
+
+    // Height of cell, not counting the moving piece on top of origin
+    fn height_without(&self, cell: u16, origin: u16) -> u8 {
+        return self.height[cell as usize] - (cell == origin) as u8;
+    }
+
+    // Pieces on the ground that can't leave without splitting the hive: the articulation points of
+    // the occupied cells, as a bitmask over piece numbers
+    pub fn pinned_pieces(&self) -> u32 {
+        let mut cells: [u16; PIECE_COUNT] = [0; PIECE_COUNT];
+        let mut ground: [u8; PIECE_COUNT] = [0; PIECE_COUNT];
+        let mut count = 0;
+        for piece in 0..PIECE_COUNT {
+            if on_grid(self.cells[piece]) && self.z[piece] == 0 {
+                cells[count] = self.cells[piece];
+                ground[count] = piece as u8;
+                count += 1;
+            }
+        }
+        if count < 3 {
+            return 0;
+        }
+
+        let mut search = Articulation {
+            cells: &cells[..count],
+            order: [0; PIECE_COUNT],
+            low: [0; PIECE_COUNT],
+            visited: 0,
+            articulation: 0,
+        };
+        search.visit(0, usize::MAX);
+
+        let mut pinned: u32 = 0;
+        for (i, &piece) in ground[..count].iter().enumerate() {
+            if search.articulation & (1 << i) != 0 {
+                pinned |= 1 << piece;
+            }
+        }
+        return pinned;
+    }
+}
+
+// Tarjan's articulation point search over the occupied cells of the grid
+struct Articulation<'a> {
+    cells: &'a [u16],
+    order: [u8; PIECE_COUNT],
+    low: [u8; PIECE_COUNT],
+    visited: u8,
+    articulation: u32,
+}
+
+impl Articulation<'_> {
+    fn visit(&mut self, i: usize, parent: usize) {
+        self.visited += 1;
+        self.order[i] = self.visited;
+        self.low[i] = self.visited;
+        let mut children = 0;
+        for d in 0..6 {
+            let n = neighbor(self.cells[i], d);
+            let j = match self.cells.iter().position(|&c| c == n) {
+                Some(j) => j,
+                None => continue,
+            };
+            if self.order[j] == 0 {
+                children += 1;
+                self.visit(j, i);
+                self.low[i] = self.low[i].min(self.low[j]);
+                if parent != usize::MAX && self.low[j] >= self.order[i] {
+                    self.articulation |= 1 << i;
+                }
+            } else if j != parent {
+                self.low[i] = self.low[i].min(self.order[j]);
+            }
+        }
+        if parent == usize::MAX && children > 1 {
+            self.articulation |= 1 << i;
+        }
+    }
+}
+
+// A set of cells as a 1024 bit bitmap
+struct CellSet([u64; CELL_COUNT / 64]);
+
+impl CellSet {
+    fn new() -> Self {
+        return CellSet([0; CELL_COUNT / 64]);
+    }
+
+    // Returns false if cell was already in the set
+    fn insert(&mut self, cell: u16) -> bool {
+        let (word, bit) = ((cell / 64) as usize, cell % 64);
+        let inserted = self.0[word] & (1 << bit) == 0;
+        self.0[word] |= 1 << bit;
+        return inserted;
+    }
+}
+
+fn on_grid(cell: u16) -> bool {
+    return cell < IN_HAND;
+}
+
+fn neighbor(cell: u16, d: usize) -> u16 {
+    let q = (cell as i32 + DQ[d]) & GRID_MASK;
+    let r = ((cell as i32 >> 5) + DR[d]) & GRID_MASK;
+    return (q | (r << 5)) as u16;
+}
+
+fn opposite(d: usize) -> usize {
+    return (d + 3) % 6;
+}
+
+fn queen(color: PieceColor) -> u8 {
+    match color {
+        PieceColor::White => return 0,
+        PieceColor::Black => return 14,
+    }
+}
+
+fn color_pieces(color: PieceColor) -> std::ops::Range<u8> {
+    let first = queen(color);
+    return first..first + 14;
+}
+
+pub fn piece_color(piece: u8) -> PieceColor {
+    if piece < 14 {
+        return PieceColor::White;
+    }
+    return PieceColor::Black;
+}
+
+pub fn piece_type(piece: u8) -> PieceType {
+    return PIECES[piece as usize % 14].0;
+}
+
+// 0 -> wq, 15 -> bs1
+pub fn piece_id(piece: u8) -> PieceId {
+    let (piece_type, ordinal) = PIECES[piece as usize % 14];
+    return PieceId::new(piece_color(piece), piece_type, ordinal);
+}
+
+// wq -> 0, bs1 -> 15
+pub fn piece_index(piece_id: PieceId) -> u8 {
+    let i = PIECES
+        .iter()
+        .position(|&p| p == (piece_id.piece_type, piece_id.ordinal))
+        .expect("Every piece is numbered");
+    return queen(piece_id.color) + i as u8;
+}
+
+// The cell a hex sits on, whatever its height
+pub fn cell(hex: Hex) -> u16 {
+    let q = hex.q() as i32 & GRID_MASK;
+    let r = hex.r() as i32 & GRID_MASK;
+    return (q | (r << 5)) as u16;
+}
+
+pub fn compact_move(piece_move: &PieceMove) -> CompactMove {
+    match piece_move {
+        PieceMove::Place { piece_id, hex } => {
+            return CompactMove::Place {
+                piece: piece_index(*piece_id),
+                to: cell(*hex),
+            }
+        }
+        PieceMove::Move {
+            piece_id,
+            from,
+            hex,
+        } => {
+            return CompactMove::Move {
+                piece: piece_index(*piece_id),
+                from: cell(*from),
+                to: cell(*hex),
+            }
+        }
+        PieceMove::Pass => return CompactMove::Pass,
+    }
+}
+
+// Describes compact_move as a PieceMove in game's coordinates. game has to hold the same position
+// as the CompactGame the move was generated for, or it errors when the moving piece or the cell it
+// goes to isn't found.
+pub fn piece_move(game: &Game, compact_move: CompactMove) -> Result<PieceMove, GameError> {
+    match compact_move {
+        CompactMove::Place { piece, to } => {
+            let piece_id = piece_id(piece);
+            let hex = destination(game, to, piece_id).ok_or(GameError::CellOffHive(to))?;
+            return Ok(PieceMove::Place { piece_id, hex });
+        }
+        CompactMove::Move { piece, to, .. } => {
+            let piece_id = piece_id(piece);
+            let from = game
+                .get_piece(piece_id)
+                .ok_or(GameError::PieceNotOnGrid(piece_id))?
+                .hex;
+            let hex = destination(game, to, piece_id).ok_or(GameError::CellOffHive(to))?;
+            return Ok(PieceMove::Move {
+                piece_id,
+                from,
+                hex,
+            });
+        }
+        CompactMove::Pass => return Ok(PieceMove::Pass),
+    }
+}
+
+// Where a piece moving to cell ends up in game, on top of anything already there. The first piece
+// goes on the origin; after that, None when cell isn't on or next to the hive.
+fn destination(game: &Game, cell: u16, moving_id: PieceId) -> Option<Hex> {
+    let ground = match hex_of_cell(game, cell, moving_id) {
+        Some(hex) => hex,
+        None if cell == self::cell(Hex::new())
+            && game.grid.node_weights().all(|p| p.id == moving_id) =>
+        {
+            Hex::new()
+        }
+        None => return None,
+    };
+    let z = game
+        .grid
+        .node_weights()
+        .filter(|p| p.hex.same_cell(ground) && p.id != moving_id)
+        .count() as isize;
+    return Some(ground.with_z(z));
+}
+
+// Finds cell in game's coordinates from the piece on it or next to it, ignoring moving_id. Every
+// cell a piece can be placed on or moved to or from touches the rest of the hive.
+fn hex_of_cell(game: &Game, cell: u16, moving_id: PieceId) -> Option<Hex> {
+    for piece in game.grid.node_weights() {
+        if piece.id == moving_id {
+            continue;
+        }
+        let ground = piece.hex.with_z(0);
+        if self::cell(ground) == cell {
+            return Some(ground);
+        }
+        if let Some(n) = ground
+            .get_neighbors()
+            .into_iter()
+            .find(|&n| self::cell(n) == cell)
+        {
+            return Some(n);
+        }
+    }
+    return None;
+}
+
+impl From<&Game> for CompactGame {
+    fn from(game: &Game) -> Self {
+        let mut compact = CompactGame::new();
+        compact.cells = [NOT_IN_GAME; PIECE_COUNT];
+        for id in game.p1_hand.ids().into_iter().chain(game.p2_hand.ids()) {
+            compact.cells[piece_index(id) as usize] = IN_HAND;
+        }
+
+        // Bottom of each stack first, so the stacks link up in order
+        let mut grid: Vec<&Piece> = game.grid.node_weights().collect();
+        grid.sort_by_key(|p| p.hex.z);
+        for piece in grid {
+            compact.push(piece_index(piece.id), cell(piece.hex));
+        }
+
+        compact.move_list = game.move_list.iter().map(compact_move).collect();
+        compact.start_ply = game.start_ply;
+        compact.rules = game.rules;
+
+        let mut rewind = compact.clone();
+        let mut history: Vec<u64> = Vec::new();
+        while let Some(&m) = rewind.move_list.last() {
+            history.push(rewind.position_hash());
+            rewind.umake_move(m);
+        }
+        history.reverse();
+        compact.history = history;
+        return compact;
+    }
+}
+
+impl From<&CompactGame> for Game {
+    fn from(compact: &CompactGame) -> Self {
+        let mut game = Game {
+            start_ply: compact.start_ply,
+            game_type: compact.game_type(),
+            rules: compact.rules,
+            ..Game::default()
+        };
+        // Highest numbers first, the order they go back into a hand
+        for piece in (0..PIECE_COUNT as u8).rev() {
+            if compact.cells[piece as usize] == IN_HAND {
+                let returned = game.hand_mut(piece_color(piece)).put_back(piece_id(piece));
+                assert!(returned, "Pieces are placed lowest number first");
+            }
+        }
+
+        // Lay the hive out from its first piece, keeping coordinates between -16 and 15 so a game
+        // that started near the origin comes back where it was
+        let mut hexes: Vec<(u16, Hex)> = Vec::new();
+        let mut cells_to_check: Vec<u16> = Vec::new();
+        for piece in 0..PIECE_COUNT {
+            let start = compact.cells[piece];
+            if !on_grid(start) || hexes.iter().any(|(c, _)| *c == start) {
+                continue;
+            }
+            let q = ((start as isize & 31) ^ 16) - 16;
+            let r = ((start as isize >> 5) ^ 16) - 16;
+            hexes.push((start, Hex::from_axial(q, r)));
+            cells_to_check.push(start);
+            while let Some(c) = cells_to_check.pop() {
+                let hex = hexes.iter().find(|(h, _)| *h == c).expect("Unknown cell").1;
+                for (d, e) in get_edge_types().into_iter().enumerate() {
+                    let n = neighbor(c, d);
+                    if compact.height[n as usize] > 0 && !hexes.iter().any(|(h, _)| *h == n) {
+                        hexes.push((n, hex.get_neighbor(e)));
+                        cells_to_check.push(n);
+                    }
+                }
+            }
+        }
+        let mut grid: Vec<u8> = (0..PIECE_COUNT as u8)
+            .filter(|&p| on_grid(compact.cells[p as usize]))
+            .collect();
+        grid.sort_by_key(|&p| compact.z[p as usize]);
+        for piece in grid {
+            let c = compact.cells[piece as usize];
+            let ground = hexes.iter().find(|(h, _)| *h == c).expect("Unknown cell").1;
+            game.add_to_grid(
+                Piece::from(piece_id(piece)),
+                ground.with_z(compact.z[piece as usize] as isize),
+            )
+            .expect("Every piece is on its own cell and level");
+        }
+
+        // Work back through the moves to find where each one went in the new coordinates
+        let mut before = game.clone();
+        let mut rewind = compact.clone();
+        let mut move_list: Vec<PieceMove> = Vec::new();
+        while let Some(&m) = rewind.move_list.last() {
+            rewind.umake_move(m);
+            let piece_move = match m {
+                CompactMove::Place { piece, .. } => {
+                    let piece_id = piece_id(piece);
+                    let hex = before
+                        .remove_from_grid(piece_id)
+                        .expect("Placed pieces are on the grid")
+                        .hex;
+                    before.hand_mut(piece_id.color).put_back(piece_id);
+                    PieceMove::Place { piece_id, hex }
+                }
+                CompactMove::Move { piece, from, .. } => {
+                    let piece_id = piece_id(piece);
+                    let ground = hex_of_cell(&before, from, piece_id).expect("Unknown cell");
+                    let from = ground.with_z(rewind.z[piece as usize] as isize);
+                    let piece = before
+                        .remove_from_grid(piece_id)
+                        .expect("Moved pieces are on the grid");
+                    let hex = piece.hex;
+                    before
+                        .add_to_grid(piece, from)
+                        .expect("A piece's origin is empty before it moves");
+                    PieceMove::Move {
+                        piece_id,
+                        from,
+                        hex,
+                    }
+                }
+                CompactMove::Pass => PieceMove::Pass,
+            };
+            move_list.push(piece_move);
+        }
+        move_list.reverse();
+        game.move_list = move_list;
+        game.rebuild_history()
+            .expect("A compact game's moves can be taken back");
+        return game;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::random::{random_game, RandomGameConfig};
+
+    // Each position of a few random games, as a Game and as a CompactGame
+    fn positions() -> Vec<(Game, CompactGame)> {
+        let mut positions: Vec<(Game, CompactGame)> = Vec::new();
+        for seed in 0..6 {
+            let config = RandomGameConfig {
+                seed,
+                max_plies: 40,
+                ..RandomGameConfig::default()
+            };
+            let mut game = Game::with_type(config.game_type);
+            let mut compact = CompactGame::with_type(config.game_type);
+            for m in random_game(&config).move_list {
+                compact.make_move(compact_move(&m));
+                game.make_move(m).unwrap();
+                positions.push((game.clone(), compact.clone()));
+            }
+        }
+        return positions;
+    }
+
+    #[test]
+    fn same_moves_and_state_as_game() {
+        for (game, compact) in positions() {
+            let mut expected: Vec<CompactMove> =
+                game.get_all_moves().iter().map(compact_move).collect();
+            expected.sort_unstable();
+            expected.dedup();
+            let mut found = compact.get_all_moves();
+            found.sort_unstable();
+            assert_eq!(found, expected, "{:?}", game.export_game_string());
+            assert_eq!(compact.get_state(), game.get_state());
+            assert_eq!(compact.current_player(), game.current_player());
+            assert_eq!(compact.turn_number(), game.turn_number());
+        }
+    }
+
+    #[test]
+    fn converts_back_to_the_same_game() {
+        for (game, compact) in positions() {
+            assert!(Game::from(&compact) == game);
+            assert!(Game::from(&CompactGame::from(&game)) == game);
+            assert_eq!(
+                CompactGame::from(&game).position_hash(),
+                compact.position_hash()
+            );
+        }
+    }
+
+    #[test]
+    fn umake_restores_the_position() {
+        for (_, mut compact) in positions() {
+            let before = Game::from(&compact);
+            for m in compact.get_all_moves() {
+                compact.make_move(m);
+                compact.umake_move(m);
+                assert!(Game::from(&compact) == before);
+            }
+        }
+    }
+
+    #[test]
+    fn piece_moves_round_trip() {
+        for (game, compact) in positions() {
+            for m in compact.get_all_moves() {
+                assert_eq!(piece_move(&game, m).map(|m| compact_move(&m)), Ok(m));
+            }
+        }
+    }
+
+    #[test]
+    fn moves_off_the_hive_are_errors() {
+        let mut game = Game::new();
+        let place = |id: &str, hex: Hex| CompactMove::Place {
+            piece: piece_index(PieceId::parse(id).unwrap()),
+            to: cell(hex),
+        };
+        let far = Hex::from_axial(5, -2);
+        assert_eq!(
+            piece_move(&game, place("wq", far)),
+            Err(GameError::CellOffHive(cell(far)))
+        );
+        let first = piece_move(&game, place("wq", Hex::new())).unwrap();
+        game.make_move(first).unwrap();
+        assert_eq!(
+            piece_move(&game, place("bq", far)),
+            Err(GameError::CellOffHive(cell(far)))
+        );
+    }
+
+    #[test]
+    fn piece_numbers_round_trip() {
+        for piece in 0..PIECE_COUNT as u8 {
+            let id = piece_id(piece);
+            assert_eq!(piece_index(id), piece);
+            assert_eq!(
+                (piece_color(piece), piece_type(piece)),
+                (id.color, id.piece_type)
+            );
+        }
+    }
+}
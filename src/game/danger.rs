@@ -0,0 +1,219 @@
+// Queen danger
+//
+// How close each queen is to being surrounded: how many of the cells around it are occupied, which
+// of the empty ones could be filled on the next turn (and by which moves), and which of its own
+// side's pieces are pinned in place by the one hive rule. Evaluators and the UI both want this
+// without walking Hex::get_neighbors themselves.
+//
+// "Next turn" means the next turn of each player: the player to move's moves now, and the other
+// player's as if the player to move passed.
+
+use crate::game::hex::Hex;
+use crate::game::piece::{Piece, PieceColor, PieceId, PieceType};
+use crate::game::piece_move::PieceMove;
+use crate::game::Game;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueenDanger {
+    pub color: PieceColor,
+    pub hex: Hex,
+    // Cells around the queen with a piece on them, of either colour
+    pub occupied: usize,
+    // Every empty cell around the queen
+    pub empty: Vec<EmptyNeighbor>,
+    // color's pieces on top of their stacks that can't move without splitting the hive
+    pub pinned: Vec<PieceId>,
+}
+
+impl QueenDanger {
+    // The empty cells around the queen that the given player could fill next turn
+    pub fn fillable_by(&self, color: PieceColor) -> Vec<Hex> {
+        return self
+            .empty
+            .iter()
+            .filter(|n| !n.fills(color).is_empty())
+            .map(|n| n.hex)
+            .collect();
+    }
+}
+
+// An empty cell around a queen, and the moves either player could make onto it next turn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmptyNeighbor {
+    pub hex: Hex,
+    pub white: Vec<PieceMove>,
+    pub black: Vec<PieceMove>,
+}
+
+impl EmptyNeighbor {
+    // The moves player color could fill the cell with. A move made with a pillbug moves one of
+    // the other player's pieces, and a piece already next to the queen that moves here leaves
+    // another cell empty.
+    pub fn fills(&self, color: PieceColor) -> &[PieceMove] {
+        match color {
+            PieceColor::White => return &self.white,
+            PieceColor::Black => return &self.black,
+        }
+    }
+
+    // The pieces that could end up on the cell next turn, from either player's moves
+    pub fn pieces(&self) -> Vec<PieceId> {
+        let mut pieces: Vec<PieceId> = Vec::new();
+        for id in self
+            .white
+            .iter()
+            .chain(&self.black)
+            .filter_map(|m| m.piece_id())
+        {
+            if !pieces.contains(&id) {
+                pieces.push(id);
+            }
+        }
+        return pieces;
+    }
+}
+
+impl Game {
+    // color's queen, if it has been placed
+    pub fn queen(&self, color: PieceColor) -> Option<&Piece> {
+        return self
+            .grid
+            .node_weights()
+            .find(|p| p.id.color == color && p.id.piece_type == PieceType::QueenBee);
+    }
+
+    // None until color's queen has been placed
+    pub fn queen_danger(&self, color: PieceColor) -> Option<QueenDanger> {
+        let queen = self.queen(color)?;
+        let (hex, queen_id) = (queen.hex, queen.id);
+        let white_moves = self.next_turn_moves(PieceColor::White);
+        let black_moves = self.next_turn_moves(PieceColor::Black);
+
+        let mut occupied = 0;
+        let mut empty: Vec<EmptyNeighbor> = Vec::new();
+        for n in hex.get_neighbors() {
+            if self.stack_height(n) > 0 {
+                occupied += 1;
+                continue;
+            }
+            let onto = |moves: &[PieceMove]| -> Vec<PieceMove> {
+                return moves
+                    .iter()
+                    .filter(|m| m.hex().is_some_and(|h| h.same_cell(n)))
+                    // The queen moving next to where it was doesn't fill anything
+                    .filter(|m| m.piece_id() != Some(queen_id))
+                    .cloned()
+                    .collect();
+            };
+            empty.push(EmptyNeighbor {
+                hex: n.with_z(0),
+                white: onto(&white_moves),
+                black: onto(&black_moves),
+            });
+        }
+
+        return Some(QueenDanger {
+            color,
+            hex,
+            occupied,
+            empty,
+            pinned: self.pinned_pieces(color),
+        });
+    }
+
+    // color's pieces on top of their stacks that can_move says can't move
+    pub fn pinned_pieces(&self, color: PieceColor) -> Vec<PieceId> {
+        return self
+            .grid
+            .node_weights()
+            .filter(|p| {
+                p.id.color == color
+                    && self.top_piece_at(p.hex).map(|t| t.id) == Some(p.id)
+                    && !p.can_move(self)
+            })
+            .map(|p| p.id)
+            .collect();
+    }
+
+    // The moves color could make on their next turn: now if it's their turn, otherwise after the
+    // player to move passes
+    pub fn next_turn_moves(&self, color: PieceColor) -> Vec<PieceMove> {
+        if color == self.current_player() {
+            return self.get_all_moves();
+        }
+        let mut passed = self.clone();
+        passed
+            .make_move(PieceMove::Pass)
+            .expect("Passing can always be made");
+        return passed.get_all_moves();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_type::GameType;
+    use crate::game::uhp::id_from_uhp_name;
+
+    // wA1 wQ bQ bA1 in a row
+    fn line() -> Game {
+        let mut game = Game::with_type(GameType::BASE);
+        for move_string in ["wQ", "bQ wQ-", "wA1 -wQ", "bA1 bQ-"] {
+            let m = game.parse_move_string(move_string).unwrap();
+            game.try_make_move(m).unwrap();
+        }
+        return game;
+    }
+
+    fn id(s: &str) -> PieceId {
+        return id_from_uhp_name(s).unwrap();
+    }
+
+    #[test]
+    fn no_danger_before_the_queen_is_placed() {
+        let mut game = Game::with_type(GameType::BASE);
+        let m = game.parse_move_string("wA1").unwrap();
+        game.try_make_move(m).unwrap();
+        assert_eq!(game.queen_danger(PieceColor::White), None);
+        assert_eq!(game.queen_danger(PieceColor::Black), None);
+    }
+
+    #[test]
+    fn counts_neighbours_and_pins() {
+        let game = line();
+        let danger = game.queen_danger(PieceColor::White).unwrap();
+        assert_eq!(danger.hex, game.queen(PieceColor::White).unwrap().hex);
+        assert_eq!(danger.occupied, 2);
+        assert_eq!(danger.empty.len(), 4);
+        assert_eq!(danger.pinned, vec![id("wQ")]);
+        assert_eq!(game.pinned_pieces(PieceColor::Black), vec![id("bQ")]);
+    }
+
+    #[test]
+    fn fills_are_the_moves_onto_each_cell() {
+        let game = line();
+        let danger = game.queen_danger(PieceColor::White).unwrap();
+        for cell in &danger.empty {
+            // Every cell around wQ touches it, so black can only get there with its ant
+            assert!(!cell.black.is_empty());
+            assert!(cell.black.iter().all(|m| m.piece_id() == Some(id("bA1"))));
+            for m in cell.white.iter().chain(&cell.black) {
+                assert!(m.hex().unwrap().same_cell(cell.hex));
+                assert_ne!(m.piece_id(), Some(id("wQ")));
+            }
+            assert!(cell.pieces().contains(&id("bA1")));
+        }
+        // White can place on the two cells away from bQ
+        let placeable = danger
+            .empty
+            .iter()
+            .filter(|cell| {
+                cell.white
+                    .iter()
+                    .any(|m| matches!(m, PieceMove::Place { .. }))
+            })
+            .count();
+        assert_eq!(placeable, 2);
+        assert_eq!(danger.fillable_by(PieceColor::Black).len(), 4);
+    }
+}
@@ -0,0 +1,92 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::game::hex::Hex;
+use crate::game::ign::IgnError;
+use crate::game::piece::PieceId;
+use crate::game::piece_move::MoveError;
+use crate::game::sgf::SgfError;
+use crate::game::uhp::UhpError;
+use crate::game::validate::InvariantError;
+
+// Anything that can go wrong working with a Game. The notation modules and try_make_move have
+// their own, more detailed errors, which convert into this one.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GameError {
+    PieceNotInHand(PieceId),
+    PieceNotOnGrid(PieceId),
+    // Pieces of a type have to be placed lowest number first and taken back in reverse
+    PieceOutOfOrder(PieceId),
+    HexOccupied(Hex),
+    WrongOrigin { piece_id: PieceId, from: Hex },
+    // Only the last move made can be taken back
+    NotLastMove,
+    NoPath { start: Hex, end: Hex },
+    // A CompactMove going to a cell that isn't on or next to the hive
+    CellOffHive(u16),
+    IllegalMove(MoveError),
+    Ign(IgnError),
+    Uhp(UhpError),
+    Sgf(SgfError),
+    Invalid(InvariantError),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::PieceNotInHand(id) => write!(f, "`{}` is not in hand", id),
+            GameError::PieceNotOnGrid(id) => write!(f, "`{}` is not on the grid", id),
+            GameError::PieceOutOfOrder(id) => {
+                write!(f, "`{}` is out of order with the rest of its type", id)
+            }
+            GameError::HexOccupied(hex) => write!(f, "{:?} is already occupied", hex),
+            GameError::WrongOrigin { piece_id, from } => {
+                write!(f, "`{}` is not at {:?}", piece_id, from)
+            }
+            GameError::NotLastMove => write!(f, "only the last move can be taken back"),
+            GameError::NoPath { start, end } => {
+                write!(f, "there is no path from {:?} to {:?}", start, end)
+            }
+            GameError::CellOffHive(cell) => {
+                write!(f, "cell {} is not on or next to the hive", cell)
+            }
+            GameError::IllegalMove(e) => write!(f, "illegal move: {}", e),
+            GameError::Ign(e) => write!(f, "{}", e),
+            GameError::Uhp(e) => write!(f, "{}", e),
+            GameError::Sgf(e) => write!(f, "{}", e),
+            GameError::Invalid(e) => write!(f, "invalid game: {}", e),
+        }
+    }
+}
+
+impl Error for GameError {}
+
+impl From<MoveError> for GameError {
+    fn from(e: MoveError) -> Self {
+        return GameError::IllegalMove(e);
+    }
+}
+
+impl From<IgnError> for GameError {
+    fn from(e: IgnError) -> Self {
+        return GameError::Ign(e);
+    }
+}
+
+impl From<UhpError> for GameError {
+    fn from(e: UhpError) -> Self {
+        return GameError::Uhp(e);
+    }
+}
+
+impl From<SgfError> for GameError {
+    fn from(e: SgfError) -> Self {
+        return GameError::Sgf(e);
+    }
+}
+
+impl From<InvariantError> for GameError {
+    fn from(e: InvariantError) -> Self {
+        return GameError::Invalid(e);
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+
+use crate::game::piece::PieceType;
+
+// Which expansion bugs are in play, on top of the base game's queen, spiders, beetles,
+// grasshoppers and ants. Written the UHP way: `Base`, `Base+M`, `Base+LP`, `Base+MLP`...
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct GameType {
+    pub mosquito: bool,
+    pub ladybug: bool,
+    pub pillbug: bool,
+}
+
+// Every expansion, which is what a game gets unless it asks for something else
+impl Default for GameType {
+    fn default() -> Self {
+        return GameType::BASE_MLP;
+    }
+}
+
+impl GameType {
+    pub const BASE: GameType = GameType {
+        mosquito: false,
+        ladybug: false,
+        pillbug: false,
+    };
+    pub const BASE_MLP: GameType = GameType {
+        mosquito: true,
+        ladybug: true,
+        pillbug: true,
+    };
+
+    // Expansions have to be listed in UHP's order (M, L, P) with none repeated
+    pub fn parse(s: &str) -> Option<GameType> {
+        let expansions = match s.strip_prefix("Base") {
+            Some("") => return Some(GameType::BASE),
+            Some(rest) => rest.strip_prefix('+').filter(|e| !e.is_empty())?,
+            None => return None,
+        };
+        let mut game_type = GameType::BASE;
+        let mut rest = expansions;
+        for (letter, included) in [
+            ('M', &mut game_type.mosquito),
+            ('L', &mut game_type.ladybug),
+            ('P', &mut game_type.pillbug),
+        ] {
+            if let Some(r) = rest.strip_prefix(letter) {
+                *included = true;
+                rest = r;
+            }
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+        return Some(game_type);
+    }
+
+    // The smallest game type with all of piece_types in it
+    pub fn containing(piece_types: impl IntoIterator<Item = PieceType>) -> GameType {
+        let mut game_type = GameType::BASE;
+        for piece_type in piece_types {
+            match piece_type {
+                PieceType::Mosquito => game_type.mosquito = true,
+                PieceType::Ladybug => game_type.ladybug = true,
+                PieceType::Pillbug => game_type.pillbug = true,
+                _ => {}
+            }
+        }
+        return game_type;
+    }
+
+    pub fn includes(&self, piece_type: PieceType) -> bool {
+        match piece_type {
+            PieceType::Mosquito => return self.mosquito,
+            PieceType::Ladybug => return self.ladybug,
+            PieceType::Pillbug => return self.pillbug,
+            _ => return true,
+        }
+    }
+}
+
+impl fmt::Display for GameType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Base")?;
+        if *self == GameType::BASE {
+            return Ok(());
+        }
+        write!(f, "+")?;
+        for (letter, included) in [
+            ('M', self.mosquito),
+            ('L', self.ladybug),
+            ('P', self.pillbug),
+        ] {
+            if included {
+                write!(f, "{}", letter)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl From<GameType> for String {
+    fn from(game_type: GameType) -> Self {
+        return game_type.to_string();
+    }
+}
+
+impl TryFrom<String> for GameType {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        return GameType::parse(&s).ok_or_else(|| format!("unknown game type `{}`", s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hand::Hand;
+    use crate::game::piece::PieceColor;
+    use crate::game::piece_move::PieceMove;
+    use crate::game::Game;
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for s in [
+            "Base", "Base+M", "Base+L", "Base+P", "Base+ML", "Base+MP", "Base+LP", "Base+MLP",
+        ] {
+            assert_eq!(GameType::parse(s).unwrap().to_string(), s);
+        }
+        for s in ["", "Base+", "Base+LM", "Base+MM", "Base+X", "base"] {
+            assert_eq!(GameType::parse(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn expansion_pieces_only_in_their_game_types() {
+        assert_eq!(Hand::starting(PieceColor::White, GameType::BASE).len(), 11);
+        assert_eq!(
+            Hand::starting(PieceColor::White, GameType::BASE_MLP).len(),
+            14
+        );
+
+        let game = Game::with_type(GameType::parse("Base+L").unwrap());
+        let placed: Vec<PieceType> = game
+            .get_all_moves()
+            .iter()
+            .filter_map(|m| match m {
+                PieceMove::Place { piece_id, .. } => Some(piece_id.piece_type),
+                _ => None,
+            })
+            .collect();
+        assert!(placed.contains(&PieceType::Ladybug));
+        assert!(!placed.contains(&PieceType::Mosquito) && !placed.contains(&PieceType::Pillbug));
+    }
+
+    #[test]
+    fn containing_picks_the_smallest_game_type() {
+        assert_eq!(
+            GameType::containing([PieceType::Ant, PieceType::QueenBee]),
+            GameType::BASE
+        );
+        assert_eq!(
+            GameType::containing([PieceType::Pillbug, PieceType::Mosquito]),
+            GameType::parse("Base+MP").unwrap()
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::game::game_type::GameType;
+use crate::game::piece::{Piece, PieceColor, PieceId, PieceType};
+
+// The order pieces are listed in a hand (see get_starting_hand)
+pub const HAND_ORDER: [PieceType; 8] = [
+    PieceType::QueenBee,
+    PieceType::Spider,
+    PieceType::Beetle,
+    PieceType::Grasshopper,
+    PieceType::Ant,
+    PieceType::Mosquito,
+    PieceType::Ladybug,
+    PieceType::Pillbug,
+];
+
+// The pieces a player has yet to place, counted by type.
+//
+// Pieces of a type are always placed lowest number first (and taken back in reverse), so the count
+// is all it takes to know which ones are left: with one of three ants placed, the hand holds wa2
+// and wa3.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Hand {
+    color: PieceColor,
+    // Indexed by HAND_ORDER
+    counts: [u8; 8],
+}
+
+impl Hand {
+    // An empty hand
+    pub fn new(color: PieceColor) -> Self {
+        return Hand {
+            color,
+            counts: [0; 8],
+        };
+    }
+
+    // Every piece the player starts a game of game_type with
+    pub fn starting(color: PieceColor, game_type: GameType) -> Self {
+        let mut hand = Hand::new(color);
+        for (i, piece_type) in HAND_ORDER.iter().enumerate() {
+            if game_type.includes(*piece_type) {
+                hand.counts[i] = piece_type.count();
+            }
+        }
+        return hand;
+    }
+
+    // Builds a hand from the ids in it, which have to be the highest numbered pieces of each type
+    pub fn from_ids(color: PieceColor, ids: &[PieceId]) -> Option<Self> {
+        let mut ids = ids.to_vec();
+        ids.sort_by_key(|id| std::cmp::Reverse(id.ordinal));
+        let mut hand = Hand::new(color);
+        for id in ids {
+            if !hand.put_back(id) {
+                return None;
+            }
+        }
+        return Some(hand);
+    }
+
+    pub fn color(&self) -> PieceColor {
+        return self.color;
+    }
+
+    pub fn count(&self, piece_type: PieceType) -> u8 {
+        return self.counts[index(piece_type)];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.counts.iter().map(|&c| c as usize).sum();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    pub fn contains(&self, id: PieceId) -> bool {
+        return id.color == self.color
+            && id.ordinal > id.piece_type.count() - self.count(id.piece_type)
+            && id.ordinal <= id.piece_type.count();
+    }
+
+    // The piece of this type that would be placed next: the lowest numbered one left
+    pub fn next(&self, piece_type: PieceType) -> Option<PieceId> {
+        let count = self.count(piece_type);
+        if count == 0 {
+            return None;
+        }
+        return Some(PieceId::new(
+            self.color,
+            piece_type,
+            piece_type.count() - count + 1,
+        ));
+    }
+
+    // Takes id out of the hand. Only the next piece of its type can be taken.
+    pub fn take(&mut self, id: PieceId) -> bool {
+        if self.next(id.piece_type) != Some(id) {
+            return false;
+        }
+        self.counts[index(id.piece_type)] -= 1;
+        return true;
+    }
+
+    // Puts id back in the hand. Only the last piece of its type to be taken can go back.
+    pub fn put_back(&mut self, id: PieceId) -> bool {
+        let count = self.count(id.piece_type);
+        if id.color != self.color || count + id.ordinal != id.piece_type.count() {
+            return false;
+        }
+        self.counts[index(id.piece_type)] += 1;
+        return true;
+    }
+
+    // Every id left, in hand order
+    pub fn ids(&self) -> Vec<PieceId> {
+        let mut ids: Vec<PieceId> = Vec::new();
+        for piece_type in HAND_ORDER {
+            let count = self.count(piece_type);
+            for ordinal in piece_type.count() - count + 1..=piece_type.count() {
+                ids.push(PieceId::new(self.color, piece_type, ordinal));
+            }
+        }
+        return ids;
+    }
+
+    pub fn pieces(&self) -> Vec<Piece> {
+        return self.ids().into_iter().map(Piece::from).collect();
+    }
+}
+
+fn index(piece_type: PieceType) -> usize {
+    return HAND_ORDER
+        .iter()
+        .position(|&t| t == piece_type)
+        .expect("Every piece type is in HAND_ORDER");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_come_out_lowest_number_first() {
+        let mut hand = Hand::starting(PieceColor::White, GameType::BASE);
+        let mut placed: Vec<PieceId> = Vec::new();
+        while let Some(id) = hand.next(PieceType::Ant) {
+            assert!(hand.take(id));
+            placed.push(id);
+        }
+        let names: Vec<String> = placed.iter().map(|id| id.to_string()).collect();
+        assert_eq!(names, ["wa1", "wa2", "wa3"]);
+        assert_eq!(hand.count(PieceType::Ant), 0);
+    }
+
+    #[test]
+    fn pieces_go_back_highest_number_first() {
+        let mut hand = Hand::starting(PieceColor::White, GameType::BASE);
+        let [wa1, wa2, wa3] = [1, 2, 3].map(|n| PieceId::new(PieceColor::White, PieceType::Ant, n));
+        assert!(!hand.take(wa2));
+        assert!(hand.take(wa1) && hand.take(wa2));
+        assert!(!hand.put_back(wa1));
+        assert!(hand.put_back(wa2));
+        assert_eq!(hand.next(PieceType::Ant), Some(wa2));
+        assert!(hand.contains(wa3) && !hand.contains(wa1));
+    }
+}
@@ -1,18 +1,206 @@
+// Cube coordinates
+//
+// A Hex is a cell of the grid in cube coordinates (q + r + s == 0) plus a height z in its stack.
+// The grid is flat-topped: N and S neighbours sit straight above and below each other. Distances,
+// rings, lines, rotations and reflections only look at the cell and keep z as it is, except for
+// the arithmetic operators, which treat a Hex as a vector and work on all four coordinates.
+//
+// q, r and s are private, and everything here that makes a Hex keeps q + r + s == 0, so every Hex
+// is a real cell. z is public: any height goes with any cell.
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+pub const SQRT_3: f64 = 1.732_050_807_568_877_2;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "CubeCoordinates")
+)]
 pub struct Hex {
-    pub q: isize,
-    pub r: isize,
-    pub s: isize,
+    q: isize,
+    r: isize,
+    s: isize,
     pub z: isize,
 }
 
 impl Hex {
+    // The origin, at ground level
     pub fn new() -> Hex {
         return Hex {
             q: 0,
             r: 0,
             s: 0,
-            z: isize::MAX,
+            z: 0,
+        };
+    }
+
+    pub fn q(&self) -> isize {
+        return self.q;
+    }
+
+    pub fn r(&self) -> isize {
+        return self.r;
+    }
+
+    pub fn s(&self) -> isize {
+        return self.s;
+    }
+
+    // A hex at ground level, or None unless q + r + s == 0
+    pub fn from_cube(q: isize, r: isize, s: isize) -> Option<Hex> {
+        if q + r + s != 0 {
+            return None;
+        }
+        return Some(Hex { q, r, s, z: 0 });
+    }
+
+    // A hex at ground level from axial coordinates, which leave out s
+    pub fn from_axial(q: isize, r: isize) -> Hex {
+        return Hex {
+            q,
+            r,
+            s: -q - r,
+            z: 0,
+        };
+    }
+
+    pub fn to_axial(&self) -> (isize, isize) {
+        return (self.q, self.r);
+    }
+
+    // A hex at ground level from (column, row) offset coordinates
+    pub fn from_offset(col: isize, row: isize, offset: Offset) -> Hex {
+        let shift = match offset {
+            Offset::Even => (col + (col & 1)) / 2,
+            Offset::Odd => (col - (col & 1)) / 2,
+        };
+        return Hex::from_axial(col, row - shift);
+    }
+
+    pub fn to_offset(&self, offset: Offset) -> (isize, isize) {
+        let shift = match offset {
+            Offset::Even => (self.q + (self.q & 1)) / 2,
+            Offset::Odd => (self.q - (self.q & 1)) / 2,
+        };
+        return (self.q, self.r + shift);
+    }
+
+    // The centre of the hex in pixels, with the origin at (0, 0) and size the distance from a
+    // hex's centre to its corners. y grows downwards.
+    pub fn to_pixel(&self, size: f64) -> (f64, f64) {
+        let x = size * 1.5 * self.q as f64;
+        let y = size * SQRT_3 * (self.r as f64 + self.q as f64 / 2.0);
+        return (x, y);
+    }
+
+    // The ground level hex the pixel is in
+    pub fn from_pixel(x: f64, y: f64, size: f64) -> Hex {
+        let q = x / (1.5 * size);
+        let r = y / (SQRT_3 * size) - q / 2.0;
+        return round_cube(q, r, -q - r);
+    }
+
+    // A copy at height z
+    pub fn with_z(&self, z: isize) -> Hex {
+        return Hex { z, ..*self };
+    }
+
+    // Number of steps between the two cells, ignoring height
+    pub fn distance(&self, other: Hex) -> usize {
+        return (self.q.abs_diff(other.q) + self.r.abs_diff(other.r) + self.s.abs_diff(other.s))
+            / 2;
+    }
+
+    // The cells radius steps away, starting radius steps N and going clockwise. Radius 0 is just
+    // the hex itself.
+    pub fn ring(&self, radius: usize) -> Vec<Hex> {
+        if radius == 0 {
+            return vec![*self];
+        }
+        let mut ring: Vec<Hex> = Vec::with_capacity(6 * radius);
+        let mut hex = *self;
+        for _ in 0..radius {
+            hex = hex.get_neighbor(HexEdge::N);
+        }
+        // From the N corner, each side of the ring runs two directions clockwise of N
+        for dir in [
+            HexEdge::SE,
+            HexEdge::S,
+            HexEdge::SW,
+            HexEdge::NW,
+            HexEdge::N,
+            HexEdge::NE,
+        ] {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex.get_neighbor(dir);
+            }
+        }
+        return ring;
+    }
+
+    // Every cell within radius steps: the hex itself, then each ring outwards
+    pub fn spiral(&self, radius: usize) -> Vec<Hex> {
+        return (0..=radius).flat_map(|r| self.ring(r)).collect();
+    }
+
+    // The cells on the straight line from this hex to other, both ends included. Lines that run
+    // exactly between two cells take the one clockwise of the line.
+    pub fn line_to(&self, other: Hex) -> Vec<Hex> {
+        let steps = self.distance(other);
+        if steps == 0 {
+            return vec![*self];
+        }
+        // Nudged so points exactly between two cells round the same way every time
+        let (q1, r1, s1) = (
+            self.q as f64 + 1e-6,
+            self.r as f64 + 1e-6,
+            self.s as f64 - 2e-6,
+        );
+        let (q2, r2, s2) = (
+            other.q as f64 + 1e-6,
+            other.r as f64 + 1e-6,
+            other.s as f64 - 2e-6,
+        );
+        return (0..=steps)
+            .map(|i| {
+                let t = i as f64 / steps as f64;
+                let hex = round_cube(q1 + (q2 - q1) * t, r1 + (r2 - r1) * t, s1 + (s2 - s1) * t);
+                return hex.with_z(self.z);
+            })
+            .collect();
+    }
+
+    // Rotated clockwise about centre's cell by sixths of a turn (negative for anticlockwise)
+    pub fn rotate_about(&self, centre: Hex, sixths: isize) -> Hex {
+        let (mut q, mut r, mut s) = (self.q - centre.q, self.r - centre.r, self.s - centre.s);
+        for _ in 0..sixths.rem_euclid(6) {
+            (q, r, s) = (-r, -s, -q);
+        }
+        return Hex {
+            q: q + centre.q,
+            r: r + centre.r,
+            s: s + centre.s,
+            z: self.z,
+        };
+    }
+
+    // Mirrored in the line through centre's cell along axis: the axis coordinate stays the same
+    // and the other two swap
+    pub fn reflect_about(&self, centre: Hex, axis: Axis) -> Hex {
+        let (q, r, s) = (self.q - centre.q, self.r - centre.r, self.s - centre.s);
+        let (q, r, s) = match axis {
+            Axis::Q => (q, s, r),
+            Axis::R => (s, r, q),
+            Axis::S => (r, q, s),
+        };
+        return Hex {
+            q: q + centre.q,
+            r: r + centre.r,
+            s: s + centre.s,
+            z: self.z,
         };
     }
 
@@ -93,19 +281,139 @@ impl Hex {
         }
     }
 
+    // True if both hexes are on the same spot of the grid, whatever their height
+    pub fn same_cell(&self, other: Hex) -> bool {
+        return self.q == other.q && self.r == other.r && self.s == other.s;
+    }
+
     pub fn get_neighbors(&self) -> Vec<Hex> {
-        let mut n = Vec::new();
-        n.push(self.get_neighbor(HexEdge::N));
-        n.push(self.get_neighbor(HexEdge::NE));
-        n.push(self.get_neighbor(HexEdge::SE));
-        n.push(self.get_neighbor(HexEdge::S));
-        n.push(self.get_neighbor(HexEdge::SW));
-        n.push(self.get_neighbor(HexEdge::NW));
-        return n;
+        return vec![
+            self.get_neighbor(HexEdge::N),
+            self.get_neighbor(HexEdge::NE),
+            self.get_neighbor(HexEdge::SE),
+            self.get_neighbor(HexEdge::S),
+            self.get_neighbor(HexEdge::SW),
+            self.get_neighbor(HexEdge::NW),
+        ];
+    }
+}
+
+impl Default for Hex {
+    fn default() -> Self {
+        return Hex::new();
+    }
+}
+
+impl Add for Hex {
+    type Output = Hex;
+
+    fn add(self, other: Hex) -> Hex {
+        return Hex {
+            q: self.q + other.q,
+            r: self.r + other.r,
+            s: self.s + other.s,
+            z: self.z + other.z,
+        };
+    }
+}
+
+impl Sub for Hex {
+    type Output = Hex;
+
+    fn sub(self, other: Hex) -> Hex {
+        return Hex {
+            q: self.q - other.q,
+            r: self.r - other.r,
+            s: self.s - other.s,
+            z: self.z - other.z,
+        };
+    }
+}
+
+impl Neg for Hex {
+    type Output = Hex;
+
+    fn neg(self) -> Hex {
+        return Hex {
+            q: -self.q,
+            r: -self.r,
+            s: -self.s,
+            z: -self.z,
+        };
+    }
+}
+
+impl Mul<isize> for Hex {
+    type Output = Hex;
+
+    fn mul(self, k: isize) -> Hex {
+        return Hex {
+            q: self.q * k,
+            r: self.r * k,
+            s: self.s * k,
+            z: self.z * k,
+        };
     }
 }
 
+// The nearest cell to fractional cube coordinates: round each one, then fix up whichever moved
+// furthest so they still add up to zero
+fn round_cube(q: f64, r: f64, s: f64) -> Hex {
+    let (mut rq, mut rr, mut rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    } else {
+        rs = -rq - rr;
+    }
+    return Hex {
+        q: rq as isize,
+        r: rr as isize,
+        s: rs as isize,
+        z: 0,
+    };
+}
+
+// What a Hex is read from, checked before it becomes one
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct CubeCoordinates {
+    q: isize,
+    r: isize,
+    s: isize,
+    z: isize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<CubeCoordinates> for Hex {
+    type Error = String;
+
+    fn try_from(c: CubeCoordinates) -> Result<Self, Self::Error> {
+        let hex = Hex::from_cube(c.q, c.r, c.s)
+            .ok_or_else(|| format!("q + r + s is {}, not 0", c.q + c.r + c.s))?;
+        return Ok(hex.with_z(c.z));
+    }
+}
+
+// Which columns of offset coordinates are pushed down half a hex: even-q or odd-q
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Offset {
+    Even,
+    Odd,
+}
+
+// The three lines through a cell, named by the coordinate that stays the same along them
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Axis {
+    Q,
+    R,
+    S,
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HexEdge {
     N,
     NE,
@@ -154,3 +462,169 @@ pub fn get_edge_types() -> [HexEdge; 6] {
         HexEdge::NW,
     ];
 }
+
+// The six directions around a hex, then up and down a stack
+pub fn get_all_edge_types() -> [HexEdge; 8] {
+    return [
+        HexEdge::N,
+        HexEdge::NE,
+        HexEdge::SE,
+        HexEdge::S,
+        HexEdge::SW,
+        HexEdge::NW,
+        HexEdge::T,
+        HexEdge::B,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells() -> Vec<Hex> {
+        return Hex::from_axial(1, -2).spiral(4);
+    }
+
+    #[test]
+    fn coordinate_conversions_round_trip() {
+        assert_eq!(Hex::from_cube(1, 2, 3), None);
+        assert_eq!(Hex::from_cube(1, 2, -3), Some(Hex::from_axial(1, 2)));
+        for hex in cells() {
+            let (q, r) = hex.to_axial();
+            assert_eq!(Hex::from_axial(q, r), hex);
+            for offset in [Offset::Even, Offset::Odd] {
+                let (col, row) = hex.to_offset(offset);
+                assert_eq!(Hex::from_offset(col, row, offset), hex);
+            }
+            let (x, y) = hex.to_pixel(10.0);
+            assert_eq!(Hex::from_pixel(x, y, 10.0), hex);
+            assert_eq!(Hex::from_pixel(x + 4.0, y - 4.0, 10.0), hex);
+        }
+    }
+
+    #[test]
+    fn neighbours_are_one_step_away() {
+        let origin = Hex::from_axial(0, 0);
+        for edge in get_edge_types() {
+            let neighbor = origin.get_neighbor(edge);
+            assert_eq!(origin.distance(neighbor), 1);
+            assert_eq!(neighbor.get_neighbor(edge.get_opposite()), origin);
+            assert_eq!(edge.get_opposite().get_opposite(), edge);
+        }
+        let above = origin.get_neighbor(HexEdge::T);
+        assert!(above.same_cell(origin));
+        assert_eq!(above.z, 1);
+        assert_eq!(above.get_neighbor(HexEdge::B), origin);
+    }
+
+    #[test]
+    fn rings_and_spirals() {
+        let centre = Hex::from_axial(2, -1).with_z(3);
+        assert_eq!(centre.ring(0), vec![centre]);
+        // Clockwise from N
+        let around: Vec<Hex> = get_edge_types()
+            .iter()
+            .map(|&e| centre.get_neighbor(e))
+            .collect();
+        assert_eq!(centre.ring(1), around);
+        for radius in 1..5 {
+            let ring = centre.ring(radius);
+            assert_eq!(ring.len(), 6 * radius);
+            assert!(ring.iter().all(|h| centre.distance(*h) == radius));
+            assert_eq!(ring[0].r, centre.r - radius as isize);
+            for (i, hex) in ring.iter().enumerate() {
+                assert_eq!(hex.distance(ring[(i + 1) % ring.len()]), 1);
+            }
+        }
+        let spiral = centre.spiral(3);
+        assert_eq!(spiral.len(), 1 + 3 * 3 * 4);
+        for (i, hex) in spiral.iter().enumerate() {
+            assert!(!spiral[..i].contains(hex));
+        }
+    }
+
+    #[test]
+    fn lines_step_one_cell_at_a_time() {
+        let start = Hex::from_axial(-2, 1).with_z(2);
+        for end in cells() {
+            let line = start.line_to(end);
+            assert_eq!(line.len(), start.distance(end) + 1);
+            assert_eq!(line[0], start);
+            assert!(line[line.len() - 1].same_cell(end));
+            assert!(line.iter().all(|h| h.z == 2));
+            for pair in line.windows(2) {
+                assert_eq!(pair[0].distance(pair[1]), 1);
+            }
+        }
+        // Exactly between two cells the line takes the same side both ways round
+        let a = Hex::from_axial(0, 0);
+        let b = Hex::from_axial(1, -2);
+        let there = a.line_to(b);
+        let mut back = b.line_to(a);
+        back.reverse();
+        assert_eq!(there, back);
+    }
+
+    #[test]
+    fn rotations_and_reflections() {
+        let centre = Hex::from_axial(1, 1);
+        let north = centre.get_neighbor(HexEdge::N);
+        assert_eq!(
+            north.rotate_about(centre, 1),
+            centre.get_neighbor(HexEdge::NE)
+        );
+        assert_eq!(
+            north.rotate_about(centre, -1),
+            centre.get_neighbor(HexEdge::NW)
+        );
+        assert_eq!(
+            north.rotate_about(centre, 3),
+            centre.get_neighbor(HexEdge::S)
+        );
+        for hex in cells() {
+            let hex = hex.with_z(1);
+            assert_eq!(hex.rotate_about(centre, 6), hex);
+            assert_eq!(hex.rotate_about(centre, 2).rotate_about(centre, -2), hex);
+            let turned = hex.rotate_about(centre, 1);
+            assert_eq!(turned.distance(centre), hex.distance(centre));
+            assert_eq!(turned.z, 1);
+            for axis in [Axis::Q, Axis::R, Axis::S] {
+                let mirrored = hex.reflect_about(centre, axis);
+                assert_eq!(mirrored.reflect_about(centre, axis), hex);
+                assert_eq!(mirrored.distance(centre), hex.distance(centre));
+                assert_eq!(mirrored.z, 1);
+            }
+            assert_eq!(hex.reflect_about(centre, Axis::Q).q, hex.q);
+        }
+    }
+
+    #[test]
+    fn every_hex_is_a_cell() {
+        assert_eq!(Hex::new(), Hex::from_axial(0, 0));
+        assert_eq!(Hex::default().z, 0);
+        let origin = Hex::new();
+        for hex in cells() {
+            assert_eq!(hex.q() + hex.r() + hex.s(), 0);
+            for moved in [
+                hex + origin.get_neighbor(HexEdge::NE),
+                hex - origin.get_neighbor(HexEdge::S),
+                -hex,
+                hex * 3,
+                hex.rotate_about(origin, 1),
+                hex.reflect_about(origin, Axis::R),
+            ] {
+                assert_eq!(moved.q() + moved.r() + moved.s(), 0);
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializing_checks_the_coordinates() {
+        let hex = Hex::from_axial(2, -3).with_z(1);
+        let json = serde_json::to_string(&hex).unwrap();
+        assert_eq!(json, r#"{"q":2,"r":-3,"s":1,"z":1}"#);
+        assert_eq!(serde_json::from_str::<Hex>(&json).unwrap(), hex);
+        assert!(serde_json::from_str::<Hex>(r#"{"q":2,"r":-3,"s":2,"z":1}"#).is_err());
+    }
+}
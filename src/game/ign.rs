@@ -0,0 +1,317 @@
+// IGN: Inline Grid Notation
+//
+// A whole position on one line:
+//
+//     <board> ; <white hand> ; <black hand> ; <turn>
+//
+// Board: the grid is drawn with flat-topped hexes, so each column (constant q) runs straight up
+// and down and every odd column sits half a cell lower than the even column to its left. The
+// leftmost occupied column is always read as column 0 (an even column), which means an IGN
+// describes a position up to translation.
+//
+// Columns are written left to right, separated by " / ". Each column lists its cells top to
+// bottom, separated by spaces, starting at the topmost row used anywhere on the board and stopping
+// after the column's last piece:
+//   - a stack is its piece ids from bottom to top joined by '_':    wq    wq_bb1    wq_bb1_wb2
+//   - a run of n empty cells is written |n|:                       |1|   |3|
+//   - a column with no pieces in it is written -
+// An empty board is written -.
+//
+// Hands: the ids of the pieces left in each hand, separated by spaces, or - for an empty hand.
+// Pieces are placed lowest number first, so a hand holds the highest numbered pieces of each type
+// (ws2 can be in hand without ws1, but not the other way round).
+//
+// Turn: who is to move and their turn number, counting from 1, as in UHP: White[1] for a new
+// game, Black[3] once each player has had two turns and white a third. An imported position
+// starts its game with that many moves already made (see Game::start_ply).
+//
+// There's no game type section: an IGN position is taken to be the smallest game type (Base,
+// Base+M, ...) with every piece it mentions.
+//
+// An imported position has to be one a game could reach (see Game::validate): every piece of the
+// game type is either in its hand or on the board, and nobody has more pieces down than turns.
+//
+// Example: three turns each into a game, a white queen with a black queen below it, covered by a
+// black beetle, and a white grasshopper to the upper left of both:
+//
+//     wg1 / wq bq_bb1 ; ws1 ws2 wb1 wb2 wg2 wg3 wa1 wa2 wa3 ; bs1 bs2 bb2 bg1 bg2 bg3 ba1 ba2 ba3 ; White[4]
+
+use std::fmt;
+
+use crate::game::game_type::GameType;
+use crate::game::hand::Hand;
+use crate::game::hex::Hex;
+use crate::game::piece::{Piece, PieceColor, PieceId};
+use crate::game::uhp::{parse_turn_string, turn_string};
+use crate::game::validate::InvariantError;
+use crate::game::Game;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum IgnError {
+    MissingSection,
+    MalformedGap(String),
+    UnknownPiece(String),
+    DuplicatePiece(String),
+    WrongHand(String),
+    MisnumberedHand(String),
+    // A piece the board can't hold where it's drawn
+    UnplaceablePiece(String),
+    MalformedTurn(String),
+    // A position no game could reach
+    Invalid(InvariantError),
+}
+
+impl fmt::Display for IgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IgnError::MissingSection => {
+                write!(
+                    f,
+                    "expected `<board> ; <white hand> ; <black hand> ; <turn>`"
+                )
+            }
+            IgnError::MalformedGap(token) => write!(f, "malformed gap `{}`", token),
+            IgnError::UnknownPiece(token) => write!(f, "unknown piece `{}`", token),
+            IgnError::DuplicatePiece(id) => write!(f, "piece `{}` appears more than once", id),
+            IgnError::WrongHand(id) => write!(f, "piece `{}` is in the wrong hand", id),
+            IgnError::MisnumberedHand(hand) => write!(
+                f,
+                "hand `{}` should hold the highest numbered pieces of each type",
+                hand
+            ),
+            IgnError::UnplaceablePiece(id) => write!(f, "piece `{}` can't go where it is", id),
+            IgnError::MalformedTurn(turn) => write!(f, "malformed turn `{}`", turn),
+            IgnError::Invalid(e) => write!(f, "impossible position: {}", e),
+        }
+    }
+}
+
+impl Game {
+    pub fn export_ign(&self) -> String {
+        let board = export_board(self);
+        let white_hand = export_hand(&self.p2_hand);
+        let black_hand = export_hand(&self.p1_hand);
+        return format!(
+            "{} ; {} ; {} ; {}",
+            board,
+            white_hand,
+            black_hand,
+            turn_string(self)
+        );
+    }
+}
+
+pub fn import_from_ign(ign: &str) -> Result<Game, IgnError> {
+    let sections: Vec<&str> = ign.split(';').map(|s| s.trim()).collect();
+    if sections.len() != 4 {
+        return Err(IgnError::MissingSection);
+    }
+
+    let mut game = Game::default();
+    let mut seen_ids: Vec<PieceId> = Vec::new();
+
+    // Board
+    if sections[0] != "-" {
+        for (col, column) in sections[0].split('/').enumerate() {
+            let column = column.trim();
+            if column == "-" {
+                continue;
+            }
+            let q = col as isize;
+            let mut row: isize = 0;
+            for token in column.split_whitespace() {
+                if token.starts_with('|') {
+                    row += parse_gap(token)?;
+                    continue;
+                }
+                let r = row - q.div_euclid(2);
+                for (z, id) in token.split('_').enumerate() {
+                    let piece = parse_piece(id, &mut seen_ids)?;
+                    let hex = Hex::from_axial(q, r).with_z(z as isize);
+                    game.add_to_grid(piece, hex)
+                        .map_err(|_| IgnError::UnplaceablePiece(id.to_string()))?;
+                }
+                row += 1;
+            }
+        }
+    }
+
+    // Hands
+    for (section, color) in [
+        (sections[1], PieceColor::White),
+        (sections[2], PieceColor::Black),
+    ] {
+        if section == "-" {
+            continue;
+        }
+        let mut ids: Vec<PieceId> = Vec::new();
+        for id in section.split_whitespace() {
+            let piece = parse_piece(id, &mut seen_ids)?;
+            if piece.id.color != color {
+                return Err(IgnError::WrongHand(piece.id.to_string()));
+            }
+            ids.push(piece.id);
+        }
+        let hand = Hand::from_ids(color, &ids)
+            .ok_or_else(|| IgnError::MisnumberedHand(section.to_string()))?;
+        match color {
+            PieceColor::White => game.p2_hand = hand,
+            PieceColor::Black => game.p1_hand = hand,
+        }
+    }
+
+    game.start_ply = parse_turn_string(sections[3])
+        .ok_or_else(|| IgnError::MalformedTurn(sections[3].to_string()))?;
+    game.game_type = GameType::containing(seen_ids.iter().map(|id| id.piece_type));
+    game.validate().map_err(IgnError::Invalid)?;
+    return Ok(game);
+}
+
+fn export_board(game: &Game) -> String {
+    let pieces: Vec<&Piece> = game.grid.node_weights().collect();
+    if pieces.is_empty() {
+        return String::from("-");
+    }
+
+    // Shift the board so the leftmost column is column 0, then find the topmost row
+    let min_q = pieces.iter().map(|p| p.hex.q()).min().unwrap_or(0);
+    let max_q = pieces.iter().map(|p| p.hex.q()).max().unwrap_or(0);
+    let row_of = |hex: Hex| hex.r() + (hex.q() - min_q).div_euclid(2);
+    let min_row = pieces.iter().map(|p| row_of(p.hex)).min().unwrap_or(0);
+
+    let mut columns: Vec<String> = Vec::new();
+    for q in min_q..=max_q {
+        let mut column_pieces: Vec<&Piece> =
+            pieces.iter().filter(|p| p.hex.q() == q).copied().collect();
+        if column_pieces.is_empty() {
+            columns.push(String::from("-"));
+            continue;
+        }
+        column_pieces.sort_by_key(|p| (row_of(p.hex), p.hex.z));
+
+        let mut cells: Vec<String> = Vec::new();
+        let mut next_row = min_row;
+        let mut i = 0;
+        while i < column_pieces.len() {
+            let row = row_of(column_pieces[i].hex);
+            if row > next_row {
+                cells.push(format!("|{}|", row - next_row));
+            }
+            let mut stack: Vec<String> = Vec::new();
+            while i < column_pieces.len() && row_of(column_pieces[i].hex) == row {
+                stack.push(column_pieces[i].id.to_string());
+                i += 1;
+            }
+            cells.push(stack.join("_"));
+            next_row = row + 1;
+        }
+        columns.push(cells.join(" "));
+    }
+    return columns.join(" / ");
+}
+
+fn export_hand(hand: &Hand) -> String {
+    if hand.is_empty() {
+        return String::from("-");
+    }
+    let ids: Vec<String> = hand.ids().iter().map(|id| id.to_string()).collect();
+    return ids.join(" ");
+}
+
+fn parse_gap(token: &str) -> Result<isize, IgnError> {
+    if let Some(count) = token
+        .strip_prefix('|')
+        .and_then(|t| t.strip_suffix('|'))
+        .and_then(|t| t.parse::<isize>().ok())
+    {
+        if count > 0 {
+            return Ok(count);
+        }
+    }
+    return Err(IgnError::MalformedGap(token.to_string()));
+}
+
+fn parse_piece(id: &str, seen_ids: &mut Vec<PieceId>) -> Result<Piece, IgnError> {
+    let piece = Piece::from_id(id).ok_or_else(|| IgnError::UnknownPiece(id.to_string()))?;
+    if seen_ids.contains(&piece.id) {
+        return Err(IgnError::DuplicatePiece(piece.id.to_string()));
+    }
+    seen_ids.push(piece.id);
+    return Ok(piece);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::random::{random_game, RandomGameConfig};
+
+    #[test]
+    fn random_positions_round_trip() {
+        for seed in 0..40 {
+            let config = RandomGameConfig {
+                seed,
+                max_plies: seed as usize * 3,
+                ..RandomGameConfig::default()
+            };
+            let game = random_game(&config).position();
+            let ign = game.export_ign();
+            assert_eq!(import_from_ign(&ign).unwrap(), game, "{}", ign);
+        }
+    }
+
+    const EXAMPLE: &str = "wg1 / wq bq_bb1 ; ws1 ws2 wb1 wb2 wg2 wg3 wa1 wa2 wa3 ; \
+                           bs1 bs2 bb2 bg1 bg2 bg3 ba1 ba2 ba3 ; White[4]";
+
+    #[test]
+    fn imports_the_example() {
+        let game = import_from_ign(EXAMPLE).unwrap();
+        assert_eq!(game.game_type, GameType::BASE);
+        assert_eq!(game.current_player(), PieceColor::White);
+        assert_eq!(game.ply(), 6);
+        assert_eq!(game.grid.node_count(), 4);
+        assert_eq!(game.export_ign(), EXAMPLE);
+        assert_eq!(import_from_ign(&game.export_ign()).unwrap(), game);
+    }
+
+    #[test]
+    fn rejects_impossible_positions() {
+        // Most of the pieces are missing
+        assert_eq!(
+            import_from_ign("wg1 / wq bq_bb1 ; ws1 ws2 ; bs1 bs2 ; White[4]").unwrap_err(),
+            IgnError::Invalid(InvariantError::MissingPiece(PieceId::parse("wb1").unwrap()))
+        );
+        // White can't have put two pieces down in one turn
+        assert_eq!(
+            import_from_ign(&EXAMPLE.replace("White[4]", "Black[1]")).unwrap_err(),
+            IgnError::Invalid(InvariantError::TooManyPieces(PieceColor::White))
+        );
+    }
+
+    #[test]
+    fn turn_sets_side_to_move() {
+        let start = Game::new().export_ign();
+        let black = start.replace("White[1]", "Black[2]");
+        let game = import_from_ign(&black).unwrap();
+        assert_eq!(game.ply(), 3);
+        assert_eq!(game.current_player(), PieceColor::Black);
+        assert_eq!(game.export_ign(), black);
+    }
+
+    #[test]
+    fn rejects_missing_or_malformed_turn() {
+        let start = Game::new().export_ign();
+        let without_turn = start.trim_end_matches(" ; White[1]");
+        assert_eq!(
+            import_from_ign(without_turn).unwrap_err(),
+            IgnError::MissingSection
+        );
+        assert_eq!(
+            import_from_ign(&start.replace("White[1]", "White[0]")).unwrap_err(),
+            IgnError::MalformedTurn(String::from("White[0]"))
+        );
+        assert_eq!(
+            import_from_ign(&start.replace("White[1]", "Red[1]")).unwrap_err(),
+            IgnError::MalformedTurn(String::from("Red[1]"))
+        );
+    }
+}
@@ -0,0 +1,243 @@
+// Relative positions
+//
+// Text formats say where a piece ends up relative to a piece that is already on the grid, its
+// anchor, rather than with coordinates: UHP's MoveStrings, and what people type in. A glyph
+// before the anchor's name puts the piece on the anchor's left (`-wS1` W, `/wS1` SW, `\wS1` NW),
+// a glyph after it on the right (`wS1-` E, `wS1/` NE, `wS1\` SE), and no glyph at all puts it on
+// top of the anchor's stack.
+//
+// The directions are UHP's, which draws pointy-topped hexes; see uhp.rs for how they map onto our
+// flat-topped grid.
+
+use std::fmt;
+
+use crate::game::hex::{get_edge_types, Hex, HexEdge};
+use crate::game::piece::{Piece, PieceId};
+use crate::game::uhp::{id_from_uhp_name, uhp_name};
+use crate::game::Game;
+
+// (direction from the anchor, glyph, whether the glyph goes before the anchor's name)
+const GLYPHS: [(HexEdge, char, bool); 6] = [
+    (HexEdge::NE, '-', false),
+    (HexEdge::N, '/', false),
+    (HexEdge::SE, '\\', false),
+    (HexEdge::SW, '-', true),
+    (HexEdge::S, '/', true),
+    (HexEdge::NW, '\\', true),
+];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum NotationError {
+    MalformedPosition(String),
+    UnknownPiece(String),
+    AnchorNotOnGrid(String),
+    // The anchor can't be the piece being moved, since it won't be where it was
+    AnchorIsMoving(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::MalformedPosition(s) => write!(f, "malformed position `{}`", s),
+            NotationError::UnknownPiece(s) => write!(f, "unknown piece `{}`", s),
+            NotationError::AnchorNotOnGrid(s) => write!(f, "piece `{}` is not on the grid", s),
+            NotationError::AnchorIsMoving(s) => {
+                write!(f, "`{}` can't be placed relative to itself", s)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+// Where a piece ends up, relative to anchor: next to it in direction (one of the six around a
+// hex), or on top of its stack when direction is None
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RelativePosition {
+    pub anchor: PieceId,
+    pub direction: Option<HexEdge>,
+}
+
+impl RelativePosition {
+    // Reads a position like `wS1/`, `\bQ` or `wB1`
+    pub fn parse(s: &str) -> Result<RelativePosition, NotationError> {
+        let s = s.trim();
+        let malformed = || NotationError::MalformedPosition(s.to_string());
+        let first = s.chars().next().ok_or_else(malformed)?;
+        let last = s.chars().last().ok_or_else(malformed)?;
+
+        let before = GLYPHS.iter().find(|(_, g, before)| *before && *g == first);
+        let after = GLYPHS.iter().find(|(_, g, before)| !*before && *g == last);
+        let (direction, name) = match (before, after) {
+            (Some(_), Some(_)) => return Err(malformed()),
+            (Some((dir, _, _)), None) => (Some(*dir), &s[1..]),
+            (None, Some((dir, _, _))) => (Some(*dir), &s[..s.len() - 1]),
+            (None, None) => (None, s),
+        };
+        let anchor =
+            id_from_uhp_name(name).map_err(|_| NotationError::UnknownPiece(name.to_string()))?;
+        return Ok(RelativePosition { anchor, direction });
+    }
+}
+
+impl fmt::Display for RelativePosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = uhp_name(self.anchor);
+        let glyph = GLYPHS
+            .iter()
+            .find(|(dir, _, _)| Some(*dir) == self.direction);
+        match glyph {
+            Some((_, g, true)) => write!(f, "{}{}", g, name),
+            Some((_, g, false)) => write!(f, "{}{}", name, g),
+            None => write!(f, "{}", name),
+        }
+    }
+}
+
+impl Game {
+    // Describes hex relative to a piece on the grid that will still be there once moving (the
+    // piece being placed or moved, if any) has gone there: the piece it lands on if hex is up a
+    // stack, otherwise the top piece of any stack next to it. None if there's nothing to anchor
+    // to, which is only the case for the first piece of the game.
+    pub fn relative_position(&self, hex: Hex, moving: Option<PieceId>) -> Option<RelativePosition> {
+        if hex.z > 0 {
+            if let Some(below) = self.anchor_at(hex, moving) {
+                return Some(RelativePosition {
+                    anchor: below.id,
+                    direction: None,
+                });
+            }
+        }
+        for e in get_edge_types() {
+            if let Some(anchor) = self.anchor_at(hex.get_neighbor(e), moving) {
+                return Some(RelativePosition {
+                    anchor: anchor.id,
+                    // e leads from hex to the anchor, so the anchor sees hex the other way
+                    direction: Some(e.get_opposite()),
+                });
+            }
+        }
+        return None;
+    }
+
+    // The hex a relative position describes for moving, at the height it ends up at: on top of
+    // whatever is already in that cell, apart from moving itself
+    pub fn resolve_position(
+        &self,
+        position: &RelativePosition,
+        moving: Option<PieceId>,
+    ) -> Result<Hex, NotationError> {
+        if Some(position.anchor) == moving {
+            return Err(NotationError::AnchorIsMoving(uhp_name(position.anchor)));
+        }
+        let anchor = self
+            .get_piece(position.anchor)
+            .ok_or_else(|| NotationError::AnchorNotOnGrid(uhp_name(position.anchor)))?;
+        let cell = match position.direction {
+            Some(dir) => anchor.hex.get_neighbor(dir),
+            None => anchor.hex,
+        };
+        let z = self
+            .grid
+            .node_weights()
+            .filter(|p| p.hex.same_cell(cell) && Some(p.id) != moving)
+            .count() as isize;
+        return Ok(cell.with_z(z));
+    }
+
+    // The top piece in hex's cell other than moving
+    fn anchor_at(&self, hex: Hex, moving: Option<PieceId>) -> Option<&Piece> {
+        return self
+            .grid
+            .node_weights()
+            .filter(|p| p.hex.same_cell(hex) && Some(p.id) != moving)
+            .max_by_key(|p| p.hex.z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::random::{random_game, RandomGameConfig};
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for s in ["wS1-", "wS1/", "wS1\\", "-bQ", "/bQ", "\\bQ", "wB1"] {
+            let position = RelativePosition::parse(s).unwrap();
+            assert_eq!(position.to_string(), s);
+        }
+        let position = RelativePosition::parse(" /bQ ").unwrap();
+        assert_eq!(position.anchor, id_from_uhp_name("bQ").unwrap());
+        assert_eq!(position.direction, Some(HexEdge::S));
+        assert_eq!(RelativePosition::parse("wB1").unwrap().direction, None);
+    }
+
+    #[test]
+    fn parse_rejects_bad_positions() {
+        assert_eq!(
+            RelativePosition::parse(""),
+            Err(NotationError::MalformedPosition(String::new()))
+        );
+        assert_eq!(
+            RelativePosition::parse("-wS1/"),
+            Err(NotationError::MalformedPosition("-wS1/".to_string()))
+        );
+        assert_eq!(
+            RelativePosition::parse("wZ1-"),
+            Err(NotationError::UnknownPiece("wZ1".to_string()))
+        );
+    }
+
+    #[test]
+    fn every_move_resolves_back_to_its_hex() {
+        for seed in 0..10 {
+            let config = RandomGameConfig {
+                seed,
+                max_plies: 30,
+                ..RandomGameConfig::default()
+            };
+            let game = random_game(&config);
+            for m in game.get_all_moves() {
+                let hex = match m.hex() {
+                    Some(hex) => hex,
+                    None => continue,
+                };
+                let moving = m.piece_id();
+                let position = match game.relative_position(hex, moving) {
+                    Some(position) => position,
+                    None => continue,
+                };
+                assert_ne!(Some(position.anchor), moving);
+                assert_eq!(game.resolve_position(&position, moving), Ok(hex));
+                if hex.z > 0 {
+                    assert_eq!(position.direction, None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_needs_an_anchor_that_stays_put() {
+        let mut game = Game::new();
+        for move_string in ["wQ", "bQ wQ-"] {
+            let m = game.parse_move_string(move_string).unwrap();
+            game.try_make_move(m).unwrap();
+        }
+        let wq = id_from_uhp_name("wQ").unwrap();
+        let on_wq = RelativePosition::parse("wQ-").unwrap();
+        assert_eq!(
+            game.resolve_position(&on_wq, Some(wq)),
+            Err(NotationError::AnchorIsMoving("wQ".to_string()))
+        );
+        let on_ant = RelativePosition::parse("wA1-").unwrap();
+        assert_eq!(
+            game.resolve_position(&on_ant, None),
+            Err(NotationError::AnchorNotOnGrid("wA1".to_string()))
+        );
+        // The first piece has nothing to be relative to
+        assert_eq!(
+            Game::new().relative_position(Hex::from_axial(0, 0), None),
+            None
+        );
+    }
+}
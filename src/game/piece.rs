@@ -1,16 +1,27 @@
+use std::fmt;
+
 use super::hex::Hex;
 use super::piece_move::*;
-use crate::game::{Game, HexEdge};
-
-use petgraph::algo::dijkstra;
+use crate::game::Game;
 
-#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceColor {
     White,
     Black,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+impl PieceColor {
+    pub fn opponent(self) -> PieceColor {
+        match self {
+            PieceColor::White => return PieceColor::Black,
+            PieceColor::Black => return PieceColor::White,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceType {
     QueenBee,
     Ant,
@@ -22,119 +33,185 @@ pub enum PieceType {
     Mosquito,
 }
 
-#[derive(Debug, Clone)]
-pub struct Piece {
+impl PieceType {
+    // How many of this type each player has
+    pub fn count(self) -> u8 {
+        match self {
+            PieceType::Ant | PieceType::Grasshopper => return 3,
+            PieceType::Beetle | PieceType::Spider => return 2,
+            _ => return 1,
+        }
+    }
+
+    pub fn letter(self) -> char {
+        match self {
+            PieceType::QueenBee => return 'q',
+            PieceType::Ant => return 'a',
+            PieceType::Beetle => return 'b',
+            PieceType::Grasshopper => return 'g',
+            PieceType::Spider => return 's',
+            PieceType::Ladybug => return 'l',
+            PieceType::Pillbug => return 'p',
+            PieceType::Mosquito => return 'm',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'q' => return Some(PieceType::QueenBee),
+            'a' => return Some(PieceType::Ant),
+            'b' => return Some(PieceType::Beetle),
+            'g' => return Some(PieceType::Grasshopper),
+            's' => return Some(PieceType::Spider),
+            'l' => return Some(PieceType::Ladybug),
+            'p' => return Some(PieceType::Pillbug),
+            'm' => return Some(PieceType::Mosquito),
+            _ => return None,
+        }
+    }
+}
+
+// Which bug a piece is: its colour, its type and its number among that player's pieces of the same
+// type, counting from 1. Written as an id like "wa1", leaving the number off for types a player
+// only has one of ("wq").
+#[derive(Debug, Eq, PartialEq, Clone, Copy, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct PieceId {
     pub color: PieceColor,
     pub piece_type: PieceType,
-    pub id: String,
+    pub ordinal: u8,
+}
+
+impl PieceId {
+    pub fn new(color: PieceColor, piece_type: PieceType, ordinal: u8) -> Self {
+        return PieceId {
+            color,
+            piece_type,
+            ordinal,
+        };
+    }
+
+    // Reads an id such as "wq" or "ba2". The number has to be one the player actually has.
+    pub fn parse(id: &str) -> Option<Self> {
+        let mut chars = id.chars();
+        let color = match chars.next()? {
+            'w' => PieceColor::White,
+            'b' => PieceColor::Black,
+            _ => return None,
+        };
+        let piece_type = PieceType::from_letter(chars.next()?)?;
+        let number = chars.as_str();
+        let ordinal = if piece_type.count() == 1 && number.is_empty() {
+            1
+        } else if piece_type.count() > 1 && number.len() == 1 {
+            number.parse::<u8>().ok()?
+        } else {
+            return None;
+        };
+        if ordinal == 0 || ordinal > piece_type.count() {
+            return None;
+        }
+        return Some(PieceId::new(color, piece_type, ordinal));
+    }
+}
+
+impl fmt::Display for PieceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let color = match self.color {
+            PieceColor::White => 'w',
+            PieceColor::Black => 'b',
+        };
+        write!(f, "{}{}", color, self.piece_type.letter())?;
+        if self.piece_type.count() > 1 {
+            write!(f, "{}", self.ordinal)?;
+        }
+        return Ok(());
+    }
+}
+
+impl From<PieceId> for String {
+    fn from(id: PieceId) -> Self {
+        return id.to_string();
+    }
+}
+
+impl TryFrom<String> for PieceId {
+    type Error = String;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        return PieceId::parse(&id).ok_or_else(|| format!("unknown piece `{}`", id));
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        into = "crate::game::serialize::PieceRepr",
+        try_from = "crate::game::serialize::PieceRepr"
+    )
+)]
+pub struct Piece {
+    pub id: PieceId,
     pub hex: Hex,
     pub in_hand: bool,
 }
 
+// Pieces are the same piece when they're the same bug, wherever they are
 impl PartialEq for Piece {
     fn eq(&self, other: &Self) -> bool {
-        return self.color == other.color && self.piece_type == other.piece_type;
+        return self.id == other.id;
     }
 }
 
-impl Piece {
-    pub fn new(color: PieceColor, piece_type: PieceType, name_suffix: &str) -> Self {
-        let mut color_prefix = String::from("b");
-        if &color == &PieceColor::White {
-            color_prefix = String::from("w");
-        }
-
+impl From<PieceId> for Piece {
+    fn from(id: PieceId) -> Self {
         return Piece {
-            color,
+            id,
             hex: Hex::new(),
-            piece_type,
-            id: color_prefix.clone() + name_suffix,
             in_hand: true,
         };
     }
+}
+
+impl Piece {
+    pub fn new(color: PieceColor, piece_type: PieceType, ordinal: u8) -> Self {
+        return Piece::from(PieceId::new(color, piece_type, ordinal));
+    }
+
+    // Builds an in-hand piece from an id such as "wq" or "ba2"
+    pub fn from_id(id: &str) -> Option<Self> {
+        return PieceId::parse(id).map(Piece::from);
+    }
 
     pub fn can_move(&self, game: &Game) -> bool {
-        // Dijkstra's algo to check if graph is fully connected
-        if let Some(piece_node) = game
-            .grid
-            .node_indices()
-            .find(|&n| game.grid[n].id == self.id)
-        {
-            // Remove the node, and compare number of nodes to number of paths
-            let mut grid_clone = game.grid.clone();
-            if let Some(_) = grid_clone.remove_node(piece_node) {
-                let paths = dijkstra(
-                    &grid_clone,
-                    grid_clone
-                        .node_indices()
-                        .next()
-                        .expect("No items in the graph"),
-                    None,
-                    |_| 1,
-                );
-                return paths.len() == grid_clone.node_count();
-                // grid_clone is dropped here
-            }
+        // Only the top piece of a stack can move
+        if game.top_piece_at(self.hex).map(|p| p.id) != Some(self.id) {
+            return false;
+        }
+        // Climbing off a stack leaves the piece underneath behind to hold the hive together
+        if self.hex.z > 0 {
+            return true;
         }
 
-        // TODO: Check for previous PillBug move
-        return false;
+        return game.is_one_hive_without(Some(self.id));
     }
 
     pub fn get_moves(&self, game: &Game) -> Vec<PieceMove> {
-        let mut valid_moves: Vec<PieceMove> = Vec::new();
-        if let Some(curr_piece_node) = game
-            .grid
-            .node_indices()
-            .find(|&n| game.grid[n].id == self.id)
-        {
-            match self.piece_type {
-                PieceType::QueenBee => {
-                    valid_moves.extend(get_queen_moves(self, curr_piece_node, game));
-                }
-                PieceType::Ant => {
-                    valid_moves.extend(get_ant_moves(self, curr_piece_node, game));
-                }
-                PieceType::Beetle => {
-                    valid_moves.extend(get_beetle_moves(self, curr_piece_node, game));
-                }
-                PieceType::Grasshopper => {
-                    valid_moves.extend(get_grasshopper_moves(self, curr_piece_node, game));
-                }
-                PieceType::Spider => {
-                    valid_moves.extend(get_spider_moves(self, curr_piece_node, game));
-                }
-                PieceType::Ladybug => {
-                    valid_moves.extend(get_ladybug_moves(self, curr_piece_node, game));
-                }
-                PieceType::Pillbug => {
-                    valid_moves.extend(get_pillbug_moves(self, curr_piece_node, game));
-                }
-                PieceType::Mosquito => {
-                    valid_moves.extend(get_mosquito_moves(self, curr_piece_node, game));
-                }
-            }
+        match self.id.piece_type {
+            PieceType::QueenBee => return get_queen_moves(self, game),
+            PieceType::Ant => return get_ant_moves(self, game),
+            PieceType::Beetle => return get_beetle_moves(self, game),
+            PieceType::Grasshopper => return get_grasshopper_moves(self, game),
+            PieceType::Spider => return get_spider_moves(self, game),
+            PieceType::Ladybug => return get_ladybug_moves(self, game),
+            PieceType::Pillbug => return get_pillbug_moves(self, game),
+            PieceType::Mosquito => return get_mosquito_moves(self, game),
         }
-        return valid_moves;
     }
 }
-
-pub fn can_slide(hex: Hex, dir: HexEdge, game: &Game) -> bool {
-    // No piece alrady there:
-    if let Some(_) = game
-        .grid
-        .node_weights()
-        .find(|&p| p.hex == hex.get_neighbor(dir))
-    {
-        return false;
-    }
-
-    // No gate blocking slide
-    let gate_blockers = dir.get_gate_edges().map(|e| hex.get_neighbor(e));
-    return game
-        .grid
-        .node_weights()
-        .filter(|&p| gate_blockers.contains(&p.hex))
-        .count()
-        < 2;
-}
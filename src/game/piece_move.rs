@@ -1,58 +1,148 @@
+use std::fmt;
+
 use crate::game::*;
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct PieceMove {
-    pub piece_node: NodeIndex,
-    pub hex: Hex,
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum PieceMove {
+    // Put a piece from the current player's hand onto the grid
+    Place {
+        piece_id: PieceId,
+        hex: Hex,
+    },
+    // Move a piece that is already on the grid (possibly by way of a pillbug) from one hex to
+    // another. Keeping the origin lets a move be undone or written out after it's been made.
+    Move {
+        piece_id: PieceId,
+        from: Hex,
+        hex: Hex,
+    },
+    Pass,
+}
+
+impl PieceMove {
+    pub fn piece_id(&self) -> Option<PieceId> {
+        match self {
+            PieceMove::Place { piece_id, .. } | PieceMove::Move { piece_id, .. } => {
+                return Some(*piece_id)
+            }
+            PieceMove::Pass => return None,
+        }
+    }
+
+    pub fn hex(&self) -> Option<Hex> {
+        match self {
+            PieceMove::Place { hex, .. } | PieceMove::Move { hex, .. } => return Some(*hex),
+            PieceMove::Pass => return None,
+        }
+    }
+}
+
+// Why a move can't be made (see Game::try_make_move)
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MoveError {
+    GameOver,
+    NotYourTurn(PieceId),
+    PieceNotInHand(PieceId),
+    // The piece's expansion isn't part of this game type
+    PieceNotInGame(PieceId),
+    // Pieces of a type are placed lowest number first
+    NotLowestNumbered {
+        piece_id: PieceId,
+        expected: PieceId,
+    },
+    // The queen has to be placed by each player's Rules::queen_deadline turn
+    QueenMustBePlaced,
+    // Tournament openings don't allow the queen on a player's first turn
+    QueenNotAllowed,
+    // Pieces can't move until their queen is on the grid
+    QueenNotPlaced,
+    PieceNotOnGrid(PieceId),
+    WrongOrigin {
+        piece_id: PieceId,
+        from: Hex,
+    },
+    PieceCovered(PieceId),
+    PieceJustMoved(PieceId),
+    PiecePinned(PieceId),
+    GateBlocked(PieceId),
+    IllegalPlacement(PieceId),
+    IllegalDestination(PieceId),
+    // Passing is only allowed when there's nothing else to do
+    CannotPass,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::GameOver => write!(f, "the game is over"),
+            MoveError::NotYourTurn(id) => write!(f, "`{}` belongs to the other player", id),
+            MoveError::PieceNotInHand(id) => write!(f, "`{}` is not in hand", id),
+            MoveError::PieceNotInGame(id) => write!(f, "`{}` is not in this game type", id),
+            MoveError::NotLowestNumbered { piece_id, expected } => {
+                write!(f, "`{}` has to be placed before `{}`", expected, piece_id)
+            }
+            MoveError::QueenMustBePlaced => write!(f, "the queen has to be placed this turn"),
+            MoveError::QueenNotAllowed => {
+                write!(f, "the queen can't be placed on the first turn")
+            }
+            MoveError::QueenNotPlaced => {
+                write!(f, "pieces can't move until the queen is placed")
+            }
+            MoveError::PieceNotOnGrid(id) => write!(f, "`{}` is not on the grid", id),
+            MoveError::WrongOrigin { piece_id, from } => {
+                write!(f, "`{}` is not at {:?}", piece_id, from)
+            }
+            MoveError::PieceCovered(id) => write!(f, "`{}` is covered by another piece", id),
+            MoveError::PieceJustMoved(id) => write!(f, "`{}` was moved last turn", id),
+            MoveError::PiecePinned(id) => {
+                write!(f, "moving `{}` would split the hive", id)
+            }
+            MoveError::GateBlocked(id) => {
+                write!(f, "`{}` can't squeeze through the gap", id)
+            }
+            MoveError::IllegalPlacement(id) => write!(f, "`{}` can't be placed there", id),
+            MoveError::IllegalDestination(id) => write!(f, "`{}` can't move there", id),
+            MoveError::CannotPass => write!(f, "passing is only allowed with no other move"),
+        }
+    }
 }
 
-pub fn get_queen_moves(queen: &Piece, queen_node: NodeIndex, game: &Game) -> Vec<PieceMove> {
+pub fn get_queen_moves(queen: &Piece, game: &Game) -> Vec<PieceMove> {
     let mut valid_moves: Vec<PieceMove> = Vec::new();
-    if queen.can_move(&game) {
-        let queen_neighbor_edges = get_edge_types();
-        for e in queen_neighbor_edges {
-            if can_slide(queen.hex, e, game)
-                && piece_is_connected(queen.hex.get_neighbor(e), game, &queen.id)
-            {
-                valid_moves.push(PieceMove {
-                    piece_node: queen_node,
-                    hex: queen.hex.get_neighbor(e),
-                })
+    if queen.can_move(game) {
+        for e in get_edge_types() {
+            let dest = queen.hex.get_neighbor(e);
+            if stack_height(dest, game, queen.id) == 0 && can_step(queen.hex, e, game, queen.id) {
+                push_move(&mut valid_moves, queen, ground(dest));
             }
         }
     }
     return valid_moves;
 }
 
-pub fn get_ant_moves(ant: &Piece, ant_node: NodeIndex, game: &Game) -> Vec<PieceMove> {
+pub fn get_ant_moves(ant: &Piece, game: &Game) -> Vec<PieceMove> {
     let mut valid_moves: Vec<PieceMove> = Vec::new();
     // Confirm Ant is not pinned:
-    if ant.can_move(&game) {
-        // BFS using a queue to determine all the hexes the ant can move to:
-        let mut hexes_to_check: Vec<Hex> = ant.hex.get_neighbors();
-        hexes_to_check.retain(|&n| {
-            game.grid.node_weights().find(|p| p.hex == n).is_none()
-                && piece_is_connected(n, game, &ant.id)
-        });
+    if ant.can_move(game) {
+        // BFS over every empty hex the ant can slide to:
+        let mut checked_hexes: Vec<Hex> = vec![ant.hex];
+        let mut hexes_to_check: Vec<Hex> = vec![ant.hex];
 
-        while !hexes_to_check.is_empty() {
-            // If game doesn't have piece at h, add to valid_moves,
-            if let Some(h) = hexes_to_check.pop() {
-                if game.grid.node_weights().find(|&p| p.hex == h).is_none()
-                    && valid_moves.iter().find(|&m| m.hex == h).is_none()
+        while let Some(h) = hexes_to_check.pop() {
+            for e in get_edge_types() {
+                let n = ground(h.get_neighbor(e));
+                if !checked_hexes.contains(&n)
+                    && stack_height(n, game, ant.id) == 0
+                    && can_step(h, e, game, ant.id)
                 {
-                    valid_moves.push(PieceMove {
-                        piece_node: ant_node,
-                        hex: h,
-                    });
-                    // Check all neighbors for h as well.
-                    let mut h_neighbors = h.get_neighbors();
-                    h_neighbors.retain(|&n| {
-                        !game.grid.node_weights().find(|p| p.hex == n).is_some()
-                            && !valid_moves.iter().find(|m| m.hex == n).is_some()
-                            && piece_is_connected(n, game, &ant.id)
-                    });
-                    hexes_to_check.extend(h_neighbors);
+                    checked_hexes.push(n);
+                    hexes_to_check.push(n);
+                    push_move(&mut valid_moves, ant, n);
                 }
             }
         }
@@ -60,303 +150,161 @@ pub fn get_ant_moves(ant: &Piece, ant_node: NodeIndex, game: &Game) -> Vec<Piece
     return valid_moves;
 }
 
-pub fn get_beetle_moves(beetle: &Piece, beetle_node: NodeIndex, game: &Game) -> Vec<PieceMove> {
+pub fn get_beetle_moves(beetle: &Piece, game: &Game) -> Vec<PieceMove> {
     let mut valid_moves: Vec<PieceMove> = Vec::new();
-    if beetle.can_move(&game) {
-        let beetle_neighbor_edges = get_edge_types();
-        for e in beetle_neighbor_edges {
-            if can_slide(beetle.hex, e, game)
-                && piece_is_connected(beetle.hex.get_neighbor(e), game, &beetle.id)
-            {
-                valid_moves.push(PieceMove {
-                    piece_node: beetle_node,
-                    hex: beetle.hex.get_neighbor(e),
-                })
-            } else if let Some(bottom_piece) = game
-                .grid
-                .node_weights()
-                .find(|&piece| piece.hex == beetle.hex.get_neighbor(e))
-            {
-                // Add move to place beetle on top!
-                valid_moves.push(PieceMove {
-                    piece_node: beetle_node,
-                    hex: Hex {
-                        q: bottom_piece.hex.q,
-                        r: bottom_piece.hex.r,
-                        s: bottom_piece.hex.s,
-                        z: bottom_piece.hex.z + 1,
-                    },
-                })
+    if beetle.can_move(game) {
+        for e in get_edge_types() {
+            let dest = beetle.hex.get_neighbor(e);
+            if can_step(beetle.hex, e, game, beetle.id) {
+                // Either slide along the ground or climb on top of whatever is there
+                let z = stack_height(dest, game, beetle.id);
+                push_move(&mut valid_moves, beetle, dest.with_z(z));
             }
         }
     }
     return valid_moves;
 }
 
-pub fn get_grasshopper_moves(
-    grasshopper: &Piece,
-    grasshopper_node: NodeIndex,
-    game: &Game,
-) -> Vec<PieceMove> {
+pub fn get_grasshopper_moves(grasshopper: &Piece, game: &Game) -> Vec<PieceMove> {
     let mut valid_moves: Vec<PieceMove> = Vec::new();
-    if grasshopper.can_move(&game) {
-        let jump_dirs = get_edge_types();
-        for dir in jump_dirs {
+    if grasshopper.can_move(game) {
+        for dir in get_edge_types() {
             let mut jumps: usize = 0;
-            let mut jump_hex = grasshopper.hex.clone();
-            while let Some(curr_piece) = game
-                .grid
-                .node_weights()
-                .find(|&piece| piece.hex == jump_hex.get_neighbor(dir))
-            {
+            let mut jump_hex = ground(grasshopper.hex.get_neighbor(dir));
+            while stack_height(jump_hex, game, grasshopper.id) > 0 {
                 jumps += 1;
-                jump_hex = curr_piece.hex.get_neighbor(dir);
+                jump_hex = jump_hex.get_neighbor(dir);
             }
 
-            // Add jump now (if we've done at least 1 move)
+            // Add jump now (if we've jumped over at least 1 piece)
             if jumps > 0 {
-                valid_moves.push(PieceMove {
-                    piece_node: grasshopper_node,
-                    hex: jump_hex,
-                });
+                push_move(&mut valid_moves, grasshopper, jump_hex);
             }
         }
     }
     return valid_moves;
 }
 
-pub fn get_spider_moves(spider: &Piece, spider_node: NodeIndex, game: &Game) -> Vec<PieceMove> {
+pub fn get_spider_moves(spider: &Piece, game: &Game) -> Vec<PieceMove> {
     let spider_move_distance = 3;
     let mut valid_moves: Vec<PieceMove> = Vec::new();
-    if spider.can_move(&game) {
-        // BFS using a queue to determine all the hexes the spider can move to:
-        let mut hexes_to_check: Vec<Hex> = spider.hex.get_neighbors();
-        let mut checked_hexes: Vec<Hex> = Vec::new();
-        hexes_to_check.retain(|&n| {
-            game.grid.node_weights().find(|p| p.hex == n).is_none() // No piece at n
-                && piece_is_connected(n, game, &spider.id)
-        });
-
-        while !hexes_to_check.is_empty() {
-            // If game doesn't have piece at h, add to valid_moves,
-            if let Some(h) = hexes_to_check.pop() {
-                if game.grid.node_weights().find(|&p| p.hex == h).is_none() // No piece at h
-                // Haven't checked this
-                // hex yet
-                    && checked_hexes.iter().find(|&prev_h| prev_h == &h).is_none()
+    if spider.can_move(game) {
+        // DFS over every path of exactly three slides that doesn't revisit a hex
+        let mut paths: Vec<Vec<Hex>> = vec![vec![spider.hex]];
+        while let Some(path) = paths.pop() {
+            let h = path[path.len() - 1];
+            if path.len() == spider_move_distance + 1 {
+                push_move(&mut valid_moves, spider, h);
+                continue;
+            }
+            for e in get_edge_types() {
+                let n = ground(h.get_neighbor(e));
+                if !path.contains(&n)
+                    && stack_height(n, game, spider.id) == 0
+                    && can_step(h, e, game, spider.id)
                 {
-                    if game.slide_distance(spider.hex, h) == spider_move_distance {
-                        valid_moves.push(PieceMove {
-                            piece_node: spider_node,
-                            hex: h,
-                        });
-                    } else {
-                        // Check all neighbors for h as well.
-                        let mut h_neighbors = h.get_neighbors();
-                        h_neighbors.retain(|&n| {
-                            game.grid.node_weights().find(|p| p.hex == n).is_none() // n is open
-                                && valid_moves.iter().find(|m| m.hex == n).is_none()
-                                && checked_hexes.iter().find(|&prev_h| prev_h == &n).is_none() // haven't
-                                                                                                // checked n yet
-                                && piece_is_connected(n, game, &spider.id) // n is connected to some
-                                                                           // other piece in the grid
-                        });
-                        hexes_to_check.extend(h_neighbors);
-                    }
+                    let mut next_path = path.clone();
+                    next_path.push(n);
+                    paths.push(next_path);
                 }
-                // Add hex to checked hexes so we don't check it again
-                checked_hexes.push(h);
             }
         }
     }
     return valid_moves;
 }
 
-pub fn get_ladybug_moves(ladybug: &Piece, ladybug_node: NodeIndex, game: &Game) -> Vec<PieceMove> {
-    let lb_top_distance = 2;
+pub fn get_ladybug_moves(ladybug: &Piece, game: &Game) -> Vec<PieceMove> {
     let mut valid_moves: Vec<PieceMove> = Vec::new();
-    if ladybug.can_move(&game) {
-        // BFS using a queue to determine all the hexes the spider can move to:
-        let mut hexes_to_check: Vec<Hex> = ladybug.hex.get_neighbors();
-        let mut final_top_hexes: Vec<Hex> = Vec::new();
-        let mut top_distance: usize = 1;
-        hexes_to_check.retain(|&n| {
-            game.grid.node_weights().find(|p| p.hex == n).is_some() // Piece at n
-                && piece_is_connected(n, game, &ladybug.id)
-        });
-
-        // Across the top of the hive:
-        while !hexes_to_check.is_empty() {
-            // If game has piece at h, check distance and add to final_top_hexes + checked_hexes,
-            if let Some(h) = hexes_to_check.pop() {
-                if game.grid.node_weights().find(|&p| p.hex == h).is_some()
-                // Piece at h
-                // Haven't checked this
-                // hex yet
-                    && final_top_hexes.iter().find(|&prev_h| prev_h == &h).is_none()
+    if ladybug.can_move(game) {
+        // Two steps across the top of the hive, then one step down
+        for up in get_edge_types() {
+            let first = ground(ladybug.hex.get_neighbor(up));
+            if stack_height(first, game, ladybug.id) == 0
+                || !can_step(ladybug.hex, up, game, ladybug.id)
+            {
+                continue;
+            }
+            for across in get_edge_types() {
+                let second = ground(first.get_neighbor(across));
+                if stack_height(second, game, ladybug.id) == 0
+                    || !can_step(first, across, game, ladybug.id)
                 {
-                    if top_distance == lb_top_distance {
-                        final_top_hexes.push(h);
-                    } else {
-                        // Check all neighbors for h as well.
-                        let mut h_neighbors = h.get_neighbors();
-                        h_neighbors.retain(|&n| {
-                            game.grid.node_weights().find(|p| p.hex == n).is_some()
-                            // n has a top
-                        });
-                        hexes_to_check.extend(h_neighbors);
-                        // Increase distance on top by 1
-                        top_distance += 1;
+                    continue;
+                }
+                for down in get_edge_types() {
+                    let dest = ground(second.get_neighbor(down));
+                    if dest != ground(ladybug.hex)
+                        && stack_height(dest, game, ladybug.id) == 0
+                        && can_step(second, down, game, ladybug.id)
+                    {
+                        push_move(&mut valid_moves, ladybug, dest);
                     }
                 }
             }
         }
-
-        // Finally, which steps down are available
-        while let Some(top_h) = final_top_hexes.pop() {
-            let mut h_neighbors = top_h.get_neighbors();
-            h_neighbors.retain(|&n| {
-                game.grid.node_weights().find(|p| p.hex == n).is_none() // no piece at n
-                && valid_moves.iter().find(|&m| m.hex == n).is_none()
-            });
-            valid_moves.extend(h_neighbors.iter().map(|&hn| PieceMove {
-                piece_node: ladybug_node,
-                hex: hn,
-            }));
-        }
     }
     return valid_moves;
 }
 
-pub fn get_pillbug_moves(pillbug: &Piece, pillbug_node: NodeIndex, game: &Game) -> Vec<PieceMove> {
+pub fn get_pillbug_moves(pillbug: &Piece, game: &Game) -> Vec<PieceMove> {
     // Start with queen-type moves
-    let mut valid_moves: Vec<PieceMove> = get_queen_moves(pillbug, pillbug_node, game);
+    let mut valid_moves: Vec<PieceMove> = get_queen_moves(pillbug, game);
 
-    // If space around pillbug, add all mobile pieces to nearby spaces:
-    let mut empty_neighbors = pillbug.hex.get_neighbors();
-    empty_neighbors.retain(|&n| game.grid.node_weights().find(|&p| p.hex == n).is_none());
-
-    let mut mobile_pieces_hexes = pillbug.hex.get_neighbors();
-    mobile_pieces_hexes.retain(|&n| {
-        game.grid
-            .node_weights()
-            .find(|&p| p.hex == n)
-            .is_some_and(|p| p.can_move(game))
-    });
+    // A pillbug that was just moved can't use its ability this turn
+    if game.last_moved_piece() == Some(pillbug.id) {
+        return valid_moves;
+    }
 
-    while let Some(open_space) = empty_neighbors.pop() {
-        for mph in &mobile_pieces_hexes {
-            valid_moves.push(PieceMove {
-                piece_node: game
-                    .grid
-                    .node_indices()
-                    .find(|&p| game.grid[p].hex == *mph)
-                    .expect("Unable to unwrap piece_node"),
-                hex: open_space,
-            });
+    // Lift an adjacent piece over the pillbug and drop it on an empty neighbouring hex
+    for from_edge in get_edge_types() {
+        let from = pillbug.hex.get_neighbor(from_edge);
+        let target = match game.top_piece_at(from) {
+            Some(p) => p,
+            None => continue,
+        };
+        if game.stack_height(from) != 1
+            || game.last_moved_piece() == Some(target.id)
+            || !target.can_move(game)
+            || !can_step(from, from_edge.get_opposite(), game, target.id)
+        {
+            continue;
+        }
+        for to_edge in get_edge_types() {
+            let to = pillbug.hex.get_neighbor(to_edge);
+            if to_edge != from_edge
+                && stack_height(to, game, target.id) == 0
+                && can_step(pillbug.hex, to_edge, game, target.id)
+            {
+                push_move(&mut valid_moves, target, ground(to));
+            }
         }
     }
 
     return valid_moves;
 }
 
-pub fn get_mosquito_moves(
-    mosquito: &Piece,
-    mosquito_node: NodeIndex,
-    game: &Game,
-) -> Vec<PieceMove> {
-    let mut valid_moves: Vec<PieceMove> = Vec::new();
-    let neighbor_hexes = mosquito.hex.get_neighbors();
+pub fn get_mosquito_moves(mosquito: &Piece, game: &Game) -> Vec<PieceMove> {
+    // A mosquito on top of the hive can only move like a beetle
+    if mosquito.hex.z > 0 {
+        return get_beetle_moves(mosquito, game);
+    }
 
-    for hex in neighbor_hexes {
-        if let Some(neighbor_piece) = game.grid.node_weights().find(|&p| p.hex == hex) {
-            match neighbor_piece.piece_type {
-                PieceType::QueenBee => {
-                    let possible_moves = get_queen_moves(mosquito, mosquito_node, game);
-                    for m in possible_moves {
-                        if valid_moves
-                            .iter()
-                            .find(|&found_move| found_move == &m)
-                            .is_none()
-                        {
-                            valid_moves.push(m);
-                        }
-                    }
-                }
-                PieceType::Ant => {
-                    let possible_moves = get_ant_moves(mosquito, mosquito_node, game);
-                    for m in possible_moves {
-                        if valid_moves
-                            .iter()
-                            .find(|&found_move| found_move == &m)
-                            .is_none()
-                        {
-                            valid_moves.push(m);
-                        }
-                    }
-                }
-                PieceType::Beetle => {
-                    let possible_moves = get_beetle_moves(mosquito, mosquito_node, game);
-                    for m in possible_moves {
-                        if valid_moves
-                            .iter()
-                            .find(|&found_move| found_move == &m)
-                            .is_none()
-                        {
-                            valid_moves.push(m);
-                        }
-                    }
-                }
-                PieceType::Grasshopper => {
-                    let possible_moves = get_grasshopper_moves(mosquito, mosquito_node, game);
-                    for m in possible_moves {
-                        if valid_moves
-                            .iter()
-                            .find(|&found_move| found_move == &m)
-                            .is_none()
-                        {
-                            valid_moves.push(m);
-                        }
-                    }
-                }
-                PieceType::Spider => {
-                    let possible_moves = get_spider_moves(mosquito, mosquito_node, game);
-                    for m in possible_moves {
-                        if valid_moves
-                            .iter()
-                            .find(|&found_move| found_move == &m)
-                            .is_none()
-                        {
-                            valid_moves.push(m);
-                        }
-                    }
-                }
-                PieceType::Ladybug => {
-                    let possible_moves = get_ladybug_moves(mosquito, mosquito_node, game);
-                    for m in possible_moves {
-                        if valid_moves
-                            .iter()
-                            .find(|&found_move| found_move == &m)
-                            .is_none()
-                        {
-                            valid_moves.push(m);
-                        }
-                    }
-                }
-                PieceType::Pillbug => {
-                    let possible_moves = get_pillbug_moves(mosquito, mosquito_node, game);
-                    for m in possible_moves {
-                        if valid_moves
-                            .iter()
-                            .find(|&found_move| found_move == &m)
-                            .is_none()
-                        {
-                            valid_moves.push(m);
-                        }
-                    }
-                }
-                PieceType::Mosquito => {
-                    valid_moves.extend([]);
+    let mut valid_moves: Vec<PieceMove> = Vec::new();
+    for hex in mosquito.hex.get_neighbors() {
+        if let Some(neighbor_piece) = game.top_piece_at(hex) {
+            let possible_moves = match neighbor_piece.id.piece_type {
+                PieceType::QueenBee => get_queen_moves(mosquito, game),
+                PieceType::Ant => get_ant_moves(mosquito, game),
+                PieceType::Beetle => get_beetle_moves(mosquito, game),
+                PieceType::Grasshopper => get_grasshopper_moves(mosquito, game),
+                PieceType::Spider => get_spider_moves(mosquito, game),
+                PieceType::Ladybug => get_ladybug_moves(mosquito, game),
+                PieceType::Pillbug => get_pillbug_moves(mosquito, game),
+                PieceType::Mosquito => Vec::new(),
+            };
+            for m in possible_moves {
+                if !valid_moves.contains(&m) {
+                    valid_moves.push(m);
                 }
             }
         }
@@ -364,22 +312,56 @@ pub fn get_mosquito_moves(
     return valid_moves;
 }
 
-pub fn can_slide(hex: Hex, dir: HexEdge, game: &Game) -> bool {
-    // No piece alrady there:
-    if let Some(_) = game
-        .grid
-        .node_weights()
-        .find(|&p| p.hex == hex.get_neighbor(dir))
-    {
+// Whether a piece at `hex` can take one step in `dir`, ignoring the moving piece itself.
+// Works at any height: the step is blocked when both gate hexes are stacked higher than the
+// start and end of the step. On the ground the piece must also keep touching the hive, so exactly
+// one of the two gate hexes has to be occupied.
+pub fn can_step(hex: Hex, dir: HexEdge, game: &Game, moving_id: PieceId) -> bool {
+    let from_height = stack_height(hex, game, moving_id);
+    let to_height = stack_height(hex.get_neighbor(dir), game, moving_id);
+    let [left, right] = dir
+        .get_gate_edges()
+        .map(|e| stack_height(hex.get_neighbor(e), game, moving_id));
+
+    if left.min(right) > from_height.max(to_height) {
         return false;
     }
+    if from_height == 0 && to_height == 0 {
+        return left.max(right) > 0;
+    }
+    return true;
+}
+
+// Whether the two hexes either side of a step are both stacked higher than its start and end
+pub fn gate_blocked(hex: Hex, dir: HexEdge, game: &Game, moving_id: PieceId) -> bool {
+    let from_height = stack_height(hex, game, moving_id);
+    let to_height = stack_height(hex.get_neighbor(dir), game, moving_id);
+    let [left, right] = dir
+        .get_gate_edges()
+        .map(|e| stack_height(hex.get_neighbor(e), game, moving_id));
+    return left.min(right) > from_height.max(to_height);
+}
 
-    // No gate blocking slide
-    let gate_blockers = dir.get_gate_edges().map(|e| hex.get_neighbor(e));
+// Number of pieces on hex's cell, not counting the piece that is moving
+fn stack_height(hex: Hex, game: &Game, moving_id: PieceId) -> isize {
     return game
         .grid
         .node_weights()
-        .filter(|&p| gate_blockers.contains(&p.hex))
-        .count()
-        < 2;
+        .filter(|p| p.hex.same_cell(hex) && p.id != moving_id)
+        .count() as isize;
+}
+
+fn ground(hex: Hex) -> Hex {
+    return hex.with_z(0);
+}
+
+fn push_move(valid_moves: &mut Vec<PieceMove>, piece: &Piece, hex: Hex) {
+    let m = PieceMove::Move {
+        piece_id: piece.id,
+        from: piece.hex,
+        hex,
+    };
+    if !valid_moves.contains(&m) {
+        valid_moves.push(m);
+    }
 }
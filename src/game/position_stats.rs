@@ -0,0 +1,216 @@
+// Position statistics
+//
+// Per-player counts for a position that evaluators, the CLI's analyze command and data pipelines
+// all want: how many pieces can move and how many are pinned, how many moves each type of piece
+// has, and what is covered, in hand or frozen by a pillbug. Moves are counted for each player's
+// next turn, as in Game::next_turn_moves.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::game::hand::HAND_ORDER;
+use crate::game::piece::{PieceColor, PieceId, PieceType};
+use crate::game::piece_move::PieceMove;
+use crate::game::Game;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionStats {
+    pub white: SideStats,
+    pub black: SideStats,
+}
+
+impl PositionStats {
+    pub fn side(&self, color: PieceColor) -> &SideStats {
+        match color {
+            PieceColor::White => return &self.white,
+            PieceColor::Black => return &self.black,
+        }
+    }
+}
+
+impl fmt::Display for PositionStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.white)?;
+        write!(f, "{}", self.black)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SideStats {
+    pub color: PieceColor,
+    // Pieces with at least one move next turn
+    pub mobile: usize,
+    // Pieces on top of their stacks that can't move without splitting the hive
+    pub pinned: usize,
+    // Moves of the player's own pieces next turn, by the type of piece moving
+    pub moves_by_type: BTreeMap<PieceType, usize>,
+    pub placements: usize,
+    // Moves of the other player's pieces, made with a pillbug (or a mosquito next to one)
+    pub pillbug_moves: usize,
+    // Pieces with another piece on top of them
+    pub covered: usize,
+    pub in_hand: usize,
+    // The piece the other player moved with a pillbug last turn, which can't move this turn
+    pub frozen: Option<PieceId>,
+}
+
+impl SideStats {
+    // Every move the player has next turn
+    pub fn total_moves(&self) -> usize {
+        return self.moves_by_type.values().sum::<usize>() + self.placements + self.pillbug_moves;
+    }
+}
+
+impl fmt::Display for SideStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:?}: {} moves ({} placements, {} with the other player's pieces)",
+            self.color,
+            self.total_moves(),
+            self.placements,
+            self.pillbug_moves
+        )?;
+        let by_type: Vec<String> = self
+            .moves_by_type
+            .iter()
+            .map(|(piece_type, count)| format!("{}{}", piece_type.letter(), count))
+            .collect();
+        writeln!(f, "  moves by piece: {}", by_type.join(" "))?;
+        write!(
+            f,
+            "  mobile {}, pinned {}, covered {}, in hand {}",
+            self.mobile, self.pinned, self.covered, self.in_hand
+        )?;
+        if let Some(frozen) = self.frozen {
+            write!(f, ", frozen {}", frozen)?;
+        }
+        return Ok(());
+    }
+}
+
+impl Game {
+    pub fn position_stats(&self) -> PositionStats {
+        return PositionStats {
+            white: self.side_stats(PieceColor::White),
+            black: self.side_stats(PieceColor::Black),
+        };
+    }
+
+    fn side_stats(&self, color: PieceColor) -> SideStats {
+        let moves = self.next_turn_moves(color);
+        let mut moves_by_type: BTreeMap<PieceType, usize> = BTreeMap::new();
+        for piece_type in HAND_ORDER {
+            if self.game_type.includes(piece_type) {
+                moves_by_type.insert(piece_type, 0);
+            }
+        }
+        let mut mobile: Vec<PieceId> = Vec::new();
+        let (mut placements, mut pillbug_moves) = (0, 0);
+        for m in &moves {
+            match m {
+                PieceMove::Place { .. } => placements += 1,
+                PieceMove::Move { piece_id, .. } if piece_id.color == color => {
+                    *moves_by_type.entry(piece_id.piece_type).or_default() += 1;
+                    if !mobile.contains(piece_id) {
+                        mobile.push(*piece_id);
+                    }
+                }
+                PieceMove::Move { .. } => pillbug_moves += 1,
+                PieceMove::Pass => {}
+            }
+        }
+
+        let covered = self
+            .grid
+            .node_weights()
+            .filter(|p| p.id.color == color && self.top_piece_at(p.hex).map(|t| t.id) != Some(p.id))
+            .count();
+        // A piece moved on the other player's turn that isn't theirs was moved with a pillbug
+        let frozen = self
+            .last_moved_piece()
+            .filter(|id| id.color == color && self.current_player() == color);
+
+        return SideStats {
+            color,
+            mobile: mobile.len(),
+            pinned: self.pinned_pieces(color).len(),
+            moves_by_type,
+            placements,
+            pillbug_moves,
+            covered,
+            in_hand: self.hand(color).len(),
+            frozen,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_type::GameType;
+    use crate::game::random::{random_game, RandomGameConfig};
+    use crate::game::GameState;
+
+    fn play(moves: &[&str]) -> Game {
+        let mut game = Game::with_type(GameType::BASE);
+        for move_string in moves {
+            let m = game.parse_move_string(move_string).unwrap();
+            game.try_make_move(m).unwrap();
+        }
+        return game;
+    }
+
+    #[test]
+    fn counts_every_move_next_turn() {
+        for seed in 0..10 {
+            let config = RandomGameConfig {
+                seed,
+                max_plies: 8 + seed as usize * 3,
+                ..RandomGameConfig::default()
+            };
+            let game = random_game(&config);
+            if game.get_state() != GameState::InProgress {
+                continue;
+            }
+            let stats = game.position_stats();
+            for color in [PieceColor::White, PieceColor::Black] {
+                let side = stats.side(color);
+                assert_eq!(side.color, color);
+                assert_eq!(side.total_moves(), game.next_turn_moves(color).len());
+                assert_eq!(side.in_hand, game.hand(color).len());
+                assert_eq!(side.pinned, game.pinned_pieces(color).len());
+            }
+        }
+    }
+
+    #[test]
+    fn line_of_four() {
+        let game = play(&["wQ", "bQ wQ-", "wA1 -wQ", "bA1 bQ-"]);
+        let white = game.position_stats().white;
+        assert_eq!(white.in_hand, 9);
+        // The queen is pinned between the ant and bQ
+        assert_eq!(white.pinned, 1);
+        assert_eq!(white.mobile, 1);
+        assert_eq!(white.moves_by_type[&PieceType::QueenBee], 0);
+        assert!(white.moves_by_type[&PieceType::Ant] > 0);
+        assert!(white.placements > 0);
+        assert_eq!(white.pillbug_moves, 0);
+        assert_eq!(white.covered, 0);
+        assert_eq!(white.frozen, None);
+        // Only the base game's piece types are listed
+        assert_eq!(white.moves_by_type.len(), 5);
+    }
+
+    #[test]
+    fn counts_covered_pieces() {
+        let game = play(&["wQ", "bQ wQ-", "wB1 -wQ", "bB1 bQ-", "wB1 wQ", "bB1 bQ"]);
+        let stats = game.position_stats();
+        assert_eq!(stats.white.covered, 1);
+        assert_eq!(stats.black.covered, 1);
+        // A covered queen can't move, and neither beetle is pinned
+        assert_eq!(stats.white.moves_by_type[&PieceType::QueenBee], 0);
+        assert_eq!(stats.white.pinned, 0);
+        assert_eq!(stats.white.mobile, 1);
+    }
+}
@@ -0,0 +1,186 @@
+// Random games
+//
+// Plays random legal moves from Game::get_all_moves until the game ends or a ply limit is reached.
+// Everything comes from the seed, so the same config always gives the same game: handy for test
+// fixtures, fuzzing Game's invariants and picking positions for perft.
+
+use crate::game::game_type::GameType;
+use crate::game::piece::PieceType;
+use crate::game::piece_move::PieceMove;
+use crate::game::rules::Rules;
+use crate::game::Game;
+use crate::rng::Rng;
+
+// How likely each legal move is to be picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Weighting {
+    // Every move as likely as any other
+    Uniform,
+    // Moves that end next to the opponent's queen are four times as likely, so more games end in a
+    // surround before the ply limit
+    Aggressive,
+}
+
+impl Weighting {
+    pub fn parse(s: &str) -> Option<Weighting> {
+        match s {
+            "uniform" => return Some(Weighting::Uniform),
+            "aggressive" => return Some(Weighting::Aggressive),
+            _ => return None,
+        }
+    }
+
+    fn weight(&self, game: &Game, piece_move: &PieceMove) -> usize {
+        match self {
+            Weighting::Uniform => return 1,
+            Weighting::Aggressive => {
+                let opponent = game.current_player().opponent();
+                let near_queen = match (game.queen(opponent), piece_move) {
+                    (Some(queen), PieceMove::Move { piece_id, hex, .. })
+                    | (Some(queen), PieceMove::Place { piece_id, hex }) => {
+                        piece_id.piece_type != PieceType::QueenBee
+                            && queen.hex.get_neighbors().iter().any(|n| n.same_cell(*hex))
+                    }
+                    _ => false,
+                };
+                if near_queen {
+                    return 4;
+                }
+                return 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomGameConfig {
+    pub seed: u64,
+    // Stop after this many moves (counting both players) if the game hasn't ended
+    pub max_plies: usize,
+    pub game_type: GameType,
+    pub rules: Rules,
+    pub weighting: Weighting,
+}
+
+impl Default for RandomGameConfig {
+    fn default() -> Self {
+        return RandomGameConfig {
+            seed: 0,
+            max_plies: 200,
+            game_type: GameType::default(),
+            rules: Rules::default(),
+            weighting: Weighting::Uniform,
+        };
+    }
+}
+
+// A random game from the start, as played by config. Its move_list is the moves picked.
+pub fn random_game(config: &RandomGameConfig) -> Game {
+    let mut game = Game::with_type(config.game_type);
+    game.rules = config.rules;
+    let mut rng = Rng::new(config.seed);
+    play_random_moves(&mut game, &mut rng, config.max_plies, config.weighting);
+    return game;
+}
+
+// Just the moves of random_game
+pub fn random_moves(config: &RandomGameConfig) -> Vec<PieceMove> {
+    return random_game(config).move_list;
+}
+
+// Plays up to plies random moves in game, stopping early if it ends
+pub fn play_random_moves(game: &mut Game, rng: &mut Rng, plies: usize, weighting: Weighting) {
+    for _ in 0..plies {
+        let moves = game.get_all_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let chosen = match weighting {
+            Weighting::Uniform => rng.choose(&moves).clone(),
+            _ => {
+                let weights: Vec<usize> = moves.iter().map(|m| weighting.weight(game, m)).collect();
+                let mut pick = rng.below(weights.iter().sum());
+                let i = weights
+                    .iter()
+                    .position(|&w| {
+                        if pick < w {
+                            return true;
+                        }
+                        pick -= w;
+                        return false;
+                    })
+                    .expect("pick is below the total weight");
+                moves[i].clone()
+            }
+        };
+        game.make_move(chosen).expect("Generated moves can be made");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+
+    #[test]
+    fn same_seed_same_game() {
+        for weighting in [Weighting::Uniform, Weighting::Aggressive] {
+            let config = RandomGameConfig {
+                seed: 11,
+                max_plies: 60,
+                weighting,
+                ..RandomGameConfig::default()
+            };
+            let game = random_game(&config);
+            assert_eq!(random_game(&config), game);
+            assert_eq!(random_moves(&config), game.move_list);
+
+            let other = RandomGameConfig { seed: 12, ..config };
+            assert_ne!(random_moves(&other), game.move_list);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_ply_limit_or_the_end() {
+        for seed in 0..20 {
+            let config = RandomGameConfig {
+                seed,
+                max_plies: 50,
+                game_type: GameType::BASE,
+                rules: Rules {
+                    draw_by_repetition: true,
+                    ..Rules::default()
+                },
+                weighting: Weighting::Aggressive,
+            };
+            let game = random_game(&config);
+            assert_eq!(game.game_type, GameType::BASE);
+            assert_eq!(game.rules, config.rules);
+            assert!(game.validate().is_ok());
+            assert!(game.move_list.len() <= 50);
+            if game.move_list.len() < 50 {
+                assert!(matches!(
+                    game.get_state(),
+                    GameState::Draw | GameState::WhiteWins | GameState::BlackWins
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn aggressive_favours_moves_next_to_the_queen() {
+        let mut game = Game::with_type(GameType::BASE);
+        for move_string in ["wQ", "bQ wQ-", "wA1 -wQ"] {
+            let m = game.parse_move_string(move_string).unwrap();
+            game.try_make_move(m).unwrap();
+        }
+        let near = game.parse_move_string("bA1 /wQ").unwrap();
+        let far = game.parse_move_string("bA1 bQ-").unwrap();
+        assert_eq!(Weighting::Aggressive.weight(&game, &near), 4);
+        assert_eq!(Weighting::Aggressive.weight(&game, &far), 1);
+        assert_eq!(Weighting::Uniform.weight(&game, &near), 1);
+        assert_eq!(Weighting::parse("aggressive"), Some(Weighting::Aggressive));
+        assert_eq!(Weighting::parse("uniform"), Some(Weighting::Uniform));
+        assert_eq!(Weighting::parse("greedy"), None);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::game::piece::PieceType;
+use crate::game::piece_move::MoveError;
+
+// Optional rules that differ between casual and tournament play. The default is casual play;
+// Rules::tournament() is the official tournament set.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Rules {
+    // Neither player may place their queen on their first turn
+    pub tournament_opening: bool,
+    // The turn (counted per player, from 1) by which a player's queen has to be placed
+    pub queen_deadline: usize,
+    // A position coming up for the third time with the same player to move is a draw
+    pub draw_by_repetition: bool,
+    // The game is drawn once both players have had this many turns
+    pub turn_limit: Option<usize>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        return Rules {
+            tournament_opening: false,
+            queen_deadline: 4,
+            draw_by_repetition: false,
+            turn_limit: None,
+        };
+    }
+}
+
+impl Rules {
+    pub fn tournament() -> Self {
+        return Rules {
+            tournament_opening: true,
+            queen_deadline: 4,
+            draw_by_repetition: true,
+            turn_limit: None,
+        };
+    }
+
+    // Whether a player whose queen isn't down yet has to place it this turn
+    pub fn queen_required(&self, turn: usize) -> bool {
+        return turn >= self.queen_deadline;
+    }
+
+    // Whether a player may place their queen this turn. The deadline wins if the two clash.
+    pub fn queen_allowed(&self, turn: usize) -> bool {
+        return !(self.tournament_opening && turn == 1) || self.queen_required(turn);
+    }
+
+    // Whether the queen rules let a player put down a piece of piece_type this turn. Every
+    // placement goes through this, so move generation and try_make_move agree.
+    pub fn check_placement(
+        &self,
+        turn: usize,
+        queen_placed: bool,
+        piece_type: PieceType,
+    ) -> Result<(), MoveError> {
+        if self.queen_required(turn) && !queen_placed && piece_type != PieceType::QueenBee {
+            return Err(MoveError::QueenMustBePlaced);
+        }
+        if !self.queen_allowed(turn) && piece_type == PieceType::QueenBee {
+            return Err(MoveError::QueenNotAllowed);
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::compact::{compact_move, CompactGame};
+    use crate::game::hex::Hex;
+    use crate::game::piece::PieceId;
+    use crate::game::piece_move::PieceMove;
+    use crate::game::Game;
+
+    fn places_queen(m: &PieceMove) -> bool {
+        return matches!(m, PieceMove::Place { piece_id, .. } if piece_id.piece_type == PieceType::QueenBee);
+    }
+
+    #[test]
+    fn queen_turns() {
+        let casual = Rules::default();
+        assert!(casual.queen_allowed(1) && !casual.queen_required(3) && casual.queen_required(4));
+        let tournament = Rules::tournament();
+        assert!(!tournament.queen_allowed(1) && tournament.queen_allowed(2));
+        // A deadline of 1 wins over the tournament opening
+        let rushed = Rules {
+            queen_deadline: 1,
+            ..Rules::tournament()
+        };
+        assert!(rushed.queen_allowed(1));
+    }
+
+    #[test]
+    fn checks_placements_against_the_queen_turns() {
+        let tournament = Rules::tournament();
+        let queen = PieceType::QueenBee;
+        assert_eq!(
+            tournament.check_placement(1, false, queen),
+            Err(MoveError::QueenNotAllowed)
+        );
+        assert_eq!(tournament.check_placement(1, false, PieceType::Ant), Ok(()));
+        assert_eq!(tournament.check_placement(4, false, queen), Ok(()));
+        assert_eq!(
+            tournament.check_placement(4, false, PieceType::Ant),
+            Err(MoveError::QueenMustBePlaced)
+        );
+        assert_eq!(tournament.check_placement(4, true, PieceType::Ant), Ok(()));
+    }
+
+    #[test]
+    fn tournament_opening_keeps_the_queen_back() {
+        let mut game = Game::new();
+        game.rules = Rules::tournament();
+        assert!(!game.get_all_moves().iter().any(places_queen));
+        let compact = CompactGame::from(&game);
+        let compact_moves = compact.get_all_moves();
+        assert!(game
+            .get_all_moves()
+            .iter()
+            .all(|m| compact_moves.contains(&compact_move(m))));
+        assert_eq!(compact_moves.len(), game.get_all_moves().len());
+        assert_eq!(
+            game.try_make_move(PieceMove::Place {
+                piece_id: PieceId::parse("wq").unwrap(),
+                hex: Hex::new(),
+            }),
+            Err(MoveError::QueenNotAllowed)
+        );
+    }
+
+    #[test]
+    fn queen_has_to_go_down_by_the_deadline() {
+        let mut game = Game::new();
+        for _ in 0..6 {
+            let m = game
+                .get_all_moves()
+                .into_iter()
+                .find(|m| !places_queen(m))
+                .unwrap();
+            game.make_move(m).unwrap();
+        }
+        assert_eq!(game.turn_number(), 4);
+        let moves = game.get_all_moves();
+        assert!(!moves.is_empty() && moves.iter().all(places_queen));
+        assert_eq!(CompactGame::from(&game).get_all_moves().len(), moves.len());
+    }
+}
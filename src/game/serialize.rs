@@ -0,0 +1,179 @@
+// Serde representations for the `serde` feature.
+//
+// Pieces are written as their id plus, once they're on the grid, their hex:
+//
+//     {"id": "wa1", "hex": {"q": 1, "r": -1, "s": 0, "z": 0}}
+//
+// A game is its game type, grid, both hands (as piece ids), the moves made before its move list
+// starts (for a game set up from a position) and its move list:
+//
+//     {
+//       "game_type": "Base+MLP",
+//       "rules": {"tournament_opening": true, "queen_deadline": 4, "draw_by_repetition": true},
+//       "grid": [{"id": "wq", "hex": {...}}, ...],
+//       "white_hand": ["ws1", "ws2", ...],
+//       "black_hand": ["bq", ...],
+//       "start_ply": 0,
+//       "move_list": [{"type": "place", "piece_id": "wq", "hex": {...}}, {"type": "pass"}, ...]
+//     }
+//
+// Nothing process-specific (like petgraph's node indices) is written out; the grid's edges are
+// rebuilt when a game is read back in. Games written before game types and rules existed are read
+// as Base+MLP with the default (casual) rules.
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::game_type::GameType;
+use crate::game::hand::Hand;
+use crate::game::hex::Hex;
+use crate::game::piece::{Piece, PieceColor, PieceId};
+use crate::game::piece_move::PieceMove;
+use crate::game::rules::Rules;
+use crate::game::Game;
+
+#[derive(Serialize, Deserialize)]
+pub struct PieceRepr {
+    pub id: PieceId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hex: Option<Hex>,
+}
+
+impl From<Piece> for PieceRepr {
+    fn from(piece: Piece) -> Self {
+        return PieceRepr {
+            hex: if piece.in_hand { None } else { Some(piece.hex) },
+            id: piece.id,
+        };
+    }
+}
+
+impl TryFrom<PieceRepr> for Piece {
+    type Error = String;
+
+    fn try_from(repr: PieceRepr) -> Result<Self, Self::Error> {
+        let mut piece = Piece::from(repr.id);
+        if let Some(hex) = repr.hex {
+            piece.hex = hex;
+            piece.in_hand = false;
+        }
+        return Ok(piece);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GameRepr {
+    #[serde(default)]
+    pub game_type: GameType,
+    #[serde(default)]
+    pub rules: Rules,
+    pub grid: Vec<Piece>,
+    pub white_hand: Vec<PieceId>,
+    pub black_hand: Vec<PieceId>,
+    #[serde(default)]
+    pub start_ply: usize,
+    pub move_list: Vec<PieceMove>,
+}
+
+impl From<Game> for GameRepr {
+    fn from(game: Game) -> Self {
+        let mut grid: Vec<Piece> = game.grid.node_weights().cloned().collect();
+        // Bottom of each stack first, so stacks are rebuilt in order
+        grid.sort_by_key(|p| (p.hex.z, p.hex.q(), p.hex.r()));
+        return GameRepr {
+            game_type: game.game_type,
+            rules: game.rules,
+            grid,
+            white_hand: game.p2_hand.ids(),
+            black_hand: game.p1_hand.ids(),
+            start_ply: game.start_ply,
+            move_list: game.move_list,
+        };
+    }
+}
+
+impl TryFrom<GameRepr> for Game {
+    type Error = String;
+
+    fn try_from(repr: GameRepr) -> Result<Self, Self::Error> {
+        let hand = |color: PieceColor, ids: Vec<PieceId>| -> Result<Hand, String> {
+            if let Some(id) = ids.iter().find(|id| id.color != color) {
+                return Err(format!("piece `{}` is in the wrong hand", id));
+            }
+            return Hand::from_ids(color, &ids).ok_or_else(|| {
+                String::from("a hand should hold the highest numbered pieces of each type")
+            });
+        };
+        let mut game = Game {
+            game_type: repr.game_type,
+            rules: repr.rules,
+            start_ply: repr.start_ply,
+            ..Game::default()
+        };
+        // The grid goes down while the hands are empty, so a piece in both can't quietly come out
+        // of its hand
+        for piece in repr.grid {
+            if piece.in_hand {
+                return Err(format!("piece `{}` on the grid has no hex", piece.id));
+            }
+            let hex = piece.hex;
+            game.add_to_grid(piece, hex).map_err(|e| e.to_string())?;
+        }
+        game.p1_hand = hand(PieceColor::Black, repr.black_hand)?;
+        game.p2_hand = hand(PieceColor::White, repr.white_hand)?;
+        game.move_list = repr.move_list;
+        game.rebuild_history()
+            .map_err(|e| format!("the move list doesn't lead to the grid: {}", e))?;
+        // Catches anything the document got wrong that building the game didn't, like a piece both
+        // in hand and on the grid, or a move list that doesn't lead to the grid
+        game.validate().map_err(|e| e.to_string())?;
+        return Ok(game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::random::{random_game, RandomGameConfig};
+
+    #[test]
+    fn json_round_trip() {
+        for seed in 0..20 {
+            let config = RandomGameConfig {
+                seed,
+                max_plies: seed as usize * 5,
+                ..RandomGameConfig::default()
+            };
+            let game = random_game(&config);
+            let json = serde_json::to_string(&game).unwrap();
+            let read: Game = serde_json::from_str(&json).unwrap();
+            assert!(read == game, "{}", json);
+            assert_eq!(read.position_history(), game.position_history());
+        }
+    }
+
+    #[test]
+    fn rejects_piece_in_hand_and_on_grid() {
+        let mut game = Game::new();
+        game.make_move(PieceMove::Place {
+            piece_id: PieceId::parse("wq").unwrap(),
+            hex: Hex::from_axial(0, 0),
+        })
+        .unwrap();
+        let mut json: serde_json::Value = serde_json::to_value(&game).unwrap();
+        json["white_hand"].as_array_mut().unwrap().push("wq".into());
+        assert!(serde_json::from_value::<Game>(json).is_err());
+    }
+
+    #[test]
+    fn rejects_move_list_that_does_not_lead_to_grid() {
+        let mut game = Game::new();
+        game.make_move(PieceMove::Place {
+            piece_id: PieceId::parse("wq").unwrap(),
+            hex: Hex::from_axial(0, 0),
+        })
+        .unwrap();
+        let mut json: serde_json::Value = serde_json::to_value(&game).unwrap();
+        json["move_list"][0]["piece_id"] = "wa1".into();
+        assert!(serde_json::from_value::<Game>(json).is_err());
+    }
+}
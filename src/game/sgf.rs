@@ -0,0 +1,426 @@
+// boardspace.net SGF game records
+//
+// boardspace writes each action of a Hive game as a P0[...] or P1[...] property (for the first
+// and second player), e.g.
+//
+//     ; P0[1 dropb wQ N 13 .]
+//     ; P0[2 done]
+//     ; P1[3 dropb bQ O 13 wQ-]
+//     ; P1[4 done]
+//     ; P0[5 move W wQ N 12 /bQ]
+//
+// Every action that puts a piece somewhere (`dropb`, `move`, `pmove`, `pdropb`) gives the piece
+// and the cell it ends up on as a column letter and row number; the trailing relative position is
+// only a hint and is ignored. `pmove` and `pdropb` are the same actions performed with a pillbug.
+// Picking pieces up (`pick`, `pickb`), `done`, clocks and player details don't change the position.
+// A player can drop a piece, pick it up again and drop it elsewhere before finishing their turn,
+// so only the last placement or move made in each turn counts.
+//
+// boardspace's grid is axial with the columns lettered and the rows numbered, drawn with pointy
+// tops like UHP's. Going one column right is east and one row up is north-west, so a cell's six
+// neighbours are (+1, 0) E, (+1, +1) NE, (0, +1) NW, (-1, 0) W, (-1, -1) SW and (0, -1) SE. These
+// are the directions the relative positions in the record use (see notation.rs), which on our
+// flat-topped grid are NE, N, NW, SW, S and SE. The first piece dropped is moved to the centre of
+// our grid.
+//
+// boardspace lets players take pieces from their hand in any order, so pieces are renumbered in
+// the order they are placed (the first white ant placed becomes wa1, and so on).
+//
+// The game type comes from the SU property: `hive` is the base game and `hive-plm`, `hive-m`... add
+// the pillbug, ladybug and mosquito. Records without one (or with a variant we don't know) are
+// read as Base+MLP.
+
+use std::fmt;
+
+use crate::game::game_type::GameType;
+use crate::game::hex::Hex;
+use crate::game::piece::{PieceColor, PieceId, PieceType};
+use crate::game::piece_move::PieceMove;
+use crate::game::Game;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SgfError {
+    MalformedRecord(String),
+    MalformedAction(String),
+    UnknownPiece(String),
+    IllegalMove { move_number: usize, action: String },
+}
+
+impl fmt::Display for SgfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SgfError::MalformedRecord(s) => write!(f, "malformed SGF record: {}", s),
+            SgfError::MalformedAction(s) => write!(f, "malformed action `{}`", s),
+            SgfError::UnknownPiece(s) => write!(f, "unknown piece `{}`", s),
+            SgfError::IllegalMove {
+                move_number,
+                action,
+            } => write!(f, "move {} `{}` is illegal", move_number, action),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    // A piece ends up on the cell at (column, row)
+    Put {
+        name: String,
+        column: isize,
+        row: isize,
+        text: String,
+    },
+    Pass,
+    Done,
+    End,
+}
+
+pub fn import_from_sgf(sgf: &str) -> Result<Game, SgfError> {
+    let properties = parse_properties(sgf)?;
+    let actions = parse_actions(&properties)?;
+    let game_type = parse_game_type(&properties);
+    // Swap colours if the record has black moving first, since our games always start with white
+    let swap_colors = actions.iter().find_map(|(_, a)| match a {
+        Action::Put { name, .. } => Some(name.starts_with('b')),
+        _ => None,
+    }) == Some(true);
+
+    return Replay::new(game_type, swap_colors).run(&actions);
+}
+
+// Our hex for the boardspace cell column columns and row rows away from the first piece dropped
+fn boardspace_offset(columns: isize, rows: isize) -> Hex {
+    return Hex::from_axial(columns - rows, -columns);
+}
+
+struct Replay {
+    game: Game,
+    swap_colors: bool,
+    // boardspace names -> our ids
+    names: Vec<(String, PieceId)>,
+    // boardspace cell of the first piece dropped
+    origin: Option<(isize, isize)>,
+    move_number: usize,
+}
+
+impl Replay {
+    fn new(game_type: GameType, swap_colors: bool) -> Self {
+        return Replay {
+            game: Game::with_type(game_type),
+            swap_colors,
+            names: Vec::new(),
+            origin: None,
+            move_number: 0,
+        };
+    }
+
+    fn run(mut self, actions: &[(usize, Action)]) -> Result<Game, SgfError> {
+        let mut pending: Option<(usize, &Action)> = None;
+        for (player, action) in actions {
+            match action {
+                Action::Put { .. } => {
+                    // The other player putting a piece down means the previous turn is over
+                    if let Some((pending_player, turn)) = pending.take() {
+                        if pending_player != *player {
+                            self.apply(turn)?;
+                        }
+                    }
+                    pending = Some((*player, action));
+                }
+                Action::Done => {
+                    if let Some((_, turn)) = pending.take() {
+                        self.apply(turn)?;
+                    }
+                }
+                Action::Pass => {
+                    if let Some((_, turn)) = pending.take() {
+                        self.apply(turn)?;
+                    }
+                    self.apply(action)?;
+                }
+                Action::End => break,
+            }
+        }
+        if let Some((_, turn)) = pending.take() {
+            self.apply(turn)?;
+        }
+        return Ok(self.game);
+    }
+
+    fn apply(&mut self, action: &Action) -> Result<(), SgfError> {
+        self.move_number += 1;
+        let (name, column, row, text) = match action {
+            Action::Put {
+                name,
+                column,
+                row,
+                text,
+            } => (name, *column, *row, text),
+            Action::Pass => return self.validate_and_make(PieceMove::Pass, "pass"),
+            _ => return Ok(()),
+        };
+        let piece_id = self.piece_id(name, text)?;
+
+        let (origin_column, origin_row) = *self.origin.get_or_insert((column, row));
+        let cell = boardspace_offset(column - origin_column, row - origin_row);
+        let z = self
+            .game
+            .grid
+            .node_weights()
+            .filter(|p| p.hex.same_cell(cell) && p.id != piece_id)
+            .count() as isize;
+        let hex = cell.with_z(z);
+
+        let piece_move = match self.game.get_piece(piece_id) {
+            // Picking a piece up and putting it straight back isn't a move
+            Some(p) if p.hex == hex => {
+                self.move_number -= 1;
+                return Ok(());
+            }
+            Some(p) => PieceMove::Move {
+                from: p.hex,
+                piece_id,
+                hex,
+            },
+            None => PieceMove::Place { piece_id, hex },
+        };
+        return self.validate_and_make(piece_move, text);
+    }
+
+    // Finds (or assigns, if the piece is being placed for the first time) our id for a piece
+    fn piece_id(&mut self, name: &str, text: &str) -> Result<PieceId, SgfError> {
+        if let Some((_, id)) = self.names.iter().find(|(n, _)| n == name) {
+            return Ok(*id);
+        }
+
+        let mut lowered = name.to_ascii_lowercase();
+        if self.swap_colors && lowered.is_ascii() && !lowered.is_empty() {
+            let color = if lowered.starts_with('w') { "b" } else { "w" };
+            lowered.replace_range(0..1, color);
+        }
+        let mut chars = lowered.chars();
+        let color = match chars.next() {
+            Some('w') => PieceColor::White,
+            Some('b') => PieceColor::Black,
+            _ => return Err(SgfError::UnknownPiece(name.to_string())),
+        };
+        let piece_type = chars
+            .next()
+            .and_then(PieceType::from_letter)
+            .ok_or_else(|| SgfError::UnknownPiece(name.to_string()))?;
+        let id = self
+            .game
+            .hand(color)
+            .next(piece_type)
+            .ok_or_else(|| SgfError::IllegalMove {
+                move_number: self.move_number,
+                action: text.to_string(),
+            })?;
+        self.names.push((name.to_string(), id));
+        return Ok(id);
+    }
+
+    fn validate_and_make(&mut self, piece_move: PieceMove, text: &str) -> Result<(), SgfError> {
+        self.game
+            .try_make_move(piece_move)
+            .map_err(|_| SgfError::IllegalMove {
+                move_number: self.move_number,
+                action: text.to_string(),
+            })?;
+        return Ok(());
+    }
+}
+
+fn parse_game_type(properties: &[(String, String)]) -> GameType {
+    let value = match properties.iter().find(|(name, _)| name == "SU") {
+        Some((_, value)) => value.trim().to_ascii_lowercase(),
+        None => return GameType::default(),
+    };
+    let expansions = match value.strip_prefix("hive") {
+        Some("") => return GameType::BASE,
+        Some(rest) => rest.strip_prefix('-').unwrap_or(""),
+        None => "",
+    };
+    if expansions.is_empty() || !expansions.chars().all(|c| "mlp".contains(c)) {
+        return GameType::default();
+    }
+    return GameType {
+        mosquito: expansions.contains('m'),
+        ladybug: expansions.contains('l'),
+        pillbug: expansions.contains('p'),
+    };
+}
+
+// Every property in the record as (name, value) pairs, in order, with the values unescaped. A
+// property with several values (`AB[aa][bb]`) gives a pair for each.
+fn parse_properties(sgf: &str) -> Result<Vec<(String, String)>, SgfError> {
+    if !sgf.trim_start().starts_with('(') {
+        return Err(SgfError::MalformedRecord(String::from(
+            "a record starts with `(`",
+        )));
+    }
+
+    let mut properties: Vec<(String, String)> = Vec::new();
+    let mut name = String::new();
+    // Whether name has had a value yet, so the next letter starts a new one
+    let mut has_value = false;
+    let mut chars = sgf.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                if name.is_empty() {
+                    return Err(SgfError::MalformedRecord(String::from(
+                        "property value without a name",
+                    )));
+                }
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('\\') => value.extend(chars.next()),
+                        Some(c) => value.push(c),
+                        None => {
+                            return Err(SgfError::MalformedRecord(String::from(
+                                "unterminated property value",
+                            )))
+                        }
+                    }
+                }
+                properties.push((name.clone(), value));
+                has_value = true;
+            }
+            c if c.is_ascii_alphanumeric() => {
+                if has_value {
+                    name.clear();
+                    has_value = false;
+                }
+                name.push(c);
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                name.clear();
+                has_value = false;
+            }
+        }
+    }
+    return Ok(properties);
+}
+
+// Every P0[...] / P1[...] action in the record, in order, with the index of the player making it
+fn parse_actions(properties: &[(String, String)]) -> Result<Vec<(usize, Action)>, SgfError> {
+    let mut actions: Vec<(usize, Action)> = Vec::new();
+    for (name, value) in properties {
+        let player = match name.as_str() {
+            "P0" => 0,
+            "P1" => 1,
+            _ => continue,
+        };
+        if let Some(action) = parse_action(value)? {
+            actions.push((player, action));
+        }
+    }
+    return Ok(actions);
+}
+
+fn parse_action(value: &str) -> Result<Option<Action>, SgfError> {
+    let tokens: Vec<&str> = value.split_whitespace().collect();
+    // Player details (`id "name"`, `time ...`) aren't numbered actions
+    if tokens.is_empty() || tokens[0].parse::<usize>().is_err() {
+        return Ok(None);
+    }
+    let malformed = || SgfError::MalformedAction(value.to_string());
+    let verb = match tokens.get(1) {
+        Some(v) => v.to_ascii_lowercase(),
+        None => return Err(malformed()),
+    };
+
+    match verb.as_str() {
+        "dropb" | "move" | "pmove" | "pdropb" => {
+            let mut args = &tokens[2..];
+            // Newer records say whose piece it is before naming it: `move W wQ N 12 /bQ`
+            if args.len() > 1 && (args[0] == "W" || args[0] == "B") {
+                args = &args[1..];
+            }
+            if args.len() < 3 {
+                return Err(malformed());
+            }
+            let column = match args[1].chars().collect::<Vec<char>>()[..] {
+                [c] if c.is_ascii_alphabetic() => (c.to_ascii_uppercase() as u8 - b'A') as isize,
+                _ => return Err(malformed()),
+            };
+            let row = args[2].parse::<isize>().map_err(|_| malformed())?;
+            return Ok(Some(Action::Put {
+                name: args[0].to_string(),
+                column,
+                row,
+                text: tokens[1..].join(" "),
+            }));
+        }
+        "pass" => return Ok(Some(Action::Pass)),
+        "done" => return Ok(Some(Action::Done)),
+        "resign" | "acceptdraw" | "gameover" => return Ok(Some(Action::End)),
+        // start, pick, pickb, edit, swap, offerdraw, declinedraw...
+        _ => return Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_boardspace_record() {
+        let game = import_from_sgf(include_str!("testdata/boardspace.sgf")).unwrap();
+        assert_eq!(game.game_type, GameType::BASE);
+        // bA2 was dropped before bA1, so they swap numbers, and wA1's first drop was taken back
+        assert_eq!(
+            game.export_game_string().unwrap(),
+            concat!(
+                r"Base;InProgress;White[7];wG1;bG1 wG1-;wQ -wG1;bQ bG1-;wB1 \wQ;bA1 bQ/;",
+                r"wB1 wQ;bA1 -wB1;wA1 \wG1;bA2 bQ\;wA1 bA2\;bG2 bA2/"
+            )
+        );
+        game.validate().unwrap();
+    }
+
+    #[test]
+    fn boardspace_neighbours_are_ours() {
+        let origin = boardspace_offset(0, 0);
+        for (columns, rows) in [(1, 0), (1, 1), (0, 1), (-1, 0), (-1, -1), (0, -1)] {
+            assert!(origin
+                .get_neighbors()
+                .contains(&boardspace_offset(columns, rows)));
+        }
+    }
+
+    #[test]
+    fn looks_properties_up_by_name() {
+        // Comments mentioning SU[ or P0[ aren't properties, and escaped brackets don't end values
+        let sgf = concat!(
+            r"(;GM[27]C[set SU[hive-plm\] and P0[1 dropb wQ N 13 .\]]SU[hive]",
+            r"; P0[1 dropb wQ N 13 .]; P1[2 dropb bQ O 13 wQ-]AB[aa][bb])"
+        );
+        let properties = parse_properties(sgf).unwrap();
+        let names: Vec<&str> = properties.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["GM", "C", "SU", "P0", "P1", "AB", "AB"]);
+        assert_eq!(
+            properties[1].1,
+            "set SU[hive-plm] and P0[1 dropb wQ N 13 .]"
+        );
+
+        let game = import_from_sgf(sgf).unwrap();
+        assert_eq!(game.game_type, GameType::BASE);
+        assert_eq!(game.move_list.len(), 2);
+    }
+
+    #[test]
+    fn rejects_illegal_record() {
+        let sgf = "(; P0[1 dropb wQ N 13 .] ; P1[2 dropb bQ Q 13 .])";
+        assert_eq!(
+            import_from_sgf(sgf).unwrap_err(),
+            SgfError::IllegalMove {
+                move_number: 2,
+                action: String::from("dropb bQ Q 13 ."),
+            }
+        );
+    }
+}
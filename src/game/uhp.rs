@@ -0,0 +1,378 @@
+// UHP (Universal Hive Protocol) GameStrings
+//
+// A GameString describes a whole game, from the first move to the current position:
+//
+//     GameTypeString;GameStateString;TurnString;MoveString;MoveString;...
+//
+// e.g. `Base+MLP;InProgress;White[3];wS1;bG1 -wS1;wA1 wS1/;bG2 /bG1`
+//
+// The GameTypeString is `Base` plus whichever expansion bugs are in play, e.g. `Base+ML` (see
+// GameType).
+//
+// Pieces are written with UHP names (`wQ`, `bA2`, `wM`) rather than our ids (`wq`, `ba2`, `wm`).
+// A MoveString is the piece being placed or moved, then where it ends up relative to a piece that
+// is already on the grid (see notation.rs), e.g. `wS1/` or `-bQ`. The first piece of the game is
+// written on its own, and `pass` is a pass.
+//
+// UHP draws the grid with pointy-topped hexes while we use flat-topped ones, so UHP's directions
+// map onto ours rotated by a twelfth of a turn: E -> NE, NE -> N, NW -> NW, W -> SW, SW -> S and
+// SE -> SE.
+
+use std::fmt;
+
+use crate::game::error::GameError;
+use crate::game::game_type::GameType;
+use crate::game::hex::Hex;
+use crate::game::notation::{NotationError, RelativePosition};
+use crate::game::piece::{PieceColor, PieceId};
+use crate::game::piece_move::PieceMove;
+use crate::game::{Game, GameState};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum UhpError {
+    MalformedGameString(String),
+    UnsupportedGameType(String),
+    UnknownGameState(String),
+    MalformedTurn(String),
+    MalformedMove(String),
+    UnknownPiece(String),
+    PieceNotOnGrid(String),
+    IllegalMove {
+        move_number: usize,
+        move_string: String,
+    },
+    StateMismatch {
+        expected: String,
+        found: String,
+    },
+    TurnMismatch {
+        expected: String,
+        found: String,
+    },
+    // A game set up from a position can't be written out, since it doesn't start from the start
+    MissingHistory,
+}
+
+impl fmt::Display for UhpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UhpError::MalformedGameString(s) => write!(f, "malformed GameString `{}`", s),
+            UhpError::UnsupportedGameType(s) => write!(f, "unsupported game type `{}`", s),
+            UhpError::UnknownGameState(s) => write!(f, "unknown game state `{}`", s),
+            UhpError::MalformedTurn(s) => write!(f, "malformed turn `{}`", s),
+            UhpError::MalformedMove(s) => write!(f, "malformed move `{}`", s),
+            UhpError::UnknownPiece(s) => write!(f, "unknown piece `{}`", s),
+            UhpError::PieceNotOnGrid(s) => write!(f, "piece `{}` is not on the grid", s),
+            UhpError::IllegalMove {
+                move_number,
+                move_string,
+            } => write!(f, "move {} `{}` is illegal", move_number, move_string),
+            UhpError::StateMismatch { expected, found } => write!(
+                f,
+                "GameString says the game is {} but the moves leave it {}",
+                expected, found
+            ),
+            UhpError::TurnMismatch { expected, found } => write!(
+                f,
+                "GameString says it is {} but the moves leave it at {}",
+                expected, found
+            ),
+            UhpError::MissingHistory => write!(
+                f,
+                "the game was set up from a position, so has no GameString"
+            ),
+        }
+    }
+}
+
+impl Game {
+    // Errors if the move_list can't be replayed from the start, e.g. for a game imported from IGN
+    pub fn export_game_string(&self) -> Result<String, GameError> {
+        if self.start_ply > 0 {
+            return Err(UhpError::MissingHistory.into());
+        }
+        let mut parts: Vec<String> = vec![
+            self.game_type.to_string(),
+            String::from(state_string(self.get_state())),
+            turn_string(self),
+        ];
+
+        // MoveStrings depend on the position they're played in, so replay the game from the start
+        let mut replay = Game::with_type(self.game_type);
+        replay.rules = self.rules;
+        for m in &self.move_list {
+            parts.push(replay.move_string(m));
+            replay.make_move(m.clone())?;
+        }
+        return Ok(parts.join(";"));
+    }
+
+    // Writes piece_move as a UHP MoveString, relative to the current position
+    pub fn move_string(&self, piece_move: &PieceMove) -> String {
+        let (piece_id, hex) = match piece_move {
+            PieceMove::Place { piece_id, hex } | PieceMove::Move { piece_id, hex, .. } => {
+                (*piece_id, *hex)
+            }
+            PieceMove::Pass => return String::from("pass"),
+        };
+        let name = uhp_name(piece_id);
+        if let Some(position) = self.relative_position(hex, Some(piece_id)) {
+            return format!("{} {}", name, position);
+        }
+
+        // Nothing to be relative to: this is the first piece of the game
+        return name;
+    }
+
+    // Reads a UHP MoveString in the current position. This only checks that the move makes sense,
+    // not that it is legal.
+    pub fn parse_move_string(&self, move_string: &str) -> Result<PieceMove, UhpError> {
+        let move_string = move_string.trim();
+        if move_string.eq_ignore_ascii_case("pass") {
+            return Ok(PieceMove::Pass);
+        }
+        let malformed = || UhpError::MalformedMove(move_string.to_string());
+
+        let tokens: Vec<&str> = move_string.split_whitespace().collect();
+        let piece_id = match tokens.first() {
+            Some(name) => id_from_uhp_name(name)?,
+            None => return Err(malformed()),
+        };
+
+        let hex = match tokens[1..] {
+            [] => {
+                if self.grid.node_count() > 0 {
+                    return Err(malformed());
+                }
+                Hex::new()
+            }
+            [position] => {
+                let position = RelativePosition::parse(position).map_err(|e| match e {
+                    NotationError::UnknownPiece(name) => UhpError::UnknownPiece(name),
+                    NotationError::AnchorNotOnGrid(name) => UhpError::PieceNotOnGrid(name),
+                    _ => malformed(),
+                })?;
+                self.resolve_position(&position, Some(piece_id))
+                    .map_err(|e| match e {
+                        NotationError::AnchorNotOnGrid(name) => UhpError::PieceNotOnGrid(name),
+                        _ => malformed(),
+                    })?
+            }
+            _ => return Err(malformed()),
+        };
+
+        if let Some(piece) = self.get_piece(piece_id) {
+            return Ok(PieceMove::Move {
+                from: piece.hex,
+                piece_id,
+                hex,
+            });
+        }
+        return Ok(PieceMove::Place { piece_id, hex });
+    }
+}
+
+// Replays every move in the GameString through Game::make_move, checking each one is legal
+pub fn import_from_game_string(game_string: &str) -> Result<Game, UhpError> {
+    let tokens: Vec<&str> = game_string.trim().split(';').map(|t| t.trim()).collect();
+    if tokens.len() < 3 {
+        return Err(UhpError::MalformedGameString(game_string.to_string()));
+    }
+    let game_type = GameType::parse(tokens[0])
+        .ok_or_else(|| UhpError::UnsupportedGameType(tokens[0].to_string()))?;
+    let state = tokens[1];
+    if ![
+        GameState::NotStarted,
+        GameState::InProgress,
+        GameState::Draw,
+        GameState::WhiteWins,
+        GameState::BlackWins,
+    ]
+    .iter()
+    .any(|&s| state_string(s) == state)
+    {
+        return Err(UhpError::UnknownGameState(state.to_string()));
+    }
+    let turn = tokens[2];
+    if parse_turn_string(turn).is_none() {
+        return Err(UhpError::MalformedTurn(turn.to_string()));
+    }
+
+    let mut game = Game::with_type(game_type);
+    for (i, move_string) in tokens[3..].iter().enumerate() {
+        let piece_move = game.parse_move_string(move_string)?;
+        game.try_make_move(piece_move)
+            .map_err(|_| UhpError::IllegalMove {
+                move_number: i + 1,
+                move_string: move_string.to_string(),
+            })?;
+    }
+
+    if state_string(game.get_state()) != state {
+        return Err(UhpError::StateMismatch {
+            expected: state.to_string(),
+            found: state_string(game.get_state()).to_string(),
+        });
+    }
+    if turn_string(&game) != turn {
+        return Err(UhpError::TurnMismatch {
+            expected: turn.to_string(),
+            found: turn_string(&game),
+        });
+    }
+    return Ok(game);
+}
+
+// wa1 -> "wA1"
+pub fn uhp_name(piece_id: PieceId) -> String {
+    let mut name = String::new();
+    for (i, c) in piece_id.to_string().chars().enumerate() {
+        if i == 1 {
+            name.push(c.to_ascii_uppercase());
+        } else {
+            name.push(c);
+        }
+    }
+    return name;
+}
+
+// "wA1" -> wa1
+pub fn id_from_uhp_name(name: &str) -> Result<PieceId, UhpError> {
+    let mut chars = name.chars();
+    let valid = matches!(chars.next(), Some('w' | 'b'))
+        && matches!(
+            chars.next(),
+            Some('Q' | 'A' | 'B' | 'G' | 'S' | 'L' | 'P' | 'M')
+        );
+    return PieceId::parse(&name.to_ascii_lowercase())
+        .filter(|_| valid)
+        .ok_or_else(|| UhpError::UnknownPiece(name.to_string()));
+}
+
+fn state_string(state: GameState) -> &'static str {
+    match state {
+        GameState::NotStarted => return "NotStarted",
+        GameState::InProgress => return "InProgress",
+        GameState::Draw => return "Draw",
+        GameState::WhiteWins => return "WhiteWins",
+        GameState::BlackWins => return "BlackWins",
+    }
+}
+
+pub(crate) fn turn_string(game: &Game) -> String {
+    let color = match game.current_player() {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black",
+    };
+    return format!("{}[{}]", color, game.turn_number());
+}
+
+// The number of moves made before a turn like `White[3]` or `Black[1]`
+pub(crate) fn parse_turn_string(turn: &str) -> Option<usize> {
+    let (black, number) = match turn.strip_prefix("White[") {
+        Some(n) => (0, n),
+        None => (1, turn.strip_prefix("Black[")?),
+    };
+    let number = number.strip_suffix(']')?.parse::<usize>().ok()?;
+    if number == 0 {
+        return None;
+    }
+    return Some(2 * (number - 1) + black);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::random::{random_game, RandomGameConfig};
+
+    #[test]
+    fn random_games_round_trip() {
+        for seed in 0..20 {
+            let config = RandomGameConfig {
+                seed,
+                max_plies: 10 + seed as usize * 5,
+                ..RandomGameConfig::default()
+            };
+            let game = random_game(&config);
+            let game_string = game.export_game_string().unwrap();
+            let imported = import_from_game_string(&game_string).unwrap();
+            assert!(imported == game, "{}", game_string);
+            assert_eq!(imported.export_game_string().unwrap(), game_string);
+        }
+    }
+
+    #[test]
+    fn new_game_string() {
+        assert_eq!(
+            Game::with_type(GameType::BASE)
+                .export_game_string()
+                .unwrap(),
+            "Base;NotStarted;White[1]"
+        );
+        let game = import_from_game_string("Base+MLP;InProgress;Black[1];wL").unwrap();
+        assert_eq!(game.ply(), 1);
+    }
+
+    #[test]
+    fn game_strings_round_trip() {
+        let game_string = "Base+MLP;InProgress;White[3];wQ;bQ wQ-;wA1 -wQ;bA1 bQ-";
+        let game = import_from_game_string(game_string).unwrap();
+        assert_eq!(game.export_game_string().unwrap(), game_string);
+    }
+
+    #[test]
+    fn rejects_bad_game_strings() {
+        let import = |s: &str| import_from_game_string(s).unwrap_err();
+        assert_eq!(
+            import("Base;InProgress"),
+            UhpError::MalformedGameString(String::from("Base;InProgress"))
+        );
+        assert_eq!(
+            import("Base+X;NotStarted;White[1]"),
+            UhpError::UnsupportedGameType(String::from("Base+X"))
+        );
+        assert_eq!(
+            import("Base;Started;White[1]"),
+            UhpError::UnknownGameState(String::from("Started"))
+        );
+        assert_eq!(
+            import("Base;InProgress;Black[2];wQ;bQ wQ-;wQ bQ-"),
+            UhpError::IllegalMove {
+                move_number: 3,
+                move_string: String::from("wQ bQ-"),
+            }
+        );
+        assert_eq!(
+            import("Base;InProgress;White[1];wQ"),
+            UhpError::TurnMismatch {
+                expected: String::from("White[1]"),
+                found: String::from("Black[1]"),
+            }
+        );
+        assert_eq!(
+            import("Base;WhiteWins;Black[1];wQ"),
+            UhpError::StateMismatch {
+                expected: String::from("WhiteWins"),
+                found: String::from("InProgress"),
+            }
+        );
+    }
+
+    #[test]
+    fn positions_have_no_game_string() {
+        let game = import_from_game_string("Base;InProgress;Black[1];wQ").unwrap();
+        assert_eq!(
+            game.position().export_game_string(),
+            Err(GameError::Uhp(UhpError::MissingHistory))
+        );
+    }
+
+    #[test]
+    fn piece_names() {
+        let id = PieceId::parse("ba2").unwrap();
+        assert_eq!(uhp_name(id), "bA2");
+        assert_eq!(id_from_uhp_name("bA2"), Ok(id));
+        assert!(id_from_uhp_name("ba2").is_err());
+        assert!(id_from_uhp_name("wQ1").is_err());
+    }
+}
@@ -0,0 +1,480 @@
+// Game invariants
+//
+// Game::validate checks the things that should hold for any position, however it was reached:
+// the hive is in one piece, stacks are stacked properly, every piece is in exactly one place, the
+// grid's edges agree with where the pieces are, and the move list agrees with whose turn it was at
+// each move.
+// make_move checks them after every move in debug builds, and fuzzers can call validate directly.
+
+use std::fmt;
+
+use petgraph::visit::EdgeRef;
+
+use crate::game::hand::Hand;
+use crate::game::hex::{get_all_edge_types, Hex, HexEdge};
+use crate::game::piece::{PieceColor, PieceId, PieceType};
+use crate::game::piece_move::PieceMove;
+use crate::game::Game;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum InvariantError {
+    HiveSplit,
+    // Two pieces at the same hex and height
+    SharedHex(Hex),
+    // A piece above an empty level of its stack
+    StackGap(Hex),
+    OnGridInHand(PieceId),
+    // A piece of the game type that is neither in a hand nor on the grid
+    MissingPiece(PieceId),
+    // A piece on the grid more than once, or both on the grid and in a hand
+    DuplicatePiece(PieceId),
+    // A piece from an expansion that isn't part of the game type
+    PieceNotInGame(PieceId),
+    // An edge whose direction doesn't lead from its source piece to its target
+    WrongEdge {
+        from: PieceId,
+        to: PieceId,
+        edge: HexEdge,
+    },
+    // Touching pieces with no edge between them
+    MissingEdge {
+        from: PieceId,
+        to: PieceId,
+    },
+    // Touching pieces with more than one edge between them
+    DuplicateEdge {
+        from: PieceId,
+        to: PieceId,
+    },
+    // A move in move_list made on the other player's turn: a placement or move of the other
+    // player's piece that couldn't have been a pillbug throw
+    WrongPlayer {
+        move_number: usize,
+        piece_id: PieceId,
+    },
+    // More placements in move_list than the player has pieces on the grid
+    TooManyPlacements(PieceColor),
+    // More of a player's pieces on the grid than turns they've had to put them there
+    TooManyPieces(PieceColor),
+    // position_history() doesn't have one entry per move
+    HistoryMismatch,
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvariantError::HiveSplit => write!(f, "the hive is split"),
+            InvariantError::SharedHex(hex) => write!(f, "more than one piece at {:?}", hex),
+            InvariantError::StackGap(hex) => write!(f, "nothing underneath {:?}", hex),
+            InvariantError::OnGridInHand(id) => {
+                write!(f, "`{}` is on the grid but marked as in hand", id)
+            }
+            InvariantError::MissingPiece(id) => {
+                write!(f, "`{}` is neither in hand nor on the grid", id)
+            }
+            InvariantError::DuplicatePiece(id) => write!(f, "`{}` is in two places", id),
+            InvariantError::PieceNotInGame(id) => write!(f, "`{}` is not in this game type", id),
+            InvariantError::WrongEdge { from, to, edge } => {
+                write!(f, "`{}` is not {:?} of `{}`", to, edge, from)
+            }
+            InvariantError::MissingEdge { from, to } => {
+                write!(f, "no edge between neighbours `{}` and `{}`", from, to)
+            }
+            InvariantError::DuplicateEdge { from, to } => {
+                write!(f, "more than one edge between `{}` and `{}`", from, to)
+            }
+            InvariantError::WrongPlayer {
+                move_number,
+                piece_id,
+            } => write!(
+                f,
+                "move {} plays `{}` on the other player's turn",
+                move_number, piece_id
+            ),
+            InvariantError::TooManyPlacements(color) => {
+                write!(f, "{:?} has placed more pieces than are on the grid", color)
+            }
+            InvariantError::TooManyPieces(color) => {
+                write!(f, "{:?} has more pieces on the grid than turns", color)
+            }
+            InvariantError::HistoryMismatch => {
+                write!(f, "the position history doesn't match the move list")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvariantError {}
+
+impl Game {
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        self.validate_grid()?;
+        self.validate_pieces()?;
+        self.validate_edges()?;
+        self.validate_move_list()?;
+        return Ok(());
+    }
+
+    // One hive, one piece per hex and no gaps in stacks
+    fn validate_grid(&self) -> Result<(), InvariantError> {
+        let pieces: Vec<Hex> = self.grid.node_weights().map(|p| p.hex).collect();
+        for (i, hex) in pieces.iter().enumerate() {
+            if pieces[..i].contains(hex) {
+                return Err(InvariantError::SharedHex(*hex));
+            }
+            let below = hex.with_z(hex.z - 1);
+            if hex.z < 0 || (hex.z > 0 && !pieces.contains(&below)) {
+                return Err(InvariantError::StackGap(*hex));
+            }
+        }
+        if !self.is_one_hive() {
+            return Err(InvariantError::HiveSplit);
+        }
+        return Ok(());
+    }
+
+    // Every piece of the game type exactly once, either in its hand or on the grid
+    fn validate_pieces(&self) -> Result<(), InvariantError> {
+        let mut seen: Vec<PieceId> = Vec::new();
+        for piece in self.grid.node_weights() {
+            if piece.in_hand {
+                return Err(InvariantError::OnGridInHand(piece.id));
+            }
+            if seen.contains(&piece.id) || self.hand(piece.id.color).contains(piece.id) {
+                return Err(InvariantError::DuplicatePiece(piece.id));
+            }
+            if !self.game_type.includes(piece.id.piece_type) {
+                return Err(InvariantError::PieceNotInGame(piece.id));
+            }
+            seen.push(piece.id);
+        }
+        for color in [PieceColor::White, PieceColor::Black] {
+            if let Some(id) = self
+                .hand(color)
+                .ids()
+                .into_iter()
+                .find(|id| !self.game_type.includes(id.piece_type))
+            {
+                return Err(InvariantError::PieceNotInGame(id));
+            }
+            for id in Hand::starting(color, self.game_type).ids() {
+                if !seen.contains(&id) && !self.hand(color).contains(id) {
+                    return Err(InvariantError::MissingPiece(id));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Each edge leads from its source piece to the piece in the edge's direction, and every pair
+    // of touching pieces (side by side at the same height, or one on top of the other) has exactly
+    // one edge
+    fn validate_edges(&self) -> Result<(), InvariantError> {
+        for edge in self.grid.edge_indices() {
+            let (a, b) = self
+                .grid
+                .edge_endpoints(edge)
+                .expect("Edges from edge_indices exist");
+            let direction = self.grid[edge];
+            let (a, b) = (&self.grid[a], &self.grid[b]);
+            if a.hex.get_neighbor(direction) != b.hex {
+                return Err(InvariantError::WrongEdge {
+                    from: a.id,
+                    to: b.id,
+                    edge: direction,
+                });
+            }
+        }
+        for node in self.grid.node_indices() {
+            for e in get_all_edge_types() {
+                let neighbor = match self.node_at(self.grid[node].hex.get_neighbor(e)) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                let (from, to) = (self.grid[node].id, self.grid[neighbor].id);
+                match self
+                    .grid
+                    .edges(node)
+                    .filter(|edge| edge.target() == neighbor)
+                    .count()
+                {
+                    0 => return Err(InvariantError::MissingEdge { from, to }),
+                    1 => {}
+                    _ => return Err(InvariantError::DuplicateEdge { from, to }),
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Moves alternate between the players, starting with white, and placements account for no
+    // more pieces than are on the grid (a game set up from a position has placements missing).
+    // Each player places and moves their own pieces, apart from the other player's pieces they
+    // throw with a pillbug.
+    fn validate_move_list(&self) -> Result<(), InvariantError> {
+        let mut placed = [0, 0];
+        for (i, m) in self.move_list.iter().enumerate() {
+            let mover = if (self.start_ply + i).is_multiple_of(2) {
+                PieceColor::White
+            } else {
+                PieceColor::Black
+            };
+            let wrong_player = |piece_id: PieceId| InvariantError::WrongPlayer {
+                move_number: i + 1,
+                piece_id,
+            };
+            match m {
+                PieceMove::Place { piece_id, .. } => {
+                    if piece_id.color != mover {
+                        return Err(wrong_player(*piece_id));
+                    }
+                    placed[mover as usize] += 1;
+                }
+                PieceMove::Move {
+                    piece_id,
+                    from,
+                    hex,
+                } => {
+                    if piece_id.color != mover && !self.could_be_throw(mover, *from, *hex) {
+                        return Err(wrong_player(*piece_id));
+                    }
+                }
+                PieceMove::Pass => {}
+            }
+        }
+        for color in [PieceColor::White, PieceColor::Black] {
+            let on_grid = self
+                .grid
+                .node_weights()
+                .filter(|p| p.id.color == color)
+                .count();
+            if placed[color as usize] > on_grid {
+                return Err(InvariantError::TooManyPlacements(color));
+            }
+            if on_grid > self.turns_taken(color) {
+                return Err(InvariantError::TooManyPieces(color));
+            }
+        }
+        if self.position_history().len() != self.move_list.len() {
+            return Err(InvariantError::HistoryMismatch);
+        }
+        return Ok(());
+    }
+
+    // Whether thrower could have moved a piece from from to to with a pillbug (or a mosquito
+    // copying one): thrown pieces go from ground level to ground level around the pillbug, so
+    // they end up at most two cells from where they started. Where the pillbug was at the time
+    // isn't known, only that thrower has one on the grid now.
+    fn could_be_throw(&self, thrower: PieceColor, from: Hex, to: Hex) -> bool {
+        let has_pillbug = self.grid.node_weights().any(|p| {
+            p.id.color == thrower
+                && matches!(p.id.piece_type, PieceType::Pillbug | PieceType::Mosquito)
+        });
+        return has_pillbug && from.z == 0 && to.z == 0 && (1..=2).contains(&from.distance(to));
+    }
+
+    // How many turns color has had, counting any made before the move list starts
+    fn turns_taken(&self, color: PieceColor) -> usize {
+        match color {
+            PieceColor::White => return self.ply().div_ceil(2),
+            PieceColor::Black => return self.ply() / 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_type::GameType;
+    use crate::game::piece::Piece;
+    use crate::game::random::{random_game, RandomGameConfig, Weighting};
+    use crate::game::uhp::id_from_uhp_name;
+
+    // wQ, bQ and wA1 in a triangle with bA1 off to the side
+    fn triangle() -> Game {
+        let mut game = Game::with_type(GameType::BASE);
+        for move_string in ["wQ", "bQ wQ-", "wA1 -wQ", "bA1 bQ-", "wA1 wQ\\"] {
+            let m = game.parse_move_string(move_string).unwrap();
+            game.try_make_move(m).unwrap();
+        }
+        return game;
+    }
+
+    fn id(s: &str) -> PieceId {
+        return id_from_uhp_name(s).unwrap();
+    }
+
+    #[test]
+    fn random_games_are_valid() {
+        for seed in 0..10 {
+            let config = RandomGameConfig {
+                seed,
+                max_plies: 60,
+                ..RandomGameConfig::default()
+            };
+            assert_eq!(random_game(&config).validate(), Ok(()));
+        }
+        assert_eq!(triangle().validate(), Ok(()));
+    }
+
+    #[test]
+    fn finds_broken_grids() {
+        let mut game = triangle();
+        let hex = game.grid[game.node_of(id("wQ")).unwrap()].hex;
+        game.grid.add_node(Piece {
+            id: id("wB1"),
+            hex,
+            in_hand: false,
+        });
+        assert_eq!(game.validate(), Err(InvariantError::SharedHex(hex)));
+
+        let mut game = triangle();
+        let node = game.node_of(id("bA1")).unwrap();
+        game.grid[node].hex.z = 1;
+        let hex = game.grid[node].hex;
+        assert_eq!(game.validate(), Err(InvariantError::StackGap(hex)));
+
+        let mut game = triangle();
+        let node = game.node_of(id("bA1")).unwrap();
+        while let Some(edge) = game.grid.first_edge(node, petgraph::Direction::Outgoing) {
+            game.grid.remove_edge(edge);
+        }
+        assert_eq!(game.validate(), Err(InvariantError::HiveSplit));
+    }
+
+    #[test]
+    fn finds_pieces_in_the_wrong_place() {
+        let mut game = triangle();
+        let node = game.node_of(id("wA1")).unwrap();
+        game.grid[node].in_hand = true;
+        assert_eq!(
+            game.validate(),
+            Err(InvariantError::OnGridInHand(id("wA1")))
+        );
+
+        let mut game = triangle();
+        assert!(game.p2_hand.put_back(id("wQ")));
+        assert_eq!(
+            game.validate(),
+            Err(InvariantError::DuplicatePiece(id("wQ")))
+        );
+
+        let mut game = triangle();
+        assert!(game.p2_hand.take(id("wB1")));
+        assert_eq!(
+            game.validate(),
+            Err(InvariantError::MissingPiece(id("wB1")))
+        );
+    }
+
+    #[test]
+    fn finds_broken_edges() {
+        let (wq, bq) = (id("wQ"), id("bQ"));
+        let mut game = triangle();
+        let (a, b) = (game.node_of(wq).unwrap(), game.node_of(bq).unwrap());
+        let edge = game.grid.find_edge(a, b).unwrap();
+        game.grid.remove_edge(edge);
+        assert!(matches!(
+            game.validate(),
+            Err(InvariantError::MissingEdge { .. })
+        ));
+
+        let mut game = triangle();
+        let edge = game.grid.find_edge(a, b).unwrap();
+        let (from, to) = game.grid.edge_endpoints(edge).unwrap();
+        let direction = game.grid[edge];
+        game.grid.add_edge(from, to, direction);
+        assert!(matches!(
+            game.validate(),
+            Err(InvariantError::DuplicateEdge { .. })
+        ));
+
+        let mut game = triangle();
+        let edge = game.grid.find_edge(a, b).unwrap();
+        let (from, to) = game.grid.edge_endpoints(edge).unwrap();
+        let wrong = get_all_edge_types()
+            .into_iter()
+            .find(|&e| e != game.grid[edge])
+            .unwrap();
+        game.grid[edge] = wrong;
+        assert_eq!(
+            game.validate(),
+            Err(InvariantError::WrongEdge {
+                from: game.grid[from].id,
+                to: game.grid[to].id,
+                edge: wrong,
+            })
+        );
+    }
+
+    #[test]
+    fn finds_move_lists_that_dont_fit() {
+        let mut game = triangle();
+        game.move_list.swap(0, 1);
+        assert_eq!(
+            game.validate(),
+            Err(InvariantError::WrongPlayer {
+                move_number: 1,
+                piece_id: id("bQ"),
+            })
+        );
+
+        let mut game = triangle();
+        game.move_list[4] = PieceMove::Place {
+            piece_id: id("wB1"),
+            hex: Hex::new(),
+        };
+        assert_eq!(
+            game.validate(),
+            Err(InvariantError::TooManyPlacements(PieceColor::White))
+        );
+
+        let mut game = triangle();
+        game.move_list.push(PieceMove::Pass);
+        assert_eq!(game.validate(), Err(InvariantError::HistoryMismatch));
+
+        // Black moving twice in a row, with no pillbug to throw its own ant on white's turn
+        let mut game = triangle();
+        let ant = game.get_piece(id("bA1")).unwrap().hex;
+        game.move_list[4] = PieceMove::Move {
+            piece_id: id("bA1"),
+            from: ant,
+            hex: ant.get_neighbor(HexEdge::N),
+        };
+        assert_eq!(
+            game.validate(),
+            Err(InvariantError::WrongPlayer {
+                move_number: 5,
+                piece_id: id("bA1"),
+            })
+        );
+    }
+
+    #[test]
+    fn pillbugs_move_the_other_players_pieces() {
+        let mut throws = 0;
+        for seed in 0..20 {
+            let config = RandomGameConfig {
+                seed,
+                weighting: Weighting::Aggressive,
+                ..RandomGameConfig::default()
+            };
+            let game = random_game(&config);
+            throws += game
+                .move_list
+                .iter()
+                .enumerate()
+                .filter(|(i, m)| {
+                    let mover = if i % 2 == 0 {
+                        PieceColor::White
+                    } else {
+                        PieceColor::Black
+                    };
+                    matches!(m, PieceMove::Move { piece_id, .. } if piece_id.color != mover)
+                })
+                .count();
+            assert_eq!(game.validate(), Ok(()));
+        }
+        assert!(throws > 0);
+    }
+}
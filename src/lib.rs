@@ -0,0 +1,7 @@
+// Explicit `return`s are the house style throughout the crate.
+#![allow(clippy::needless_return)]
+
+pub mod engine;
+pub mod game;
+pub mod rng;
+pub mod svg;
@@ -1,65 +1,662 @@
-use crate::game::{
+#![allow(clippy::needless_return)]
+
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use boudica::engine::book::OpeningBook;
+use boudica::engine::eval::WIN_SCORE;
+use boudica::engine::selfplay::{play_match_with, play_sprt, MatchConfig};
+use boudica::engine::stats::Sprt;
+use boudica::engine::surround::{prove_surround, SurroundProof};
+use boudica::engine::{Analysis, EngineConfig};
+use boudica::game::{
+    self,
+    game_type::GameType,
     hex::Hex,
     piece::{Piece, PieceColor, PieceType},
+    piece_move::PieceMove,
+    random::{random_game, RandomGameConfig, Weighting},
+    Game, GameError,
 };
-
-mod game;
+use boudica::rng::Rng;
+use boudica::svg::{write_svg, SvgOptions};
+use clap::builder::RangedU64ValueParser;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 
 // pub struct GameLog {
 //     turn: u32,
 //     moves: Vec<PieceMove>,
 // }
 
-fn main() {
-    let mut game = game::Game::new();
-    // let game_log = GameLog {
-    //     turn: 0,
-    //     moves: Vec::new(),
-    // };
+fn main() -> ExitCode {
+    let matches = Command::new("boudica")
+        .about("A Hive engine")
+        .subcommand(
+            Command::new("selfplay")
+                .about("Plays two engines against each other and reports the results")
+                .arg(engine_arg("a", "alphabeta:mobility:2"))
+                .arg(engine_arg("b", "alphabeta:queen:2"))
+                .arg(
+                    Arg::new("games")
+                        .long("games")
+                        .value_parser(value_parser!(usize))
+                        .default_value("100"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_parser(value_parser!(u64))
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::new("opening-moves")
+                        .long("opening-moves")
+                        .help("Random moves at the start of each pair of games")
+                        .value_parser(value_parser!(usize))
+                        .default_value("4"),
+                )
+                .arg(
+                    Arg::new("game-type")
+                        .long("game-type")
+                        .help("UHP game type, e.g. Base or Base+MLP")
+                        .default_value("Base+MLP"),
+                )
+                .arg(
+                    Arg::new("turn-limit")
+                        .long("turn-limit")
+                        .help("Turns each before a game is drawn (0 for no limit)")
+                        .value_parser(value_parser!(usize))
+                        .default_value("100"),
+                )
+                .arg(
+                    Arg::new("log")
+                        .long("log")
+                        .help("Print every game's GameString as it finishes")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sprt")
+                        .long("sprt")
+                        .help("Stop once an SPRT decides between elo0 and elo1 (--games is the most to play)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(sprt_arg("elo0", "0"))
+                .arg(sprt_arg("elo1", "10"))
+                .arg(sprt_arg("alpha", "0.05"))
+                .arg(sprt_arg("beta", "0.05"))
+                .arg(
+                    Arg::new("book")
+                        .long("book")
+                        .help("Opening book for both engines to play from"),
+                ),
+        )
+        .subcommand(
+            Command::new("book")
+                .about("Builds and inspects opening books")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("build")
+                        .about("Builds a book from game records (.sgf files, or GameStrings one per line)")
+                        .arg(Arg::new("records").required(true).num_args(1..))
+                        .arg(Arg::new("out").long("out").short('o').required(true))
+                        .arg(
+                            Arg::new("plies")
+                                .long("plies")
+                                .help("How many moves into each game to take")
+                                .value_parser(value_parser!(usize))
+                                .default_value("12"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Lists a book's moves for a position")
+                        .arg(Arg::new("book").required(true))
+                        .arg(
+                            Arg::new("game-string")
+                                .long("game-string")
+                                .help("The position, as a GameString (default: the start of a game)"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("random")
+                .about("Plays random games and prints their GameStrings, one per line")
+                .arg(
+                    Arg::new("count")
+                        .long("count")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .help("Seed of the first game (each game after uses the next one)")
+                        .value_parser(value_parser!(u64))
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::new("plies")
+                        .long("plies")
+                        .help("Moves (counting both players) before a game is stopped")
+                        .value_parser(value_parser!(usize))
+                        .default_value("200"),
+                )
+                .arg(
+                    Arg::new("game-type")
+                        .long("game-type")
+                        .help("UHP game type, e.g. Base or Base+MLP")
+                        .default_value("Base+MLP"),
+                )
+                .arg(
+                    Arg::new("weighting")
+                        .long("weighting")
+                        .help("uniform, or aggressive to favour moves next to the enemy queen")
+                        .default_value("uniform"),
+                )
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Check the game is valid after every move (see Game::validate)")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("analyze")
+                .about("Prints statistics, queen danger and the engine's best moves for a position")
+                .arg(
+                    Arg::new("game-string")
+                        .required(true)
+                        .help("The position, as a GameString"),
+                )
+                .arg(engine_arg("engine", "alphabeta:mobility:2"))
+                .arg(multi_pv_arg("3")),
+        )
+        .subcommand(
+            Command::new("svg")
+                .about("Draws a position as an SVG, with numbered moves on top")
+                .arg(
+                    Arg::new("game-string")
+                        .required(true)
+                        .help("The position, as a GameString"),
+                )
+                .arg(Arg::new("out").long("out").short('o').required(true))
+                .arg(
+                    Arg::new("moves")
+                        .long("moves")
+                        .help("MoveStrings to draw from the position, separated by semicolons"),
+                )
+                .arg(
+                    Arg::new("pv")
+                        .long("pv")
+                        .help("Draw the engine's best line from the position")
+                        .conflicts_with("moves")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(engine_arg("engine", "alphabeta:mobility:2")),
+        )
+        .subcommand(
+            Command::new("bestmove")
+                .about("Prints the engine's move for a position like UHP's bestmove")
+                .arg(
+                    Arg::new("game-string")
+                        .required(true)
+                        .help("The position, as a GameString"),
+                )
+                .arg(engine_arg("engine", "alphabeta:mobility:2"))
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .help("Search depth, at least 1 (default: the engine's)")
+                        .value_parser(RangedU64ValueParser::<usize>::new().range(1..)),
+                )
+                .arg(multi_pv_arg("1"))
+                .arg(
+                    Arg::new("lines")
+                        .long("lines")
+                        .help("Print the --multi-pv best lines, as analyze does, before the move")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("surround")
+                .about("Looks for a forced win by surrounding the enemy queen")
+                .arg(
+                    Arg::new("game-string")
+                        .required(true)
+                        .help("The position, as a GameString"),
+                )
+                .arg(
+                    Arg::new("moves")
+                        .long("moves")
+                        .help("Most of the attacker's own moves to look through")
+                        .value_parser(value_parser!(usize))
+                        .default_value("2"),
+                )
+                .arg(
+                    Arg::new("attacker")
+                        .long("attacker")
+                        .help("white or black (default: the player to move)"),
+                ),
+        )
+        .get_matches();
 
-    let first_piece = Piece::new(PieceColor::Black, PieceType::QueenBee, "q");
-    let second_piece = Piece::new(PieceColor::White, PieceType::QueenBee, "q");
-    let third_piece = Piece::new(PieceColor::White, PieceType::Grasshopper, "g1");
-    let cuarto_piece = Piece::new(PieceColor::Black, PieceType::Pillbug, "p");
-
-    let fmi = game.add_to_grid(
-        first_piece,
-        Hex {
-            q: 0,
-            r: 0,
-            s: 0,
-            z: 0,
+    let result = match matches.subcommand() {
+        Some(("selfplay", args)) => selfplay(args),
+        Some(("book", args)) => match args.subcommand() {
+            Some(("build", args)) => build_book(args),
+            Some(("show", args)) => show_book(args),
+            _ => unreachable!("clap requires a book subcommand"),
         },
+        Some(("random", args)) => random(args),
+        Some(("analyze", args)) => analyze(args),
+        Some(("svg", args)) => svg(args),
+        Some(("bestmove", args)) => bestmove(args),
+        Some(("surround", args)) => surround(args),
+        _ => demo().map_err(|e| e.to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    return ExitCode::SUCCESS;
+}
+
+fn engine_arg(name: &'static str, default: &'static str) -> Arg {
+    return Arg::new(name)
+        .long(name)
+        .help("Engine as search:evaluator:depth:surround (random, minimax or alphabeta; queen or mobility)")
+        .default_value(default);
+}
+
+fn multi_pv_arg(default: &'static str) -> Arg {
+    return Arg::new("multi-pv")
+        .long("multi-pv")
+        .help("How many of the best moves to search exactly and show")
+        .value_parser(value_parser!(usize))
+        .default_value(default);
+}
+
+fn sprt_arg(name: &'static str, default: &'static str) -> Arg {
+    return Arg::new(name)
+        .long(name)
+        .value_parser(value_parser!(f64))
+        .default_value(default);
+}
+
+fn selfplay(args: &ArgMatches) -> Result<(), String> {
+    let engine = |name: &str| -> Result<EngineConfig, String> {
+        let s = args.get_one::<String>(name).unwrap();
+        return EngineConfig::parse(s).ok_or_else(|| format!("unknown engine `{}`", s));
+    };
+    let (a, b) = (engine("a")?, engine("b")?);
+
+    let mut config = MatchConfig {
+        games: *args.get_one::<usize>("games").unwrap(),
+        seed: *args.get_one::<u64>("seed").unwrap(),
+        opening_moves: *args.get_one::<usize>("opening-moves").unwrap(),
+        ..MatchConfig::default()
+    };
+    let game_type = args.get_one::<String>("game-type").unwrap();
+    config.game_type =
+        GameType::parse(game_type).ok_or_else(|| format!("unknown game type `{}`", game_type))?;
+    config.rules.turn_limit = match *args.get_one::<usize>("turn-limit").unwrap() {
+        0 => None,
+        limit => Some(limit),
+    };
+
+    if let Some(path) = args.get_one::<String>("book") {
+        let book = OpeningBook::load(path).map_err(|e| format!("{}: {}", path, e))?;
+        config.book = Some(Rc::new(book));
+    }
+
+    let log = args.get_flag("log");
+    if args.get_flag("sprt") {
+        let sprt = Sprt {
+            elo0: *args.get_one::<f64>("elo0").unwrap(),
+            elo1: *args.get_one::<f64>("elo1").unwrap(),
+            alpha: *args.get_one::<f64>("alpha").unwrap(),
+            beta: *args.get_one::<f64>("beta").unwrap(),
+        };
+        let (lower, upper) = sprt.bounds();
+        let result = play_sprt(a, b, &config, sprt, |games, llr| {
+            if log {
+                for record in &games[games.len() - 2..] {
+                    println!("{}", record);
+                }
+            }
+            let score: f64 = games.iter().map(|g| g.score_for_a()).sum();
+            println!(
+                "{} games, A scored {}: LLR {:.2} ({:.2}, {:.2})",
+                games.len(),
+                score,
+                llr,
+                lower,
+                upper
+            );
+        });
+        println!("{}", result);
+        return Ok(());
+    }
+
+    let result = play_match_with(a, b, &config, |record| {
+        if log {
+            println!("{}", record);
+        }
+    });
+    println!("{}", result);
+    return Ok(());
+}
+
+fn build_book(args: &ArgMatches) -> Result<(), String> {
+    let mut book = OpeningBook::new(*args.get_one::<usize>("plies").unwrap());
+    let (mut added, mut skipped) = (0, 0);
+    for path in args.get_many::<String>("records").unwrap() {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let games: Vec<(String, Result<Game, GameError>)> =
+            if Path::new(path).extension().is_some_and(|e| e == "sgf") {
+                vec![(
+                    path.clone(),
+                    game::import_from_sgf(&text).map_err(GameError::from),
+                )]
+            } else {
+                text.lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .map(|(i, line)| {
+                        let game = game::import_from_game_string(line).map_err(GameError::from);
+                        return (format!("{}:{}", path, i + 1), game);
+                    })
+                    .collect()
+            };
+        for (source, game) in games {
+            match game.and_then(|g| book.add_game(&g)) {
+                Ok(()) => added += 1,
+                Err(e) => {
+                    eprintln!("skipping {}: {}", source, e);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let out = args.get_one::<String>("out").unwrap();
+    book.save(out).map_err(|e| format!("{}: {}", out, e))?;
+    println!(
+        "{} games ({} skipped): {} positions, {} moves",
+        added,
+        skipped,
+        book.position_count(),
+        book.move_count()
     );
-    let smi = game.add_to_grid(
-        second_piece,
-        Hex {
-            q: 0,
-            r: -1,
-            s: 1,
-            z: 0,
-        },
+    return Ok(());
+}
+
+fn show_book(args: &ArgMatches) -> Result<(), String> {
+    let path = args.get_one::<String>("book").unwrap();
+    let book = OpeningBook::load(path).map_err(|e| format!("{}: {}", path, e))?;
+    println!(
+        "{} positions, {} moves, {} plies deep",
+        book.position_count(),
+        book.move_count(),
+        book.plies()
     );
 
-    let tmi = game.add_to_grid(
-        third_piece,
-        Hex {
-            q: -1,
-            r: 0,
-            s: 1,
-            z: 0,
-        },
+    let game = match args.get_one::<String>("game-string") {
+        Some(s) => game::import_from_game_string(s).map_err(|e| e.to_string())?,
+        None => Game::new(),
+    };
+    let moves = book.moves(&game);
+    if moves.is_empty() {
+        println!("No book moves for this position");
+    }
+    for (m, entry) in moves {
+        println!(
+            "{:<12} {:>6} games  +{} -{} ={}  {:>5.1}%  weight {}",
+            game.move_string(&m),
+            entry.games,
+            entry.wins,
+            entry.losses,
+            entry.draws(),
+            100.0 * entry.score(),
+            entry.weight()
+        );
+    }
+    return Ok(());
+}
+
+fn random(args: &ArgMatches) -> Result<(), String> {
+    let game_type = args.get_one::<String>("game-type").unwrap();
+    let weighting = args.get_one::<String>("weighting").unwrap();
+    let mut config = RandomGameConfig {
+        max_plies: *args.get_one::<usize>("plies").unwrap(),
+        game_type: GameType::parse(game_type)
+            .ok_or_else(|| format!("unknown game type `{}`", game_type))?,
+        weighting: Weighting::parse(weighting)
+            .ok_or_else(|| format!("unknown weighting `{}`", weighting))?,
+        ..RandomGameConfig::default()
+    };
+    let first_seed = *args.get_one::<u64>("seed").unwrap();
+    for seed in first_seed..first_seed + *args.get_one::<u64>("count").unwrap() {
+        config.seed = seed;
+        let game = random_game(&config);
+        if args.get_flag("check") {
+            check_random_game(&game).map_err(|e| format!("seed {}: {}", seed, e))?;
+        }
+        println!("{}", game.export_game_string().map_err(|e| e.to_string())?);
+    }
+    return Ok(());
+}
+
+// Replays game checking Game::validate after each move
+fn check_random_game(game: &Game) -> Result<(), String> {
+    let mut replay = Game::with_type(game.game_type);
+    for (i, m) in game.move_list.iter().enumerate() {
+        replay.make_move(m.clone()).map_err(|e| e.to_string())?;
+        replay
+            .validate()
+            .map_err(|e| format!("after move {}: {}", i + 1, e))?;
+    }
+    return Ok(());
+}
+
+fn analyze(args: &ArgMatches) -> Result<(), String> {
+    let game = game::import_from_game_string(args.get_one::<String>("game-string").unwrap())
+        .map_err(|e| e.to_string())?;
+    println!(
+        "{:?}, {:?} to move on turn {}",
+        game.get_state(),
+        game.current_player(),
+        game.turn_number()
     );
+    println!("{}", game.position_stats());
 
-    let cmi = game.add_to_grid(
-        cuarto_piece,
-        Hex {
-            q: -1,
-            r: -1,
-            s: 2,
-            z: 0,
-        },
+    for color in [PieceColor::White, PieceColor::Black] {
+        let danger = match game.queen_danger(color) {
+            Some(danger) => danger,
+            None => {
+                println!("{:?} queen: not placed", color);
+                continue;
+            }
+        };
+        println!(
+            "{:?} queen: {} of 6 neighbours occupied, {} pinned",
+            color,
+            danger.occupied,
+            danger.pinned.len()
+        );
+        for n in &danger.empty {
+            let pieces: Vec<String> = n.pieces().iter().map(|id| id.to_string()).collect();
+            println!(
+                "  ({}, {}): {} white / {} black moves onto it ({})",
+                n.hex.q(),
+                n.hex.r(),
+                n.white.len(),
+                n.black.len(),
+                pieces.join(" ")
+            );
+        }
+    }
+
+    let engine = parse_engine(args)?;
+    let multi_pv = *args.get_one::<usize>("multi-pv").unwrap();
+    let analysis = match engine.analyze(&game, multi_pv, &mut Rng::new(0)) {
+        Some(analysis) => analysis,
+        None => return Ok(()),
+    };
+    print!("{}", analysis_string(&engine, &game, &analysis)?);
+    return Ok(());
+}
+
+// The search's statistics and then each of its best lines, one per line
+fn analysis_string(
+    engine: &EngineConfig,
+    game: &Game,
+    analysis: &Analysis,
+) -> Result<String, String> {
+    let mut s = format!(
+        "{}: depth {}, {} nodes in {} ms\n",
+        engine,
+        analysis.depth,
+        analysis.nodes,
+        analysis.time.as_millis()
     );
+    for (i, line) in analysis.lines.iter().enumerate() {
+        s += &format!(
+            "  {}. {} ({}): {}\n",
+            i + 1,
+            game.move_string(line.piece_move()),
+            score_string(line.score),
+            line_string(game, &line.pv)?
+        );
+    }
+    return Ok(s);
+}
+
+fn svg(args: &ArgMatches) -> Result<(), String> {
+    let game = game::import_from_game_string(args.get_one::<String>("game-string").unwrap())
+        .map_err(|e| e.to_string())?;
+    let mut line: Vec<PieceMove> = Vec::new();
+    if let Some(moves) = args.get_one::<String>("moves") {
+        let mut replay = game.clone();
+        for move_string in moves.split(';') {
+            let m = replay
+                .parse_move_string(move_string)
+                .map_err(|e| format!("`{}`: {}", move_string, e))?;
+            replay.make_move(m.clone()).map_err(|e| e.to_string())?;
+            line.push(m);
+        }
+    } else if args.get_flag("pv") {
+        let engine = parse_engine(args)?;
+        let analysis = engine
+            .analyze(&game, 1, &mut Rng::new(0))
+            .ok_or_else(|| String::from("the game is over"))?;
+        line = analysis.lines[0].pv.clone();
+    }
+
+    let mut options = SvgOptions::default();
+    options.add_moves(&line, true);
+    let path = args.get_one::<String>("out").unwrap();
+    return write_svg(&game, Path::new(path), &options).map_err(|e| format!("{}: {}", path, e));
+}
+
+fn bestmove(args: &ArgMatches) -> Result<(), String> {
+    let game = game::import_from_game_string(args.get_one::<String>("game-string").unwrap())
+        .map_err(|e| e.to_string())?;
+    let mut engine = parse_engine(args)?;
+    if let Some(&depth) = args.get_one::<usize>("depth") {
+        engine.depth = depth;
+    }
+    let multi_pv = *args.get_one::<usize>("multi-pv").unwrap();
+    let analysis = engine
+        .analyze(&game, multi_pv, &mut Rng::new(0))
+        .ok_or_else(|| String::from("the game is over"))?;
+    if args.get_flag("lines") {
+        print!("{}", analysis_string(&engine, &game, &analysis)?);
+    }
+    print!("{}", bestmove_string(&game, &analysis));
+    return Ok(());
+}
+
+// UHP's answer to bestmove: the MoveString and then ok
+fn bestmove_string(game: &Game, analysis: &Analysis) -> String {
+    return format!("{}\nok\n", game.move_string(analysis.lines[0].piece_move()));
+}
+
+fn parse_engine(args: &ArgMatches) -> Result<EngineConfig, String> {
+    let s = args.get_one::<String>("engine").unwrap();
+    return EngineConfig::parse(s).ok_or_else(|| format!("unknown engine `{}`", s));
+}
+
+// The moves of a line from game as MoveStrings, separated by semicolons like a GameString
+fn line_string(game: &Game, line: &[PieceMove]) -> Result<String, String> {
+    let mut replay = game.clone();
+    let mut move_strings: Vec<String> = Vec::new();
+    for m in line {
+        move_strings.push(replay.move_string(m));
+        replay.make_move(m.clone()).map_err(|e| e.to_string())?;
+    }
+    return Ok(move_strings.join(";"));
+}
+
+// Scores close to WIN_SCORE are wins (or losses) that many moves away
+fn score_string(score: i32) -> String {
+    let plies = WIN_SCORE - score.abs();
+    if plies > 1000 {
+        return score.to_string();
+    }
+    if score > 0 {
+        return format!("win in {}", (plies + 1) / 2);
+    }
+    return format!("loss in {}", (plies + 1) / 2);
+}
+
+fn surround(args: &ArgMatches) -> Result<(), String> {
+    let game = game::import_from_game_string(args.get_one::<String>("game-string").unwrap())
+        .map_err(|e| e.to_string())?;
+    let attacker = match args.get_one::<String>("attacker").map(|s| s.as_str()) {
+        None => game.current_player(),
+        Some("white") => PieceColor::White,
+        Some("black") => PieceColor::Black,
+        Some(s) => return Err(format!("unknown colour `{}`", s)),
+    };
+    let max_moves = *args.get_one::<usize>("moves").unwrap();
+
+    match prove_surround(&game, attacker, max_moves) {
+        SurroundProof::Win { moves, line } => println!(
+            "{:?} surrounds the queen in {} moves: {}",
+            attacker,
+            moves,
+            line_string(&game, &line)?
+        ),
+        SurroundProof::NoWin => println!(
+            "{:?} can't force a surround within {} moves",
+            attacker, max_moves
+        ),
+    }
+    return Ok(());
+}
+
+// Lists the moves available in a small hand-built position
+fn demo() -> Result<(), GameError> {
+    let mut game = game::Game::new();
+    // let game_log = GameLog {
+    //     turn: 0,
+    //     moves: Vec::new(),
+    // };
+
+    let first_piece = Piece::new(PieceColor::Black, PieceType::QueenBee, 1);
+    let second_piece = Piece::new(PieceColor::White, PieceType::QueenBee, 1);
+    let third_piece = Piece::new(PieceColor::White, PieceType::Grasshopper, 1);
+    let cuarto_piece = Piece::new(PieceColor::Black, PieceType::Pillbug, 1);
+
+    game.add_to_grid(first_piece, Hex::from_axial(0, 0))?;
+    game.add_to_grid(second_piece, Hex::from_axial(0, -1))?;
+
+    game.add_to_grid(third_piece, Hex::from_axial(-1, 0))?;
+
+    game.add_to_grid(cuarto_piece, Hex::from_axial(-1, -1))?;
 
     let legal_moves = game.get_all_moves();
 
@@ -82,4 +679,20 @@ fn main() {
      *      fns: update_graph, update_hands
      *
      * */
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bestmove_prints_only_the_move_and_ok() {
+        let game = game::import_from_game_string("Base;InProgress;White[2];wQ;bQ wQ-").unwrap();
+        let analysis = EngineConfig::default()
+            .analyze(&game, 3, &mut Rng::new(0))
+            .unwrap();
+        let best = game.move_string(analysis.lines[0].piece_move());
+        assert_eq!(bestmove_string(&game, &analysis), format!("{}\nok\n", best));
+    }
 }
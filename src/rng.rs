@@ -0,0 +1,30 @@
+// A small seedable random number generator (SplitMix64), so that anything random we do (self-play
+// openings, random games) comes out the same for the same seed on every platform and build.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        return Rng { state: seed };
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        return z ^ (z >> 31);
+    }
+
+    // Uniform in 0..n (n has to be at least 1)
+    pub fn below(&mut self, n: usize) -> usize {
+        debug_assert!(n > 0);
+        return (self.next_u64() % n as u64) as usize;
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        return &items[self.below(items.len())];
+    }
+}
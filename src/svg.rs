@@ -0,0 +1,440 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::game::hex::Hex;
+use crate::game::piece::{Piece, PieceColor, PieceId, PieceType};
+use crate::game::piece_move::PieceMove;
+use crate::game::Game;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arrow {
+    pub from: Hex,
+    pub to: Hex,
+    pub label: Option<String>,
+}
+
+// A piece put down from a hand, drawn as a dashed outline of it on the hex it goes to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub piece_id: PieceId,
+    pub hex: Hex,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    // Distance from the centre of a tile to any of its corners, in pixels
+    pub hex_size: f64,
+    pub arrows: Vec<Arrow>,
+    pub placements: Vec<Placement>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        return SvgOptions {
+            hex_size: 40.0,
+            arrows: Vec::new(),
+            placements: Vec::new(),
+        };
+    }
+}
+
+impl SvgOptions {
+    // Adds an arrow for each move and a placement for each piece put down; passes draw nothing.
+    // Works for a game's move_list as well as moves that haven't been played yet. Set `numbered`
+    // to label them 1, 2, 3... in the order they're played (e.g. for a principal variation),
+    // carrying on from whatever is already drawn.
+    pub fn add_moves(&mut self, moves: &[PieceMove], numbered: bool) {
+        for m in moves {
+            let label = if numbered {
+                Some((self.arrows.len() + self.placements.len() + 1).to_string())
+            } else {
+                None
+            };
+            match m {
+                PieceMove::Place { piece_id, hex } => self.placements.push(Placement {
+                    piece_id: *piece_id,
+                    hex: *hex,
+                    label,
+                }),
+                PieceMove::Move { from, hex, .. } => self.arrows.push(Arrow {
+                    from: *from,
+                    to: *hex,
+                    label,
+                }),
+                PieceMove::Pass => {}
+            }
+        }
+    }
+}
+
+pub fn write_svg(game: &Game, path: &Path, options: &SvgOptions) -> io::Result<()> {
+    return fs::write(path, render_svg(game, options));
+}
+
+pub fn render_svg(game: &Game, options: &SvgOptions) -> String {
+    let size = options.hex_size;
+
+    // Group pieces into stacks (bottom -> top), keyed by their position on the ground
+    let mut stacks: Vec<Vec<&Piece>> = Vec::new();
+    for piece in game.grid.node_weights() {
+        match stacks
+            .iter_mut()
+            .find(|stack| stack[0].hex.same_cell(piece.hex))
+        {
+            Some(stack) => stack.push(piece),
+            None => stacks.push(vec![piece]),
+        }
+    }
+    for stack in stacks.iter_mut() {
+        stack.sort_by_key(|p| p.hex.z);
+    }
+    // Draw left -> right, top -> bottom so the output is stable between runs
+    stacks.sort_by_key(|stack| (stack[0].hex.q(), stack[0].hex.r()));
+
+    // Empty hexes that an arrow points at get a faint outline
+    // (placements draw their own)
+    let mut targets: Vec<Hex> = Vec::new();
+    for arrow in &options.arrows {
+        if !stacks.iter().any(|s| s[0].hex.same_cell(arrow.to))
+            && !targets.iter().any(|t| t.same_cell(arrow.to))
+            && !options.placements.iter().any(|p| p.hex.same_cell(arrow.to))
+        {
+            targets.push(arrow.to);
+        }
+    }
+
+    // Bounding box over every hex we are going to draw
+    let mut centres: Vec<(f64, f64)> = stacks.iter().map(|s| s[0].hex.to_pixel(size)).collect();
+    centres.extend(targets.iter().map(|t| t.to_pixel(size)));
+    centres.extend(options.arrows.iter().map(|a| a.from.to_pixel(size)));
+    centres.extend(options.placements.iter().map(|p| p.hex.to_pixel(size)));
+    if centres.is_empty() {
+        centres.push((0.0, 0.0));
+    }
+    let margin = size * 1.5;
+    let min_x = centres.iter().map(|c| c.0).fold(f64::INFINITY, f64::min) - margin;
+    let max_x = centres
+        .iter()
+        .map(|c| c.0)
+        .fold(f64::NEG_INFINITY, f64::max)
+        + margin;
+    let min_y = centres.iter().map(|c| c.1).fold(f64::INFINITY, f64::min) - margin;
+    let max_y = centres
+        .iter()
+        .map(|c| c.1)
+        .fold(f64::NEG_INFINITY, f64::max)
+        + margin;
+
+    let mut svg = String::new();
+    svg.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{:.1} {:.1} {:.1} {:.1}\" width=\"{:.0}\" height=\"{:.0}\">\n",
+        min_x,
+        min_y,
+        max_x - min_x,
+        max_y - min_y,
+        max_x - min_x,
+        max_y - min_y
+    ));
+    svg.push_str(
+        "<defs><marker id=\"arrowhead\" viewBox=\"0 0 10 10\" refX=\"8\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto-start-reverse\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#c0392b\"/></marker></defs>\n",
+    );
+    svg.push_str(&format!(
+        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#ffffff\"/>\n",
+        min_x,
+        min_y,
+        max_x - min_x,
+        max_y - min_y
+    ));
+
+    for target in &targets {
+        let (x, y) = target.to_pixel(size);
+        svg.push_str(&format!(
+            "<polygon points=\"{}\" fill=\"none\" stroke=\"#bbbbbb\" stroke-width=\"2\" stroke-dasharray=\"4 3\"/>\n",
+            hex_points(x, y, size * 0.95)
+        ));
+    }
+
+    for stack in &stacks {
+        svg.push_str(&render_stack(stack, size));
+    }
+
+    for placement in &options.placements {
+        svg.push_str(&render_placement(placement, size));
+    }
+
+    for arrow in &options.arrows {
+        svg.push_str(&render_arrow(arrow, size));
+    }
+
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+fn render_stack(stack: &[&Piece], size: f64) -> String {
+    let top = stack[stack.len() - 1];
+    let (x, y) = top.hex.to_pixel(size);
+    let (fill, text) = match top.id.color {
+        PieceColor::White => ("#f4ecd8", "#2b2b2b"),
+        PieceColor::Black => ("#2b2b2b", "#f4ecd8"),
+    };
+    let ids: Vec<String> = stack.iter().map(|p| p.id.to_string()).collect();
+
+    let mut out = String::new();
+    out.push_str(&format!("<g><title>{}</title>\n", ids.join(" < ")));
+    out.push_str(&format!(
+        "<polygon points=\"{}\" fill=\"{}\" stroke=\"#666666\" stroke-width=\"2\"/>\n",
+        hex_points(x, y, size * 0.95),
+        fill
+    ));
+    out.push_str(&format!(
+        "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-weight=\"bold\" font-size=\"{:.1}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"{}\">{}</text>\n",
+        x,
+        y - size * 0.1,
+        size * 0.7,
+        bug_color(top.id.piece_type),
+        bug_glyph(top.id.piece_type)
+    ));
+    out.push_str(&format!(
+        "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-size=\"{:.1}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"{}\">{}</text>\n",
+        x,
+        y + size * 0.5,
+        size * 0.28,
+        text,
+        escape(&top.id.to_string())
+    ));
+
+    // Stack indicator: a badge with the height of the stack, plus a coloured pip per covered piece
+    if stack.len() > 1 {
+        let (bx, by) = (x + size * 0.5, y - size * 0.55);
+        out.push_str(&format!(
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"#c0392b\"/>\n",
+            bx,
+            by,
+            size * 0.22
+        ));
+        out.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-weight=\"bold\" font-size=\"{:.1}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"#ffffff\">{}</text>\n",
+            bx,
+            by,
+            size * 0.28,
+            stack.len()
+        ));
+        for (i, covered) in stack[..stack.len() - 1].iter().enumerate() {
+            let pip_fill = match covered.id.color {
+                PieceColor::White => "#f4ecd8",
+                PieceColor::Black => "#2b2b2b",
+            };
+            out.push_str(&format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+                x - size * 0.55 + (i as f64) * size * 0.2,
+                y - size * 0.55,
+                size * 0.09,
+                pip_fill,
+                bug_color(covered.id.piece_type)
+            ));
+        }
+    }
+    out.push_str("</g>\n");
+    return out;
+}
+
+fn render_placement(placement: &Placement, size: f64) -> String {
+    let (x, y) = placement.hex.to_pixel(size);
+    let stroke = match placement.piece_id.color {
+        PieceColor::White => "#b8a98a",
+        PieceColor::Black => "#2b2b2b",
+    };
+
+    let mut out = format!("<g><title>{}</title>\n", placement.piece_id);
+    out.push_str(&format!(
+        "<polygon points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"3\" stroke-dasharray=\"6 4\"/>\n",
+        hex_points(x, y, size * 0.9),
+        stroke
+    ));
+    out.push_str(&format!(
+        "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-weight=\"bold\" font-size=\"{:.1}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"{}\" fill-opacity=\"0.6\">{}</text>\n",
+        x,
+        y - size * 0.1,
+        size * 0.7,
+        bug_color(placement.piece_id.piece_type),
+        bug_glyph(placement.piece_id.piece_type)
+    ));
+    if let Some(label) = &placement.label {
+        out.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-weight=\"bold\" font-size=\"{:.1}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"#c0392b\" stroke=\"#ffffff\" stroke-width=\"3\" paint-order=\"stroke\">{}</text>\n",
+            x,
+            y + size * 0.5,
+            size * 0.35,
+            escape(label)
+        ));
+    }
+    out.push_str("</g>\n");
+    return out;
+}
+
+fn render_arrow(arrow: &Arrow, size: f64) -> String {
+    let (x1, y1) = arrow.from.to_pixel(size);
+    let (x2, y2) = arrow.to.to_pixel(size);
+
+    // Pull the ends in slightly so the arrow doesn't hide the glyphs underneath
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length = (dx * dx + dy * dy).sqrt();
+    let trim = if length > 0.0 {
+        (size * 0.35).min(length / 3.0) / length
+    } else {
+        0.0
+    };
+
+    let mut out = format!(
+        "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#c0392b\" stroke-width=\"{:.1}\" stroke-opacity=\"0.85\" marker-end=\"url(#arrowhead)\"/>\n",
+        x1 + dx * trim,
+        y1 + dy * trim,
+        x2 - dx * trim,
+        y2 - dy * trim,
+        size * 0.1
+    );
+    if let Some(label) = &arrow.label {
+        out.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" font-weight=\"bold\" font-size=\"{:.1}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"#c0392b\" stroke=\"#ffffff\" stroke-width=\"3\" paint-order=\"stroke\">{}</text>\n",
+            (x1 + x2) / 2.0,
+            (y1 + y2) / 2.0,
+            size * 0.35,
+            escape(label)
+        ));
+    }
+    return out;
+}
+
+fn hex_points(x: f64, y: f64, size: f64) -> String {
+    let corners: Vec<String> = (0..6)
+        .map(|i| {
+            let angle = std::f64::consts::PI / 3.0 * i as f64;
+            format!(
+                "{:.1},{:.1}",
+                x + size * angle.cos(),
+                y + size * angle.sin()
+            )
+        })
+        .collect();
+    return corners.join(" ");
+}
+
+fn bug_glyph(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::QueenBee => return 'Q',
+        PieceType::Ant => return 'A',
+        PieceType::Beetle => return 'B',
+        PieceType::Grasshopper => return 'G',
+        PieceType::Spider => return 'S',
+        PieceType::Ladybug => return 'L',
+        PieceType::Pillbug => return 'P',
+        PieceType::Mosquito => return 'M',
+    }
+}
+
+fn bug_color(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::QueenBee => return "#e0a800",
+        PieceType::Ant => return "#2e7dd7",
+        PieceType::Beetle => return "#8e44ad",
+        PieceType::Grasshopper => return "#27ae60",
+        PieceType::Spider => return "#8b4513",
+        PieceType::Ladybug => return "#d62828",
+        PieceType::Pillbug => return "#16a085",
+        PieceType::Mosquito => return "#7f8c8d",
+    }
+}
+
+fn escape(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(q: isize, r: isize) -> Hex {
+        return Hex::from_axial(q, r);
+    }
+
+    // Two queens side by side
+    fn two_queens() -> Game {
+        let mut game = Game::new();
+        for (id, hex) in [("wq", hex(0, 0)), ("bq", hex(0, -1))] {
+            game.make_move(PieceMove::Place {
+                piece_id: PieceId::parse(id).unwrap(),
+                hex,
+            })
+            .unwrap();
+        }
+        return game;
+    }
+
+    #[test]
+    fn numbers_placements_and_moves_but_not_passes() {
+        let moves = [
+            PieceMove::Place {
+                piece_id: PieceId::parse("wa1").unwrap(),
+                hex: hex(0, 1),
+            },
+            PieceMove::Pass,
+            PieceMove::Move {
+                piece_id: PieceId::parse("wq").unwrap(),
+                from: hex(0, 0),
+                hex: hex(1, 0),
+            },
+        ];
+        let mut options = SvgOptions::default();
+        options.add_moves(&moves, true);
+        let label = |n: &str| Some(String::from(n));
+        assert_eq!(
+            options.placements,
+            [Placement {
+                piece_id: PieceId::parse("wa1").unwrap(),
+                hex: hex(0, 1),
+                label: label("1"),
+            }]
+        );
+        assert_eq!(
+            options.arrows,
+            [Arrow {
+                from: hex(0, 0),
+                to: hex(1, 0),
+                label: label("2"),
+            }]
+        );
+
+        let mut unnumbered = SvgOptions::default();
+        unnumbered.add_moves(&moves, false);
+        assert!(unnumbered.arrows.iter().all(|a| a.label.is_none()));
+        assert!(unnumbered.placements.iter().all(|p| p.label.is_none()));
+    }
+
+    #[test]
+    fn renders_placements_and_arrows() {
+        let game = two_queens();
+        let pv = [
+            PieceMove::Place {
+                piece_id: PieceId::parse("wa1").unwrap(),
+                hex: hex(0, 1),
+            },
+            PieceMove::Move {
+                piece_id: PieceId::parse("wq").unwrap(),
+                from: hex(0, 0),
+                hex: hex(1, 0),
+            },
+        ];
+        let mut options = SvgOptions::default();
+        options.add_moves(&pv, true);
+        let svg = render_svg(&game, &options);
+        assert_eq!(svg.matches("marker-end").count(), 1);
+        assert_eq!(svg.matches("stroke-dasharray=\"6 4\"").count(), 1);
+        assert!(svg.contains(">1</text>") && svg.contains(">2</text>"));
+    }
+}